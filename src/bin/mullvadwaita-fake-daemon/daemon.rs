@@ -0,0 +1,405 @@
+use std::{
+    net::{Ipv4Addr, SocketAddr},
+    sync::{Arc, Mutex, MutexGuard},
+    time::Duration,
+};
+
+use chrono::{DateTime, TimeDelta, Utc};
+use mullvad_management_interface::types::{self, daemon_event::Event};
+use mullvad_types::{
+    account::AccountNumber,
+    device::{
+        AccountAndDevice, Device, DeviceEvent, DeviceEventCause, DeviceState, RemoveDeviceEvent,
+    },
+    features::FeatureIndicators,
+    location::GeoIpLocation,
+    relay_list::{RelayEndpointData, RelayList},
+    settings::Settings,
+    states::TunnelState,
+    version::AppVersionInfo,
+};
+use talpid_types::net::{
+    wireguard::PrivateKey, Endpoint, TransportProtocol, TunnelEndpoint, TunnelType,
+};
+use tokio::{sync::broadcast, task::JoinHandle};
+use tonic::Status;
+
+use super::{
+    limits::MAX_DEVICES,
+    scenario::{LoginResult, Scenario},
+};
+
+/// Time added by any voucher which is not used up.
+const VOUCHER_DAYS: i64 = 30;
+
+const WIREGUARD_PORT: u16 = 51820;
+
+#[derive(Debug)]
+pub struct State {
+    pub settings: Settings,
+    pub tunnel_state: TunnelState,
+    pub device_state: DeviceState,
+    pub devices: Vec<Device>,
+    pub account_expiry: DateTime<Utc>,
+    pub account_history: Option<AccountNumber>,
+    pub used_vouchers: Vec<String>,
+    pub relay_list: RelayList,
+}
+
+#[derive(Debug)]
+pub struct FakeDaemon {
+    state: Mutex<State>,
+    events: broadcast::Sender<types::DaemonEvent>,
+    scenario: Scenario,
+    connect_sequence: Vec<TunnelState>,
+    /// The connect sequence being played, if any.
+    connect_task: Mutex<Option<JoinHandle<()>>>,
+}
+
+/// Connecting to and then connected to the first WireGuard relay of `relay_list`,
+/// or to a made up one if there is none.
+fn get_connect_sequence(relay_list: &RelayList) -> Vec<TunnelState> {
+    let relay = relay_list
+        .countries
+        .iter()
+        .flat_map(|country| {
+            country
+                .cities
+                .iter()
+                .flat_map(move |city| city.relays.iter().map(move |relay| (country, city, relay)))
+        })
+        .find(|(_, _, relay)| {
+            relay.active && matches!(relay.endpoint_data, RelayEndpointData::Wireguard(_))
+        });
+
+    let (address, location) = match relay {
+        Some((country, city, relay)) => (
+            relay.ipv4_addr_in,
+            Some(GeoIpLocation {
+                ipv4: Some(relay.ipv4_addr_in),
+                ipv6: None,
+                country: country.name.clone(),
+                city: Some(city.name.clone()),
+                latitude: city.latitude,
+                longitude: city.longitude,
+                mullvad_exit_ip: true,
+                hostname: Some(relay.hostname.clone()),
+                bridge_hostname: None,
+                entry_hostname: None,
+                obfuscator_hostname: None,
+            }),
+        ),
+        None => (Ipv4Addr::new(10, 0, 0, 1), None),
+    };
+
+    let endpoint = TunnelEndpoint {
+        endpoint: Endpoint {
+            address: SocketAddr::from((address, WIREGUARD_PORT)),
+            protocol: TransportProtocol::Udp,
+        },
+        tunnel_type: TunnelType::Wireguard,
+        quantum_resistant: false,
+        proxy: None,
+        obfuscation: None,
+        entry_endpoint: None,
+        tunnel_interface: Some("wg0-mullvad".to_string()),
+        daita: false,
+    };
+
+    vec![
+        TunnelState::Connecting {
+            endpoint: endpoint.clone(),
+            location: location.clone(),
+            feature_indicators: FeatureIndicators::default(),
+        },
+        TunnelState::Connected {
+            endpoint,
+            location,
+            feature_indicators: FeatureIndicators::default(),
+        },
+    ]
+}
+
+fn get_new_device() -> Device {
+    Device {
+        id: format!("fake-{}", Utc::now().timestamp_micros()),
+        name: "fake ferret".to_string(),
+        pubkey: PrivateKey::new_from_random().public_key(),
+        hijack_dns: false,
+        created: Utc::now(),
+    }
+}
+
+impl FakeDaemon {
+    pub fn new(scenario: Scenario) -> Self {
+        let mut devices = scenario.devices.clone();
+        let device_state = match &scenario.account {
+            Some(account) => {
+                // The device is on the account like any other.
+                if devices.is_empty() {
+                    devices.push(get_new_device());
+                }
+                DeviceState::LoggedIn(AccountAndDevice {
+                    account_number: account.clone(),
+                    device: devices[0].clone(),
+                })
+            }
+            None => DeviceState::LoggedOut,
+        };
+
+        let state = State {
+            settings: scenario.settings.clone(),
+            tunnel_state: scenario.tunnel_state.clone(),
+            device_state,
+            devices,
+            account_expiry: scenario.account_expiry,
+            account_history: scenario.account_history.clone(),
+            used_vouchers: vec![],
+            relay_list: scenario.relay_list.clone(),
+        };
+
+        let connect_sequence = scenario
+            .connect_sequence
+            .clone()
+            .unwrap_or_else(|| get_connect_sequence(&scenario.relay_list));
+
+        FakeDaemon {
+            state: Mutex::new(state),
+            events: broadcast::channel(16).0,
+            scenario,
+            connect_sequence,
+            connect_task: Mutex::default(),
+        }
+    }
+
+    pub fn lock_state(&self) -> MutexGuard<'_, State> {
+        self.state.lock().expect("fake daemon state lock")
+    }
+
+    pub fn subscribe(&self) -> broadcast::Receiver<types::DaemonEvent> {
+        self.events.subscribe()
+    }
+
+    pub fn get_version(&self) -> &str {
+        &self.scenario.version
+    }
+
+    /// The scenario's version info, by default the running version is the latest one.
+    pub fn get_version_info(&self) -> AppVersionInfo {
+        self.scenario
+            .version_info
+            .clone()
+            .unwrap_or_else(|| AppVersionInfo {
+                supported: true,
+                latest_stable: self.scenario.version.clone(),
+                latest_beta: self.scenario.version.clone(),
+                suggested_upgrade: None,
+            })
+    }
+
+    fn emit(&self, event: Event) {
+        // Nobody listening is fine.
+        let _ = self.events.send(types::DaemonEvent { event: Some(event) });
+    }
+
+    pub fn set_tunnel_state(&self, tunnel_state: TunnelState) {
+        log::info!("Tunnel state: {tunnel_state:?}");
+        self.lock_state().tunnel_state = tunnel_state.clone();
+        self.emit(Event::TunnelState(types::TunnelState::from(tunnel_state)));
+    }
+
+    pub fn update_settings(&self, update: impl FnOnce(&mut Settings)) {
+        let settings = {
+            let mut state = self.lock_state();
+            update(&mut state.settings);
+            state.settings.clone()
+        };
+        self.emit(Event::Settings(types::Settings::from(&settings)));
+
+        // Like the real daemon, lockdown mode applies to the current state right away.
+        let tunnel_state = self.lock_state().tunnel_state.clone();
+        if let TunnelState::Disconnected {
+            location,
+            locked_down,
+        } = tunnel_state
+        {
+            if locked_down != settings.block_when_disconnected {
+                self.set_tunnel_state(TunnelState::Disconnected {
+                    location,
+                    locked_down: settings.block_when_disconnected,
+                });
+            }
+        }
+    }
+
+    fn set_device_state(&self, device_state: DeviceState, cause: DeviceEventCause) {
+        self.lock_state().device_state = device_state.clone();
+        self.emit(Event::Device(types::DeviceEvent::from(DeviceEvent {
+            cause,
+            new_state: device_state,
+        })));
+    }
+
+    /// Plays the scenario connect sequence, returns whether the state changes.
+    pub fn connect(self: &Arc<Self>) -> bool {
+        if self.lock_state().tunnel_state.is_connected() {
+            return false;
+        }
+        self.reconnect()
+    }
+
+    pub fn reconnect(self: &Arc<Self>) -> bool {
+        self.cancel_connect_sequence();
+
+        let sequence = self.connect_sequence.clone();
+        if sequence.is_empty() {
+            log::warn!("The scenario connect sequence is empty");
+            return false;
+        }
+
+        let daemon = self.clone();
+        let step = Duration::from_millis(self.scenario.step_ms);
+        let task = tokio::spawn(async move {
+            for tunnel_state in sequence {
+                daemon.set_tunnel_state(tunnel_state);
+                tokio::time::sleep(step).await;
+            }
+        });
+        *self.connect_task.lock().expect("connect task lock") = Some(task);
+        true
+    }
+
+    /// Stops the connect sequence being played, so it can't undo a later state change.
+    fn cancel_connect_sequence(&self) {
+        if let Some(task) = self.connect_task.lock().expect("connect task lock").take() {
+            task.abort();
+        }
+    }
+
+    pub fn disconnect(&self) -> bool {
+        self.cancel_connect_sequence();
+
+        let (was_disconnected, locked_down) = {
+            let state = self.lock_state();
+            (
+                state.tunnel_state.is_disconnected(),
+                state.settings.block_when_disconnected,
+            )
+        };
+        if was_disconnected {
+            return false;
+        }
+
+        self.set_tunnel_state(TunnelState::Disconnected {
+            location: None,
+            locked_down,
+        });
+        true
+    }
+
+    pub fn login(&self, account: AccountNumber) -> Result<(), Status> {
+        let result = self
+            .scenario
+            .login_results
+            .get(&account)
+            .copied()
+            .unwrap_or(LoginResult::Ok);
+
+        match result {
+            LoginResult::Ok => {}
+            LoginResult::InvalidAccount => return Err(Status::unauthenticated("invalid account")),
+            LoginResult::TooManyDevices => {
+                return Err(Status::resource_exhausted("too many devices"))
+            }
+            LoginResult::Error => return Err(Status::unavailable("scripted login error")),
+        }
+
+        let device = {
+            let mut state = self.lock_state();
+            if state.devices.len() >= MAX_DEVICES {
+                return Err(Status::resource_exhausted("too many devices"));
+            }
+            let device = get_new_device();
+            state.devices.push(device.clone());
+            state.account_history = Some(account.clone());
+            device
+        };
+
+        self.set_device_state(
+            DeviceState::LoggedIn(AccountAndDevice {
+                account_number: account,
+                device,
+            }),
+            DeviceEventCause::LoggedIn,
+        );
+        Ok(())
+    }
+
+    pub fn logout(&self) {
+        let current_device = match &self.lock_state().device_state {
+            DeviceState::LoggedIn(account_and_device) => Some(account_and_device.device.id.clone()),
+            _ => None,
+        };
+        if let Some(device_id) = current_device {
+            self.lock_state()
+                .devices
+                .retain(|device| device.id != device_id);
+        }
+        self.set_device_state(DeviceState::LoggedOut, DeviceEventCause::LoggedOut);
+    }
+
+    pub fn create_account(&self) -> Result<AccountNumber, Status> {
+        let account = format!("{:016}", Utc::now().timestamp_micros() % 10_i64.pow(16));
+        {
+            let mut state = self.lock_state();
+            state.account_expiry = Utc::now();
+            state.devices.clear();
+        }
+        self.login(account.clone())?;
+        Ok(account)
+    }
+
+    pub fn remove_device(&self, account: AccountNumber, device_id: String) -> Result<(), Status> {
+        let (new_devices, is_current) = {
+            let mut state = self.lock_state();
+            let count = state.devices.len();
+            state.devices.retain(|device| device.id != device_id);
+            if state.devices.len() == count {
+                return Err(Status::not_found("no such device"));
+            }
+            let is_current = matches!(
+                &state.device_state,
+                DeviceState::LoggedIn(account_and_device) if account_and_device.device.id == device_id
+            );
+            (state.devices.clone(), is_current)
+        };
+
+        self.emit(Event::RemoveDevice(types::RemoveDeviceEvent::from(
+            RemoveDeviceEvent {
+                account_number: account,
+                new_devices,
+            },
+        )));
+        if is_current {
+            self.set_device_state(DeviceState::Revoked, DeviceEventCause::Revoked);
+        }
+        Ok(())
+    }
+
+    /// Returns the added time and the new expiry.
+    pub fn submit_voucher(&self, voucher: String) -> Result<(TimeDelta, DateTime<Utc>), Status> {
+        let mut state = self.lock_state();
+        if voucher.len() != 16 {
+            return Err(Status::not_found("invalid voucher"));
+        }
+        if state.used_vouchers.contains(&voucher) {
+            return Err(Status::resource_exhausted("voucher already used"));
+        }
+
+        let time_added = TimeDelta::days(VOUCHER_DAYS);
+        state.account_expiry = state.account_expiry.max(Utc::now()) + time_added;
+        state.used_vouchers.push(voucher);
+
+        Ok((time_added, state.account_expiry))
+    }
+}
@@ -0,0 +1,55 @@
+//! Serves the Mullvad management gRPC interface from a scenario file, so
+//! mullvadwaita can run without Mullvad installed:
+//!
+//! ```sh
+//! mullvadwaita-fake-daemon scenario.json /tmp/fake-mullvad.sock &
+//! mullvadwaita --socket /tmp/fake-mullvad.sock
+//! ```
+
+mod daemon;
+#[path = "../../mullvad/limits.rs"]
+mod limits;
+mod scenario;
+mod service;
+
+use std::{path::PathBuf, sync::Arc};
+
+use anyhow::{Context, Result};
+use tokio::net::UnixListener;
+use tokio_stream::wrappers::UnixListenerStream;
+
+use daemon::FakeDaemon;
+use scenario::Scenario;
+use service::ManagementServiceImpl;
+
+const DEFAULT_SOCKET_PATH: &str = "/tmp/mullvadwaita-fake-daemon.sock";
+
+#[tokio::main]
+async fn main() -> Result<()> {
+    env_logger::init();
+
+    let mut args = std::env::args_os().skip(1);
+    let scenario = match args.next() {
+        Some(path) => Scenario::load(&PathBuf::from(path))?,
+        None => Scenario::default(),
+    };
+    let socket_path = args
+        .next()
+        .map(PathBuf::from)
+        .unwrap_or_else(|| PathBuf::from(DEFAULT_SOCKET_PATH));
+
+    // A socket left over by a previous run would make binding fail.
+    let _ = std::fs::remove_file(&socket_path);
+    let listener = UnixListener::bind(&socket_path)
+        .with_context(|| format!("Can't listen on {socket_path:?}"))?;
+    log::info!("Listening on {socket_path:?}, run mullvadwaita with --socket {socket_path:?}");
+
+    let daemon = Arc::new(FakeDaemon::new(scenario));
+
+    tonic::transport::Server::builder()
+        .add_service(ManagementServiceImpl::new(daemon))
+        .serve_with_incoming(UnixListenerStream::new(listener))
+        .await?;
+
+    Ok(())
+}
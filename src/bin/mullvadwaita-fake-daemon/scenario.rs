@@ -0,0 +1,71 @@
+use std::{collections::HashMap, fs, path::Path};
+
+use anyhow::{Context, Result};
+use chrono::{DateTime, TimeDelta, Utc};
+use mullvad_types::{
+    account::AccountNumber, device::Device, relay_list::RelayList, settings::Settings,
+    states::TunnelState, version::AppVersionInfo,
+};
+use serde::Deserialize;
+use smart_default::SmartDefault;
+
+/// What the fake daemon starts with and how it answers.
+///
+/// Mullvad types use the daemon's own JSON format, e.g. tunnel states
+/// as printed by `mullvad status --json`.
+#[derive(Debug, SmartDefault, Deserialize)]
+#[serde(default)]
+pub struct Scenario {
+    pub settings: Settings,
+
+    #[default(TunnelState::Disconnected { location: None, locked_down: false })]
+    pub tunnel_state: TunnelState,
+
+    /// Logged in at start if set.
+    pub account: Option<AccountNumber>,
+
+    /// Devices on the account, the first one is this device when logged in.
+    pub devices: Vec<Device>,
+
+    #[default(Utc::now() + TimeDelta::days(30))]
+    pub account_expiry: DateTime<Utc>,
+
+    pub account_history: Option<AccountNumber>,
+
+    /// Results of logging in to specific accounts, any other account logs in fine.
+    pub login_results: HashMap<AccountNumber, LoginResult>,
+
+    /// States sent one after another on connect and reconnect, the last one stays.
+    /// By default connecting to and then connected to a relay of `relay_list`.
+    pub connect_sequence: Option<Vec<TunnelState>>,
+
+    /// Delay between the states of `connect_sequence`.
+    #[default(500)]
+    pub step_ms: u64,
+
+    #[default(RelayList::empty())]
+    pub relay_list: RelayList,
+
+    #[default("2025.1")]
+    pub version: String,
+
+    /// Whether `version` is supported and what to upgrade to,
+    /// by default it's supported and the latest release.
+    pub version_info: Option<AppVersionInfo>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum LoginResult {
+    Ok,
+    InvalidAccount,
+    TooManyDevices,
+    Error,
+}
+
+impl Scenario {
+    pub fn load(path: &Path) -> Result<Scenario> {
+        let json = fs::read_to_string(path).with_context(|| format!("Can't read {path:?}"))?;
+        serde_json::from_str(&json).with_context(|| format!("Can't parse {path:?}"))
+    }
+}
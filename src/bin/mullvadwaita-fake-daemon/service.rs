@@ -0,0 +1,429 @@
+use std::sync::Arc;
+
+use futures::{stream::BoxStream, StreamExt};
+use mullvad_management_interface::types::{
+    self,
+    management_service_server::{ManagementService, ManagementServiceServer},
+};
+use mullvad_types::relay_constraints::RelaySettings;
+use prost_types::Timestamp;
+use tokio_stream::wrappers::BroadcastStream;
+use tonic::{Request, Response, Status};
+
+use super::daemon::FakeDaemon;
+
+type RpcResult<T> = Result<Response<T>, Status>;
+
+/// The management service answering the calls mullvadwaita makes,
+/// everything else is refused as unimplemented.
+#[derive(Debug, Clone)]
+pub struct ManagementServiceImpl {
+    daemon: Arc<FakeDaemon>,
+}
+
+impl ManagementServiceImpl {
+    pub fn new(daemon: Arc<FakeDaemon>) -> ManagementServiceServer<Self> {
+        ManagementServiceServer::new(ManagementServiceImpl { daemon })
+    }
+}
+
+fn get_timestamp(time: chrono::DateTime<chrono::Utc>) -> Timestamp {
+    Timestamp {
+        seconds: time.timestamp(),
+        nanos: time.timestamp_subsec_nanos() as i32,
+    }
+}
+
+fn unsupported<T>(method: &str) -> RpcResult<T> {
+    let message = format!("{method} is not supported by the fake daemon");
+    log::warn!("{message}");
+    Err(Status::unimplemented(message))
+}
+
+#[tonic::async_trait]
+impl ManagementService for ManagementServiceImpl {
+    type EventsListenStream = BoxStream<'static, Result<types::DaemonEvent, Status>>;
+    type GetSplitTunnelProcessesStream = BoxStream<'static, Result<i32, Status>>;
+
+    async fn events_listen(&self, _: Request<()>) -> RpcResult<Self::EventsListenStream> {
+        let events = BroadcastStream::new(self.daemon.subscribe()).filter_map(|event| async move {
+            // A lagging listener just misses some events.
+            event.ok().map(Ok)
+        });
+        Ok(Response::new(events.boxed()))
+    }
+
+    async fn get_current_version(&self, _: Request<()>) -> RpcResult<String> {
+        Ok(Response::new(self.daemon.get_version().to_string()))
+    }
+
+    async fn get_version_info(&self, _: Request<()>) -> RpcResult<types::AppVersionInfo> {
+        let version_info = self.daemon.get_version_info();
+        Ok(Response::new(types::AppVersionInfo::from(version_info)))
+    }
+
+    async fn get_tunnel_state(&self, _: Request<()>) -> RpcResult<types::TunnelState> {
+        let tunnel_state = self.daemon.lock_state().tunnel_state.clone();
+        Ok(Response::new(types::TunnelState::from(tunnel_state)))
+    }
+
+    async fn connect_tunnel(&self, _: Request<()>) -> RpcResult<bool> {
+        Ok(Response::new(self.daemon.connect()))
+    }
+
+    async fn reconnect_tunnel(&self, _: Request<()>) -> RpcResult<bool> {
+        Ok(Response::new(self.daemon.reconnect()))
+    }
+
+    async fn disconnect_tunnel(&self, _: Request<()>) -> RpcResult<bool> {
+        Ok(Response::new(self.daemon.disconnect()))
+    }
+
+    async fn get_settings(&self, _: Request<()>) -> RpcResult<types::Settings> {
+        let settings = types::Settings::from(&self.daemon.lock_state().settings);
+        Ok(Response::new(settings))
+    }
+
+    async fn set_auto_connect(&self, request: Request<bool>) -> RpcResult<()> {
+        let state = request.into_inner();
+        self.daemon
+            .update_settings(|settings| settings.auto_connect = state);
+        Ok(Response::new(()))
+    }
+
+    async fn set_allow_lan(&self, request: Request<bool>) -> RpcResult<()> {
+        let state = request.into_inner();
+        self.daemon
+            .update_settings(|settings| settings.allow_lan = state);
+        Ok(Response::new(()))
+    }
+
+    async fn set_block_when_disconnected(&self, request: Request<bool>) -> RpcResult<()> {
+        let state = request.into_inner();
+        self.daemon
+            .update_settings(|settings| settings.block_when_disconnected = state);
+        Ok(Response::new(()))
+    }
+
+    async fn set_enable_ipv6(&self, request: Request<bool>) -> RpcResult<()> {
+        let state = request.into_inner();
+        self.daemon
+            .update_settings(|settings| settings.tunnel_options.generic.enable_ipv6 = state);
+        Ok(Response::new(()))
+    }
+
+    async fn set_relay_settings(&self, request: Request<types::RelaySettings>) -> RpcResult<()> {
+        let update = RelaySettings::try_from(request.into_inner())
+            .map_err(|err| Status::invalid_argument(err.to_string()))?;
+        self.daemon
+            .update_settings(|settings| settings.relay_settings = update);
+        Ok(Response::new(()))
+    }
+
+    async fn get_relay_locations(&self, _: Request<()>) -> RpcResult<types::RelayList> {
+        let relay_list = self.daemon.lock_state().relay_list.clone();
+        Ok(Response::new(types::RelayList::from(relay_list)))
+    }
+
+    async fn login_account(&self, request: Request<String>) -> RpcResult<()> {
+        self.daemon.login(request.into_inner())?;
+        Ok(Response::new(()))
+    }
+
+    async fn logout_account(&self, _: Request<()>) -> RpcResult<()> {
+        self.daemon.logout();
+        Ok(Response::new(()))
+    }
+
+    async fn create_new_account(&self, _: Request<()>) -> RpcResult<String> {
+        Ok(Response::new(self.daemon.create_account()?))
+    }
+
+    async fn get_account_history(&self, _: Request<()>) -> RpcResult<types::AccountHistory> {
+        let number = self.daemon.lock_state().account_history.clone();
+        Ok(Response::new(types::AccountHistory { number }))
+    }
+
+    async fn clear_account_history(&self, _: Request<()>) -> RpcResult<()> {
+        self.daemon.lock_state().account_history = None;
+        Ok(Response::new(()))
+    }
+
+    async fn get_account_data(&self, request: Request<String>) -> RpcResult<types::AccountData> {
+        let expiry = self.daemon.lock_state().account_expiry;
+        Ok(Response::new(types::AccountData {
+            id: request.into_inner(),
+            expiry: Some(get_timestamp(expiry)),
+        }))
+    }
+
+    async fn get_www_auth_token(&self, _: Request<()>) -> RpcResult<String> {
+        Ok(Response::new("fake-token".to_string()))
+    }
+
+    async fn submit_voucher(
+        &self,
+        request: Request<String>,
+    ) -> RpcResult<types::VoucherSubmission> {
+        let (added, expiry) = self.daemon.submit_voucher(request.into_inner())?;
+        Ok(Response::new(types::VoucherSubmission {
+            seconds_added: added.num_seconds() as u64,
+            new_expiry: Some(get_timestamp(expiry)),
+        }))
+    }
+
+    async fn get_device(&self, _: Request<()>) -> RpcResult<types::DeviceState> {
+        let device_state = self.daemon.lock_state().device_state.clone();
+        Ok(Response::new(types::DeviceState::from(device_state)))
+    }
+
+    async fn list_devices(&self, _: Request<String>) -> RpcResult<types::DeviceList> {
+        let devices = self.daemon.lock_state().devices.clone();
+        Ok(Response::new(types::DeviceList {
+            devices: devices.into_iter().map(types::Device::from).collect(),
+        }))
+    }
+
+    async fn remove_device(&self, request: Request<types::DeviceRemoval>) -> RpcResult<()> {
+        let removal = request.into_inner();
+        self.daemon
+            .remove_device(removal.account_number, removal.device_id)?;
+        Ok(Response::new(()))
+    }
+
+    // Not used by mullvadwaita.
+
+    async fn prepare_restart(&self, _: Request<()>) -> RpcResult<()> {
+        unsupported("PrepareRestart")
+    }
+
+    async fn prepare_restart_v2(&self, _: Request<bool>) -> RpcResult<()> {
+        unsupported("PrepareRestartV2")
+    }
+
+    async fn factory_reset(&self, _: Request<()>) -> RpcResult<()> {
+        unsupported("FactoryReset")
+    }
+
+    async fn is_performing_post_upgrade(&self, _: Request<()>) -> RpcResult<bool> {
+        unsupported("IsPerformingPostUpgrade")
+    }
+
+    async fn update_relay_locations(&self, _: Request<()>) -> RpcResult<()> {
+        unsupported("UpdateRelayLocations")
+    }
+
+    async fn set_bridge_settings(&self, _: Request<types::BridgeSettings>) -> RpcResult<()> {
+        unsupported("SetBridgeSettings")
+    }
+
+    async fn set_bridge_state(&self, _: Request<types::BridgeState>) -> RpcResult<()> {
+        unsupported("SetBridgeState")
+    }
+
+    async fn set_obfuscation_settings(
+        &self,
+        _: Request<types::ObfuscationSettings>,
+    ) -> RpcResult<()> {
+        unsupported("SetObfuscationSettings")
+    }
+
+    async fn set_show_beta_releases(&self, _: Request<bool>) -> RpcResult<()> {
+        unsupported("SetShowBetaReleases")
+    }
+
+    async fn set_openvpn_mssfix(&self, _: Request<u32>) -> RpcResult<()> {
+        unsupported("SetOpenvpnMssfix")
+    }
+
+    async fn set_wireguard_mtu(&self, _: Request<u32>) -> RpcResult<()> {
+        unsupported("SetWireguardMtu")
+    }
+
+    async fn set_quantum_resistant_tunnel(
+        &self,
+        _: Request<types::QuantumResistantState>,
+    ) -> RpcResult<()> {
+        unsupported("SetQuantumResistantTunnel")
+    }
+
+    async fn set_enable_daita(&self, _: Request<bool>) -> RpcResult<()> {
+        unsupported("SetEnableDaita")
+    }
+
+    async fn set_daita_direct_only(&self, _: Request<bool>) -> RpcResult<()> {
+        unsupported("SetDaitaDirectOnly")
+    }
+
+    async fn set_daita_settings(&self, _: Request<types::DaitaSettings>) -> RpcResult<()> {
+        unsupported("SetDaitaSettings")
+    }
+
+    async fn set_dns_options(&self, _: Request<types::DnsOptions>) -> RpcResult<()> {
+        unsupported("SetDnsOptions")
+    }
+
+    async fn set_relay_override(&self, _: Request<types::RelayOverride>) -> RpcResult<()> {
+        unsupported("SetRelayOverride")
+    }
+
+    async fn clear_all_relay_overrides(&self, _: Request<()>) -> RpcResult<()> {
+        unsupported("ClearAllRelayOverrides")
+    }
+
+    async fn update_device(&self, _: Request<()>) -> RpcResult<()> {
+        unsupported("UpdateDevice")
+    }
+
+    async fn set_wireguard_rotation_interval(
+        &self,
+        _: Request<prost_types::Duration>,
+    ) -> RpcResult<()> {
+        unsupported("SetWireguardRotationInterval")
+    }
+
+    async fn reset_wireguard_rotation_interval(&self, _: Request<()>) -> RpcResult<()> {
+        unsupported("ResetWireguardRotationInterval")
+    }
+
+    async fn rotate_wireguard_key(&self, _: Request<()>) -> RpcResult<()> {
+        unsupported("RotateWireguardKey")
+    }
+
+    async fn get_wireguard_key(&self, _: Request<()>) -> RpcResult<types::PublicKey> {
+        unsupported("GetWireguardKey")
+    }
+
+    async fn create_custom_list(&self, _: Request<types::NewCustomList>) -> RpcResult<String> {
+        unsupported("CreateCustomList")
+    }
+
+    async fn delete_custom_list(&self, _: Request<String>) -> RpcResult<()> {
+        unsupported("DeleteCustomList")
+    }
+
+    async fn update_custom_list(&self, _: Request<types::CustomList>) -> RpcResult<()> {
+        unsupported("UpdateCustomList")
+    }
+
+    async fn clear_custom_lists(&self, _: Request<()>) -> RpcResult<()> {
+        unsupported("ClearCustomLists")
+    }
+
+    async fn add_api_access_method(
+        &self,
+        _: Request<types::NewAccessMethodSetting>,
+    ) -> RpcResult<types::Uuid> {
+        unsupported("AddApiAccessMethod")
+    }
+
+    async fn remove_api_access_method(&self, _: Request<types::Uuid>) -> RpcResult<()> {
+        unsupported("RemoveApiAccessMethod")
+    }
+
+    async fn set_api_access_method(&self, _: Request<types::Uuid>) -> RpcResult<()> {
+        unsupported("SetApiAccessMethod")
+    }
+
+    async fn update_api_access_method(
+        &self,
+        _: Request<types::AccessMethodSetting>,
+    ) -> RpcResult<()> {
+        unsupported("UpdateApiAccessMethod")
+    }
+
+    async fn clear_custom_api_access_methods(&self, _: Request<()>) -> RpcResult<()> {
+        unsupported("ClearCustomApiAccessMethods")
+    }
+
+    async fn get_current_api_access_method(
+        &self,
+        _: Request<()>,
+    ) -> RpcResult<types::AccessMethodSetting> {
+        unsupported("GetCurrentApiAccessMethod")
+    }
+
+    async fn test_custom_api_access_method(
+        &self,
+        _: Request<types::CustomProxy>,
+    ) -> RpcResult<bool> {
+        unsupported("TestCustomApiAccessMethod")
+    }
+
+    async fn test_api_access_method_by_id(&self, _: Request<types::Uuid>) -> RpcResult<bool> {
+        unsupported("TestApiAccessMethodById")
+    }
+
+    async fn get_split_tunnel_processes(
+        &self,
+        _: Request<()>,
+    ) -> RpcResult<Self::GetSplitTunnelProcessesStream> {
+        unsupported("GetSplitTunnelProcesses")
+    }
+
+    async fn add_split_tunnel_process(&self, _: Request<i32>) -> RpcResult<()> {
+        unsupported("AddSplitTunnelProcess")
+    }
+
+    async fn remove_split_tunnel_process(&self, _: Request<i32>) -> RpcResult<()> {
+        unsupported("RemoveSplitTunnelProcess")
+    }
+
+    async fn clear_split_tunnel_processes(&self, _: Request<()>) -> RpcResult<()> {
+        unsupported("ClearSplitTunnelProcesses")
+    }
+
+    async fn add_split_tunnel_app(&self, _: Request<String>) -> RpcResult<()> {
+        unsupported("AddSplitTunnelApp")
+    }
+
+    async fn remove_split_tunnel_app(&self, _: Request<String>) -> RpcResult<()> {
+        unsupported("RemoveSplitTunnelApp")
+    }
+
+    async fn clear_split_tunnel_apps(&self, _: Request<()>) -> RpcResult<()> {
+        unsupported("ClearSplitTunnelApps")
+    }
+
+    async fn set_split_tunnel_state(&self, _: Request<bool>) -> RpcResult<()> {
+        unsupported("SetSplitTunnelState")
+    }
+
+    async fn get_excluded_processes(
+        &self,
+        _: Request<()>,
+    ) -> RpcResult<types::ExcludedProcessList> {
+        unsupported("GetExcludedProcesses")
+    }
+
+    async fn init_play_purchase(
+        &self,
+        _: Request<()>,
+    ) -> RpcResult<types::PlayPurchasePaymentToken> {
+        unsupported("InitPlayPurchase")
+    }
+
+    async fn verify_play_purchase(&self, _: Request<types::PlayPurchase>) -> RpcResult<()> {
+        unsupported("VerifyPlayPurchase")
+    }
+
+    async fn need_full_disk_permissions(&self, _: Request<()>) -> RpcResult<bool> {
+        unsupported("NeedFullDiskPermissions")
+    }
+
+    async fn check_volumes(&self, _: Request<()>) -> RpcResult<()> {
+        unsupported("CheckVolumes")
+    }
+
+    async fn apply_json_settings(&self, _: Request<String>) -> RpcResult<()> {
+        unsupported("ApplyJsonSettings")
+    }
+
+    async fn export_json_settings(&self, _: Request<()>) -> RpcResult<String> {
+        unsupported("ExportJsonSettings")
+    }
+
+    async fn get_feature_indicators(&self, _: Request<()>) -> RpcResult<types::FeatureIndicators> {
+        unsupported("GetFeatureIndicators")
+    }
+}
@@ -0,0 +1,95 @@
+use std::{fs, io, path::PathBuf};
+
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use smart_default::SmartDefault;
+
+use crate::schedule::Schedule;
+
+const CONFIG_FILE_NAME: &str = "config.json";
+
+/// GUI settings which are not part of the daemon settings.
+#[derive(Debug, Clone, SmartDefault, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Config {
+    pub schedule: Schedule,
+
+    /// Where the tunnel interface statistics are read from.
+    #[default(PathBuf::from("/sys"))]
+    pub sysfs_root: PathBuf,
+
+    pub connection_check: ConnectionCheckConfig,
+
+    /// Management socket of the daemon, the default one if unset.
+    /// The `--socket` command-line option takes precedence.
+    pub daemon_socket_path: Option<PathBuf>,
+
+    /// D-Bus address of systemd, the system bus if unset.
+    pub systemd_bus_address: Option<String>,
+
+    /// Account page of the website, opened with a `token` query parameter.
+    #[default("https://mullvad.net/account".to_string())]
+    pub account_url: String,
+
+    /// Hours before the account expires at which a desktop notification is sent.
+    #[default(vec![72, 24, 1])]
+    pub expiry_notification_hours: Vec<u32>,
+}
+
+/// Endpoints of the "am I Mullvad" connection check.
+#[derive(Debug, Clone, SmartDefault, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ConnectionCheckConfig {
+    /// Returns the exit IP and whether it belongs to Mullvad.
+    #[default("https://am.i.mullvad.net/json".to_string())]
+    pub url: String,
+
+    /// Same as `url` but only reachable over IPv6.
+    #[default(Some("https://ipv6.am.i.mullvad.net/json".to_string()))]
+    pub ipv6_url: Option<String>,
+
+    /// Requesting it makes the resolver look up a unique name, `{id}` is replaced with it.
+    #[default(Some("https://{id}.dnsleak.am.i.mullvad.net".to_string()))]
+    pub dns_leak_lookup_url: Option<String>,
+
+    /// Lists the DNS servers which looked up the `{id}` name.
+    #[default(Some("https://am.i.mullvad.net/dnsleak/{id}".to_string()))]
+    pub dns_leak_result_url: Option<String>,
+}
+
+impl Config {
+    pub fn get_config_dir() -> PathBuf {
+        gtk::glib::user_config_dir().join("mullvadwaita")
+    }
+
+    fn get_config_path() -> PathBuf {
+        Self::get_config_dir().join(CONFIG_FILE_NAME)
+    }
+
+    /// Loads the config, falling back to defaults if it is missing or broken.
+    pub fn load() -> Config {
+        let path = Self::get_config_path();
+        match fs::read_to_string(&path) {
+            Ok(json) => serde_json::from_str(&json)
+                .inspect_err(|err| log::warn!("Can't parse config {path:?}: {err}"))
+                .unwrap_or_default(),
+            Err(err) if err.kind() == io::ErrorKind::NotFound => Config::default(),
+            Err(err) => {
+                log::warn!("Can't read config {path:?}: {err}");
+                Config::default()
+            }
+        }
+    }
+
+    pub fn save(&self) -> Result<()> {
+        fs::create_dir_all(Self::get_config_dir())?;
+
+        // Write to a temporary file first so a crash can't leave a truncated config.
+        let path = Self::get_config_path();
+        let tmp_path = path.with_extension("json.tmp");
+        fs::write(&tmp_path, serde_json::to_string_pretty(self)?)?;
+        fs::rename(tmp_path, path)?;
+
+        Ok(())
+    }
+}
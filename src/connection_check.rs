@@ -0,0 +1,262 @@
+use std::{
+    net::IpAddr,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use anyhow::Result;
+use serde::{de::DeserializeOwned, Deserialize};
+
+use crate::{config::ConnectionCheckConfig, tr};
+
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+
+#[derive(Debug, Deserialize)]
+struct CheckResponse {
+    ip: IpAddr,
+    #[serde(default)]
+    mullvad_exit_ip: bool,
+}
+
+#[derive(Debug, Deserialize)]
+struct DnsServer {
+    ip: IpAddr,
+    #[serde(default)]
+    mullvad_dns: bool,
+}
+
+#[derive(Debug, Clone)]
+pub struct ConnectionCheckReport {
+    pub exit_ip: IpAddr,
+    /// Every mismatch between what the check endpoints see and the tunnel state.
+    pub warnings: Vec<String>,
+}
+
+/// Compares what the check endpoints see with the tunnel exit IPs
+/// (as returned by `TunnelStateExt::get_tunnel_out`).
+pub async fn check_connection(
+    config: &ConnectionCheckConfig,
+    tunnel_out: Option<String>,
+) -> Result<ConnectionCheckReport> {
+    let client = reqwest::Client::builder()
+        .timeout(REQUEST_TIMEOUT)
+        .build()?;
+
+    let expected_ips: Vec<IpAddr> = tunnel_out
+        .iter()
+        .flat_map(|out| out.lines())
+        .filter_map(|line| line.trim().parse().ok())
+        .collect();
+
+    let mut warnings = vec![];
+
+    let response: CheckResponse = fetch_json(&client, &config.url).await?;
+
+    if !response.mullvad_exit_ip {
+        warnings.push(tr!(
+            "Your traffic leaves from {}, which is not a Mullvad server.",
+            response.ip
+        ));
+    } else if !expected_ips.is_empty() && !expected_ips.contains(&response.ip) {
+        warnings.push(tr!(
+            "Your traffic leaves from {}, but the tunnel exit IP is {}.",
+            response.ip,
+            tunnel_out
+                .as_deref()
+                .unwrap_or_default()
+                .replace('\n', ", ")
+        ));
+    }
+
+    if let Some(ipv6_url) = &config.ipv6_url {
+        // Not having IPv6 connectivity at all is fine.
+        match fetch_json::<CheckResponse>(&client, ipv6_url).await {
+            Ok(ipv6_response) if !ipv6_response.mullvad_exit_ip => warnings.push(tr!(
+                "IPv6 traffic leaves outside of the tunnel from {}.",
+                ipv6_response.ip
+            )),
+            Ok(_) => {}
+            Err(err) => log::debug!("IPv6 connection check failed: {err}"),
+        }
+    }
+
+    if let (Some(lookup_url), Some(result_url)) =
+        (&config.dns_leak_lookup_url, &config.dns_leak_result_url)
+    {
+        match check_dns(&client, lookup_url, result_url).await {
+            Ok(leaking) if !leaking.is_empty() => warnings.push(tr!(
+                "DNS requests are answered by non-Mullvad servers: {}.",
+                leaking
+                    .iter()
+                    .map(|ip| ip.to_string())
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            )),
+            Ok(_) => {}
+            Err(err) => log::debug!("DNS leak check failed: {err}"),
+        }
+    }
+
+    Ok(ConnectionCheckReport {
+        exit_ip: response.ip,
+        warnings,
+    })
+}
+
+async fn fetch_json<T: DeserializeOwned>(client: &reqwest::Client, url: &str) -> Result<T> {
+    Ok(client
+        .get(url)
+        .send()
+        .await?
+        .error_for_status()?
+        .json()
+        .await?)
+}
+
+/// Makes the system resolver look up a unique name and returns the servers
+/// which did it, except Mullvad's own.
+async fn check_dns(
+    client: &reqwest::Client,
+    lookup_url: &str,
+    result_url: &str,
+) -> Result<Vec<IpAddr>> {
+    let id = format!(
+        "{:x}",
+        SystemTime::now().duration_since(UNIX_EPOCH)?.as_nanos()
+    );
+
+    // Only the name lookup matters, the request itself may fail.
+    let _ = client.get(lookup_url.replace("{id}", &id)).send().await;
+
+    let servers: Vec<DnsServer> = fetch_json(client, &result_url.replace("{id}", &id)).await?;
+
+    Ok(servers
+        .into_iter()
+        .filter(|server| !server.mullvad_dns)
+        .map(|server| server.ip)
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use tokio::{
+        io::{AsyncReadExt, AsyncWriteExt},
+        net::TcpListener,
+    };
+
+    use super::*;
+
+    /// A local stand-in for the check endpoints, answering with the body of
+    /// the first route the request path starts with, or 404.
+    async fn serve(routes: Vec<(&'static str, &'static str)>) -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap();
+
+        tokio::spawn(async move {
+            while let Ok((mut stream, _)) = listener.accept().await {
+                let mut request = vec![0; 4096];
+                let len = stream.read(&mut request).await.unwrap_or_default();
+                let request = String::from_utf8_lossy(&request[..len]);
+                let path = request.split_whitespace().nth(1).unwrap_or_default();
+
+                let response = match routes.iter().find(|(route, _)| path.starts_with(route)) {
+                    Some((_, body)) => format!(
+                        "HTTP/1.1 200 OK\r\nContent-Type: application/json\r\n\
+                         Content-Length: {}\r\nConnection: close\r\n\r\n{body}",
+                        body.len()
+                    ),
+                    None => "HTTP/1.1 404 Not Found\r\nContent-Length: 0\r\n\
+                             Connection: close\r\n\r\n"
+                        .to_string(),
+                };
+                let _ = stream.write_all(response.as_bytes()).await;
+            }
+        });
+
+        format!("http://{address}")
+    }
+
+    fn get_config(base_url: &str) -> ConnectionCheckConfig {
+        ConnectionCheckConfig {
+            url: format!("{base_url}/json"),
+            ipv6_url: Some(format!("{base_url}/ipv6")),
+            dns_leak_lookup_url: Some(format!("{base_url}/lookup/{{id}}")),
+            dns_leak_result_url: Some(format!("{base_url}/dnsleak/{{id}}")),
+        }
+    }
+
+    #[tokio::test]
+    async fn matching_exit_is_verified() {
+        let base_url = serve(vec![
+            (
+                "/json",
+                r#"{"ip": "185.65.134.1", "mullvad_exit_ip": true}"#,
+            ),
+            ("/dnsleak/", r#"[{"ip": "10.64.0.1", "mullvad_dns": true}]"#),
+        ])
+        .await;
+
+        let report = check_connection(
+            &get_config(&base_url),
+            Some("185.65.134.1\n2a03:1b20::1".to_string()),
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(report.exit_ip, "185.65.134.1".parse::<IpAddr>().unwrap());
+        assert!(report.warnings.is_empty(), "{:?}", report.warnings);
+    }
+
+    #[tokio::test]
+    async fn every_mismatch_is_a_warning() {
+        let base_url = serve(vec![
+            (
+                "/json",
+                r#"{"ip": "185.65.134.2", "mullvad_exit_ip": true}"#,
+            ),
+            (
+                "/ipv6",
+                r#"{"ip": "2001:db8::1", "mullvad_exit_ip": false}"#,
+            ),
+            (
+                "/dnsleak/",
+                r#"[{"ip": "10.64.0.1", "mullvad_dns": true}, {"ip": "192.0.2.53"}]"#,
+            ),
+        ])
+        .await;
+
+        let report = check_connection(&get_config(&base_url), Some("185.65.134.1".to_string()))
+            .await
+            .unwrap();
+
+        assert_eq!(report.warnings.len(), 3, "{:?}", report.warnings);
+        assert!(report.warnings[0].contains("185.65.134.1"));
+        assert!(report.warnings[1].contains("2001:db8::1"));
+        assert!(report.warnings[2].contains("192.0.2.53"));
+        assert!(!report.warnings[2].contains("10.64.0.1"));
+    }
+
+    #[tokio::test]
+    async fn non_mullvad_exit_is_a_warning() {
+        let base_url = serve(vec![(
+            "/json",
+            r#"{"ip": "198.51.100.7", "mullvad_exit_ip": false}"#,
+        )])
+        .await;
+
+        let report = check_connection(&get_config(&base_url), None)
+            .await
+            .unwrap();
+
+        assert_eq!(report.warnings.len(), 1);
+        assert!(report.warnings[0].contains("198.51.100.7"));
+    }
+
+    #[tokio::test]
+    async fn unreachable_check_is_an_error() {
+        let base_url = serve(vec![]).await;
+
+        assert!(check_connection(&get_config(&base_url), None)
+            .await
+            .is_err());
+    }
+}
@@ -9,9 +9,12 @@ use talpid_types::{net::TunnelEndpoint, tunnel::ActionAfterDisconnect};
 pub trait TunnelStateExt {
     fn is_connecting_or_connected(&self) -> bool;
     fn is_connecting_or_reconnecting(&self) -> bool;
+    fn is_blocking(&self) -> bool;
     fn get_endpoint(&self) -> Option<&TunnelEndpoint>;
     fn get_location(&self) -> Option<&GeoIpLocation>;
     fn get_tunnel_state_label(&self) -> String;
+    fn get_state_name(&self) -> &'static str;
+    fn get_error_cause(&self) -> Option<String>;
     fn get_country(&self) -> Option<String>;
     fn get_city(&self) -> Option<String>;
     fn get_hostname(&self) -> Option<String>;
@@ -32,6 +35,15 @@ impl TunnelStateExt for TunnelState {
         )
     }
 
+    fn is_blocking(&self) -> bool {
+        match self {
+            Disconnected { locked_down, .. } => *locked_down,
+            Error(error_state) => error_state.is_blocking(),
+            Disconnecting(ActionAfterDisconnect::Block) => true,
+            _ => false,
+        }
+    }
+
     fn get_location(&self) -> Option<&GeoIpLocation> {
         match self {
             Disconnected { location, .. }
@@ -91,6 +103,23 @@ impl TunnelStateExt for TunnelState {
         }
     }
 
+    fn get_state_name(&self) -> &'static str {
+        match self {
+            Connected { .. } => "connected",
+            Connecting { .. } => "connecting",
+            Disconnected { .. } => "disconnected",
+            Disconnecting(..) => "disconnecting",
+            Error(..) => "error",
+        }
+    }
+
+    fn get_error_cause(&self) -> Option<String> {
+        match self {
+            Error(error_state) => Some(error_state.cause().to_string()),
+            _ => None,
+        }
+    }
+
     fn get_country(&self) -> Option<String> {
         self.get_location().map(|location| location.country.clone())
     }
@@ -171,3 +200,141 @@ impl ToStr for Option<String> {
         self.as_ref().map(|ss| ss.as_str()).unwrap_or_default()
     }
 }
+
+pub trait DurationExt {
+    fn to_human_string(&self) -> String;
+}
+
+impl DurationExt for chrono::TimeDelta {
+    fn to_human_string(&self) -> String {
+        let hours = self.num_hours();
+        let minutes = self.num_minutes() % 60;
+        if hours > 0 {
+            tr!("{} h {} min", hours, minutes)
+        } else if minutes > 0 {
+            tr!("{} min", minutes)
+        } else {
+            tr!("{} s", self.num_seconds().max(0))
+        }
+    }
+}
+
+/// A code grouped by four with `CodeFormatExt::regroup_by_four`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RegroupedCode {
+    /// The code without separators.
+    pub code: String,
+    /// The code with separators.
+    pub text: String,
+    /// The caret in `text`, in characters.
+    pub position: usize,
+}
+
+pub trait CodeFormatExt {
+    /// Splits into groups of four characters, e.g. `1234 5678` for account numbers.
+    fn group_by_four(&self, separator: char) -> String;
+
+    /// Keeps at most `max_len` of the characters `normalize` lets through,
+    /// grouped by four. The caret at `position` (in characters) is moved to
+    /// behind the same character in the result.
+    fn regroup_by_four(
+        &self,
+        position: usize,
+        max_len: usize,
+        separator: char,
+        normalize: impl Fn(char) -> Option<char>,
+    ) -> RegroupedCode;
+}
+
+impl CodeFormatExt for str {
+    fn group_by_four(&self, separator: char) -> String {
+        let mut grouped = String::with_capacity(self.len() + self.len() / 4);
+        for (i, c) in self.chars().enumerate() {
+            if i > 0 && i % 4 == 0 {
+                grouped.push(separator);
+            }
+            grouped.push(c);
+        }
+        grouped
+    }
+
+    fn regroup_by_four(
+        &self,
+        position: usize,
+        max_len: usize,
+        separator: char,
+        normalize: impl Fn(char) -> Option<char>,
+    ) -> RegroupedCode {
+        let code: String = self.chars().filter_map(&normalize).take(max_len).collect();
+        let kept_before = self
+            .chars()
+            .take(position)
+            .filter_map(&normalize)
+            .count()
+            .min(code.chars().count());
+
+        RegroupedCode {
+            text: code.group_by_four(separator),
+            code,
+            // Every full group before the caret is followed by a separator,
+            // unless the caret is right behind it.
+            position: kept_before + kept_before.saturating_sub(1) / 4,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn digit(c: char) -> Option<char> {
+        c.is_ascii_digit().then_some(c)
+    }
+
+    fn regroup(text: &str, position: usize) -> (String, usize) {
+        let regrouped = text.regroup_by_four(position, 16, ' ', digit);
+        (regrouped.text, regrouped.position)
+    }
+
+    #[test]
+    fn groups_by_four() {
+        assert_eq!("".group_by_four(' '), "");
+        assert_eq!("123".group_by_four(' '), "123");
+        assert_eq!("1234".group_by_four(' '), "1234");
+        assert_eq!("12345".group_by_four(' '), "1234 5");
+        assert_eq!("ABCDEFGHJKLMNPQR".group_by_four('-'), "ABCD-EFGH-JKLM-NPQR");
+        assert_eq!("ÅÄÖÜ1".group_by_four('-'), "ÅÄÖÜ-1");
+    }
+
+    #[test]
+    fn regroups_pasted_codes() {
+        let regrouped = " 1234-5678 9012\t3456 7890".regroup_by_four(0, 16, ' ', digit);
+
+        assert_eq!(regrouped.code, "1234567890123456");
+        assert_eq!(regrouped.text, "1234 5678 9012 3456");
+        assert_eq!(regrouped.position, 0);
+    }
+
+    #[test]
+    fn caret_stays_behind_the_same_character() {
+        // Typing at the end.
+        assert_eq!(regroup("12345", 5), ("1234 5".to_string(), 6));
+        // Typing in the middle shifts the following groups.
+        assert_eq!(regroup("1230 4 5678", 4), ("1230 4567 8".to_string(), 4));
+        assert_eq!(regroup("1234 95678", 6), ("1234 9567 8".to_string(), 6));
+        // Deleting in the middle.
+        assert_eq!(regroup("1234 678", 5), ("1234 678".to_string(), 4));
+        // Deleting a separator only moves the caret.
+        assert_eq!(regroup("12345678", 4), ("1234 5678".to_string(), 4));
+        // Rejected characters don't move it.
+        assert_eq!(regroup("12a34", 3), ("1234".to_string(), 2));
+    }
+
+    #[test]
+    fn extra_characters_are_dropped() {
+        let regrouped = "12345678901234567890".regroup_by_four(20, 16, ' ', digit);
+
+        assert_eq!(regrouped.text, "1234 5678 9012 3456");
+        assert_eq!(regrouped.position, 19);
+    }
+}
@@ -0,0 +1,442 @@
+use std::{
+    fmt::Write as _,
+    fs::{self, OpenOptions},
+    io::{self, Write as _},
+    path::PathBuf,
+};
+
+use chrono::prelude::*;
+use mullvad_types::states::TunnelState;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    extensions::TunnelStateExt,
+    mullvad::{Event, EventSubscription},
+};
+
+const HISTORY_FILE_NAME: &str = "history.jsonl";
+
+/// The size at which the history file is rotated.
+const MAX_FILE_SIZE: u64 = 1024 * 1024;
+
+/// How many rotated files are kept besides the current one.
+const MAX_ROTATED_FILES: usize = 3;
+
+/// One line of the history file.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct HistoryEntry {
+    pub timestamp: DateTime<Utc>,
+    pub state: String,
+    pub hostname: Option<String>,
+    pub country: Option<String>,
+    pub city: Option<String>,
+    pub protocol: Option<String>,
+    pub error_cause: Option<String>,
+    pub time_to_connect_ms: Option<i64>,
+    /// The first entry after the app started. Sessions before it ended while
+    /// nothing was recording.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub is_first_after_start: bool,
+}
+
+impl HistoryEntry {
+    pub fn get_location(&self) -> Option<String> {
+        match (&self.city, &self.country) {
+            (Some(city), Some(country)) => Some(format!("{city}, {country}")),
+            (None, Some(country)) => Some(country.clone()),
+            _ => None,
+        }
+    }
+
+    pub fn matches(&self, text: &str) -> bool {
+        let text = text.to_lowercase();
+        [
+            Some(&self.state),
+            self.hostname.as_ref(),
+            self.country.as_ref(),
+            self.city.as_ref(),
+            self.protocol.as_ref(),
+            self.error_cause.as_ref(),
+        ]
+        .into_iter()
+        .flatten()
+        .any(|value| value.to_lowercase().contains(&text))
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SessionEnd {
+    Ongoing,
+    At(DateTime<Utc>),
+    /// The app was closed before the session ended.
+    Unknown,
+}
+
+/// A period of time the tunnel was connected to one relay.
+#[derive(Debug, Clone)]
+pub struct Session {
+    pub start: DateTime<Utc>,
+    pub end: SessionEnd,
+    pub entry: HistoryEntry,
+}
+
+impl Session {
+    pub fn get_duration(&self) -> Option<chrono::TimeDelta> {
+        match self.end {
+            SessionEnd::Ongoing => Some(Utc::now() - self.start),
+            SessionEnd::At(end) => Some(end - self.start),
+            SessionEnd::Unknown => None,
+        }
+    }
+}
+
+/// An append-only JSON Lines log of tunnel state transitions.
+#[derive(Debug, Clone)]
+pub struct HistoryLog {
+    path: PathBuf,
+}
+
+impl Default for HistoryLog {
+    fn default() -> Self {
+        Self::new(
+            gtk::glib::user_data_dir()
+                .join("mullvadwaita")
+                .join(HISTORY_FILE_NAME),
+        )
+    }
+}
+
+impl HistoryLog {
+    pub fn new(path: PathBuf) -> Self {
+        Self { path }
+    }
+
+    fn get_rotated_path(&self, index: usize) -> PathBuf {
+        let mut path = self.path.clone().into_os_string();
+        path.push(format!(".{index}"));
+        path.into()
+    }
+
+    /// Appends the entry on a blocking thread, rotating the file if it's full.
+    pub async fn append(&self, entry: HistoryEntry) -> io::Result<()> {
+        let log = self.clone();
+        tokio::task::spawn_blocking(move || log.append_blocking(&entry)).await?
+    }
+
+    fn append_blocking(&self, entry: &HistoryEntry) -> io::Result<()> {
+        if let Some(dir) = self.path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+
+        if fs::metadata(&self.path).is_ok_and(|metadata| metadata.len() >= MAX_FILE_SIZE) {
+            self.rotate()?;
+        }
+
+        let mut line = serde_json::to_string(entry)?;
+        line.push('\n');
+
+        OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)?
+            .write_all(line.as_bytes())
+    }
+
+    fn rotate(&self) -> io::Result<()> {
+        for index in (1..MAX_ROTATED_FILES).rev() {
+            let from = self.get_rotated_path(index);
+            if from.exists() {
+                fs::rename(from, self.get_rotated_path(index + 1))?;
+            }
+        }
+        fs::rename(&self.path, self.get_rotated_path(1))
+    }
+
+    /// Reads every entry on a blocking thread, oldest first, skipping unparsable lines.
+    pub async fn read_all(&self) -> Vec<HistoryEntry> {
+        let log = self.clone();
+        tokio::task::spawn_blocking(move || log.read_all_blocking())
+            .await
+            .unwrap_or_else(|err| {
+                log::warn!("Can't read connection history: {err}");
+                vec![]
+            })
+    }
+
+    fn read_all_blocking(&self) -> Vec<HistoryEntry> {
+        (1..=MAX_ROTATED_FILES)
+            .rev()
+            .map(|index| self.get_rotated_path(index))
+            .chain([self.path.clone()])
+            .filter_map(|path| fs::read_to_string(path).ok())
+            .flat_map(|content| {
+                content
+                    .lines()
+                    .filter_map(|line| {
+                        serde_json::from_str(line)
+                            .inspect_err(|err| log::debug!("Bad history line: {err}"))
+                            .ok()
+                    })
+                    .collect::<Vec<_>>()
+            })
+            .collect()
+    }
+}
+
+/// Turns tunnel states into history entries, skipping repeated states.
+#[derive(Debug, Default)]
+pub struct HistoryRecorder {
+    has_recorded: bool,
+    last: Option<(&'static str, Option<String>)>,
+    connecting_since: Option<DateTime<Utc>>,
+}
+
+impl HistoryRecorder {
+    pub fn get_entry(&mut self, tunnel_state: &TunnelState) -> Option<HistoryEntry> {
+        let now = Utc::now();
+        let state = tunnel_state.get_state_name();
+        let hostname = tunnel_state.get_hostname();
+
+        let current = Some((state, hostname.clone()));
+        if self.last == current {
+            return None;
+        }
+        self.last = current;
+
+        let mut time_to_connect_ms = None;
+        if tunnel_state.is_connecting_or_reconnecting() {
+            self.connecting_since.get_or_insert(now);
+        } else if tunnel_state.is_connected() {
+            time_to_connect_ms = self
+                .connecting_since
+                .take()
+                .map(|since| (now - since).num_milliseconds());
+        } else {
+            self.connecting_since = None;
+        }
+
+        Some(HistoryEntry {
+            timestamp: now,
+            state: state.to_string(),
+            hostname,
+            country: tunnel_state.get_country(),
+            city: tunnel_state.get_city(),
+            protocol: tunnel_state.get_tunnel_protocol(),
+            error_cause: tunnel_state.get_error_cause(),
+            time_to_connect_ms,
+            is_first_after_start: !std::mem::replace(&mut self.has_recorded, true),
+        })
+    }
+}
+
+pub async fn record_history(mut events: EventSubscription, log: HistoryLog) {
+    let mut recorder = HistoryRecorder::default();
+
+    while let Some(event) = events.recv().await {
+        let Event::TunnelState(tunnel_state) = event else {
+            continue;
+        };
+        if let Some(entry) = recorder.get_entry(&tunnel_state) {
+            if let Err(err) = log.append(entry).await {
+                log::warn!("Can't write connection history: {err}");
+            }
+        }
+    }
+}
+
+/// Every connected session in the entries, oldest first.
+pub fn get_sessions(entries: &[HistoryEntry]) -> Vec<Session> {
+    let mut sessions: Vec<Session> = vec![];
+
+    for entry in entries {
+        if let Some(session) = sessions
+            .last_mut()
+            .filter(|session| session.end == SessionEnd::Ongoing)
+        {
+            session.end = if entry.is_first_after_start {
+                SessionEnd::Unknown
+            } else {
+                SessionEnd::At(entry.timestamp)
+            };
+        }
+        if entry.state == "connected" {
+            sessions.push(Session {
+                start: entry.timestamp,
+                end: SessionEnd::Ongoing,
+                entry: entry.clone(),
+            });
+        }
+    }
+
+    sessions
+}
+
+pub fn to_csv(entries: &[HistoryEntry]) -> String {
+    fn escape(value: &str) -> String {
+        if value.contains([',', '"', '\n', '\r']) {
+            format!("\"{}\"", value.replace('"', "\"\""))
+        } else {
+            value.to_string()
+        }
+    }
+
+    let mut csv = String::from(
+        "timestamp,state,hostname,country,city,protocol,error_cause,time_to_connect_ms\n",
+    );
+
+    for entry in entries {
+        let _ = writeln!(
+            csv,
+            "{},{},{},{},{},{},{},{}",
+            entry.timestamp.to_rfc3339(),
+            escape(&entry.state),
+            escape(entry.hostname.as_deref().unwrap_or_default()),
+            escape(entry.country.as_deref().unwrap_or_default()),
+            escape(entry.city.as_deref().unwrap_or_default()),
+            escape(entry.protocol.as_deref().unwrap_or_default()),
+            escape(entry.error_cause.as_deref().unwrap_or_default()),
+            entry
+                .time_to_connect_ms
+                .map(|ms| ms.to_string())
+                .unwrap_or_default(),
+        );
+    }
+
+    csv
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(minutes: i64, state: &str) -> HistoryEntry {
+        HistoryEntry {
+            timestamp: DateTime::UNIX_EPOCH + chrono::TimeDelta::minutes(minutes),
+            state: state.to_string(),
+            hostname: Some("se-got-wg-001".to_string()),
+            country: Some("Sweden".to_string()),
+            city: Some("Gothenburg".to_string()),
+            protocol: Some("WireGuard".to_string()),
+            error_cause: None,
+            time_to_connect_ms: None,
+            is_first_after_start: false,
+        }
+    }
+
+    fn get_temp_log(name: &str) -> HistoryLog {
+        let dir = std::env::temp_dir().join(format!(
+            "mullvadwaita-history-{}-{name}",
+            std::process::id()
+        ));
+        let _ = fs::remove_dir_all(&dir);
+        HistoryLog::new(dir.join(HISTORY_FILE_NAME))
+    }
+
+    #[test]
+    fn sessions_end_at_the_next_state() {
+        let entries = [
+            entry(0, "connecting"),
+            entry(1, "connected"),
+            entry(10, "disconnecting"),
+            entry(11, "disconnected"),
+            entry(20, "connected"),
+        ];
+        let sessions = get_sessions(&entries);
+
+        assert_eq!(sessions.len(), 2);
+        assert_eq!(sessions[0].start, entries[1].timestamp);
+        assert_eq!(sessions[0].end, SessionEnd::At(entries[2].timestamp));
+        assert_eq!(
+            sessions[0].get_duration(),
+            Some(chrono::TimeDelta::minutes(9))
+        );
+        assert_eq!(sessions[1].end, SessionEnd::Ongoing);
+    }
+
+    #[test]
+    fn sessions_dont_span_the_time_the_app_was_closed() {
+        let restarted = HistoryEntry {
+            is_first_after_start: true,
+            ..entry(60, "connected")
+        };
+        let entries = [entry(0, "connected"), restarted, entry(70, "disconnected")];
+        let sessions = get_sessions(&entries);
+
+        assert_eq!(sessions.len(), 2);
+        assert_eq!(sessions[0].end, SessionEnd::Unknown);
+        assert_eq!(sessions[0].get_duration(), None);
+        assert_eq!(sessions[1].start, entries[1].timestamp);
+        assert_eq!(sessions[1].end, SessionEnd::At(entries[2].timestamp));
+    }
+
+    #[test]
+    fn csv_quotes_special_characters() {
+        let mut special = entry(0, "error");
+        special.city = Some("Göteborg, Västra".to_string());
+        special.error_cause = Some("said \"no\"\r\nthen quit".to_string());
+        special.time_to_connect_ms = Some(1500);
+        let csv = to_csv(&[special]);
+        let mut lines = csv.split('\n');
+
+        assert_eq!(
+            lines.next(),
+            Some("timestamp,state,hostname,country,city,protocol,error_cause,time_to_connect_ms")
+        );
+        assert_eq!(
+            csv.split_once('\n').map(|(_, rest)| rest),
+            Some(
+                "1970-01-01T00:00:00+00:00,error,se-got-wg-001,Sweden,\"Göteborg, Västra\",\
+                 WireGuard,\"said \"\"no\"\"\r\nthen quit\",1500\n"
+            )
+        );
+        assert!(to_csv(&[entry(0, "a\rb")]).contains("\"a\rb\""));
+    }
+
+    #[test]
+    fn entries_survive_a_round_trip() {
+        let log = get_temp_log("round-trip");
+        let first = HistoryEntry {
+            is_first_after_start: true,
+            ..entry(0, "connecting")
+        };
+
+        for entry in [first.clone(), entry(1, "connected")] {
+            log.append_blocking(&entry).unwrap();
+        }
+        fs::write(
+            &log.path,
+            fs::read_to_string(&log.path).unwrap() + "not json\n",
+        )
+        .unwrap();
+
+        assert_eq!(log.read_all_blocking(), vec![first, entry(1, "connected")]);
+        let _ = fs::remove_dir_all(log.path.parent().unwrap());
+    }
+
+    #[tokio::test]
+    async fn full_file_is_rotated() {
+        let log = get_temp_log("rotation");
+
+        for minutes in 0..=MAX_ROTATED_FILES as i64 + 1 {
+            log.append(entry(minutes, "connected")).await.unwrap();
+            // Fill the file up so the next entry starts a new one.
+            let mut file = OpenOptions::new().append(true).open(&log.path).unwrap();
+            file.write_all(&vec![b' '; MAX_FILE_SIZE as usize]).unwrap();
+            file.write_all(b"\n").unwrap();
+        }
+
+        assert!(!log.get_rotated_path(MAX_ROTATED_FILES + 1).exists());
+        // The oldest entry was dropped with the oldest file.
+        let minutes: Vec<_> = log
+            .read_all()
+            .await
+            .iter()
+            .map(|entry| entry.timestamp.timestamp() / 60)
+            .collect();
+        assert_eq!(
+            minutes,
+            (1..=MAX_ROTATED_FILES as i64 + 1).collect::<Vec<_>>()
+        );
+        let _ = fs::remove_dir_all(log.path.parent().unwrap());
+    }
+}
@@ -0,0 +1,126 @@
+use std::{
+    os::unix::fs::PermissionsExt,
+    path::{Path, PathBuf},
+    process::Stdio,
+    time::Duration,
+};
+
+use mullvad_types::states::TunnelState;
+use tokio::{fs, process::Command, task::JoinSet};
+
+use crate::{
+    config::Config,
+    extensions::{ToStr, TunnelStateExt},
+    mullvad::{Event, EventSubscription},
+};
+
+const HOOK_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// `~/.config/mullvadwaita/hooks/`, containing a `<state>.d/` directory of
+/// executables per tunnel state.
+pub fn get_hooks_dir() -> PathBuf {
+    Config::get_config_dir().join("hooks")
+}
+
+/// Runs the hook scripts of every tunnel state received. Repeated updates of
+/// the same state (e.g. a location update while connected) don't run the hooks
+/// again.
+///
+/// Hooks run side by side, so a hanging one doesn't hold up the others. The
+/// ones still running are killed when this is dropped.
+pub async fn run_hooks(mut events: EventSubscription) {
+    let mut last_state_name = None;
+    let mut running = JoinSet::new();
+
+    while let Some(event) = events.recv().await {
+        while running.try_join_next().is_some() {}
+
+        let Event::TunnelState(tunnel_state) = event else {
+            continue;
+        };
+        let state_name = tunnel_state.get_state_name();
+        if last_state_name == Some(state_name) {
+            continue;
+        }
+        last_state_name = Some(state_name);
+
+        let dir = get_hooks_dir().join(format!("{state_name}.d"));
+        for script in get_executables(&dir).await {
+            running.spawn(run_hook(script, tunnel_state.clone()));
+        }
+    }
+}
+
+async fn get_executables(dir: &Path) -> Vec<PathBuf> {
+    let mut executables = vec![];
+
+    let Ok(mut entries) = fs::read_dir(dir).await else {
+        return executables;
+    };
+
+    while let Ok(Some(entry)) = entries.next_entry().await {
+        let path = entry.path();
+        match fs::metadata(&path).await {
+            Ok(metadata) if metadata.is_file() && metadata.permissions().mode() & 0o111 != 0 => {
+                executables.push(path)
+            }
+            Ok(_) => log::debug!("Skipping non-executable hook {path:?}"),
+            Err(err) => log::warn!("Can't read hook {path:?}: {err}"),
+        }
+    }
+
+    executables.sort();
+    executables
+}
+
+async fn run_hook(script: PathBuf, tunnel_state: TunnelState) {
+    log::info!("Running hook {script:?}");
+
+    let child = Command::new(&script)
+        .env("MULLVAD_STATE", tunnel_state.get_state_name())
+        .env("MULLVAD_HOSTNAME", tunnel_state.get_hostname().to_str())
+        .env("MULLVAD_COUNTRY", tunnel_state.get_country().to_str())
+        .env("MULLVAD_CITY", tunnel_state.get_city().to_str())
+        .env("MULLVAD_TUNNEL_IN", tunnel_state.get_tunnel_in().to_str())
+        .env(
+            "MULLVAD_TUNNEL_OUT",
+            tunnel_state.get_tunnel_out().to_str().replace('\n', " "),
+        )
+        .env(
+            "MULLVAD_PROTOCOL",
+            tunnel_state.get_tunnel_protocol().to_str(),
+        )
+        .env("MULLVAD_ERROR", tunnel_state.get_error_cause().to_str())
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .kill_on_drop(true)
+        .spawn();
+
+    let child = match child {
+        Ok(child) => child,
+        Err(err) => {
+            log::warn!("Can't run hook {script:?}: {err}");
+            return;
+        }
+    };
+
+    // On timeout the child is dropped and thereby killed.
+    match tokio::time::timeout(HOOK_TIMEOUT, child.wait_with_output()).await {
+        Ok(Ok(output)) => {
+            for line in String::from_utf8_lossy(&output.stdout).lines() {
+                log::info!("{script:?}: {line}");
+            }
+            for line in String::from_utf8_lossy(&output.stderr).lines() {
+                log::warn!("{script:?}: {line}");
+            }
+            if output.status.success() {
+                log::info!("Hook {script:?} finished.");
+            } else {
+                log::warn!("Hook {script:?} failed: {}", output.status);
+            }
+        }
+        Ok(Err(err)) => log::warn!("Hook {script:?} failed: {err}"),
+        Err(_) => log::warn!("Hook {script:?} timed out after {HOOK_TIMEOUT:?} and was killed."),
+    }
+}
@@ -1,11 +1,21 @@
+mod config;
+mod connection_check;
 mod extensions;
+mod history;
+mod hooks;
 mod macros;
 mod mullvad;
+mod saved_state;
+mod schedule;
+mod systemd;
+mod traffic;
 mod ui;
 
 use ui::app::AppModel;
 
-use anyhow::Result;
+use std::path::PathBuf;
+
+use anyhow::{anyhow, Result};
 use relm4::RelmApp;
 use tr::tr;
 
@@ -34,17 +44,41 @@ fn init_gettext(
     )
 }
 
+/// Takes `--socket <path>` out of the arguments, the rest is left to GTK.
+fn parse_args() -> Result<(Option<PathBuf>, Vec<String>)> {
+    let mut socket_path = None;
+    let mut args = vec![];
+
+    let mut all_args = std::env::args();
+    while let Some(arg) = all_args.next() {
+        if arg == "--socket" {
+            let path = all_args
+                .next()
+                .ok_or_else(|| anyhow!("--socket needs a path"))?;
+            socket_path = Some(PathBuf::from(path));
+        } else if let Some(path) = arg.strip_prefix("--socket=") {
+            socket_path = Some(PathBuf::from(path));
+        } else {
+            args.push(arg);
+        }
+    }
+
+    Ok((socket_path, args))
+}
+
 fn main() -> Result<()> {
     init_logger()?;
     log::debug!("mullvadwaita starting...");
     init_gettext()?;
 
-    let app = RelmApp::new("draft.mullvadwaita");
+    let (socket_path, args) = parse_args()?;
+
+    let app = RelmApp::new("draft.mullvadwaita").with_args(args);
     relm4::set_global_css(include_str!("./res/global.css"));
 
     relm4_icons::initialize_icons(icon_names::GRESOURCE_BYTES, icon_names::RESOURCE_PREFIX);
 
-    app.run_async::<AppModel>(());
+    app.run_async::<AppModel>(socket_path);
 
     Ok(())
 }
@@ -0,0 +1,356 @@
+use std::{
+    future::Future,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
+    time::{Duration, Instant},
+};
+
+use anyhow::{anyhow, Result};
+use chrono::{DateTime, Utc};
+use mullvad_types::account::{AccountData, AccountNumber};
+use tokio::sync::Mutex;
+
+/// How long fetched account data is used without asking the daemon again.
+const ACCOUNT_DATA_TTL: Duration = Duration::from_secs(60);
+
+const MIN_BACKOFF: Duration = Duration::from_secs(5);
+const MAX_BACKOFF: Duration = Duration::from_secs(5 * 60);
+
+/// Account data together with how fresh it is.
+#[derive(Debug, Clone)]
+pub struct CachedAccountData {
+    pub data: AccountData,
+    pub updated: DateTime<Utc>,
+    /// The last refresh failed, so `data` may be outdated.
+    pub is_stale: bool,
+}
+
+#[derive(Debug, Default)]
+struct CacheState {
+    account: Option<AccountNumber>,
+    data: Option<AccountData>,
+    updated: Option<DateTime<Utc>>,
+    fetched: Option<Instant>,
+    failures: u32,
+    retry_at: Option<Instant>,
+    last_error: Option<String>,
+    /// The `AccountCache::generation` this state was last invalidated for.
+    generation: u64,
+}
+
+/// Account data cache shared by every clone of the `DaemonConnector`.
+///
+/// The lock is held while fetching, so concurrent callers wait for
+/// the one in-flight request and then get its result from the cache.
+/// Invalidating doesn't take the lock, it's applied by the next `get`.
+#[derive(Debug, Default, Clone)]
+pub struct AccountCache {
+    state: Arc<Mutex<CacheState>>,
+    generation: Arc<AtomicU64>,
+}
+
+impl CacheState {
+    fn get_cached(&self, is_stale: bool) -> Option<CachedAccountData> {
+        Some(CachedAccountData {
+            data: self.data.clone()?,
+            updated: self.updated?,
+            is_stale,
+        })
+    }
+
+    fn get_backoff(&self) -> Duration {
+        let exponent = self.failures.saturating_sub(1).min(16);
+        MIN_BACKOFF.saturating_mul(1 << exponent).min(MAX_BACKOFF)
+    }
+}
+
+impl AccountCache {
+    /// Returns cached data while it's fresh, otherwise fetches it with `fetch`.
+    /// If fetching fails, the last known data is returned marked as stale.
+    pub async fn get<F, Fut>(&self, account: AccountNumber, fetch: F) -> Result<CachedAccountData>
+    where
+        F: FnOnce(AccountNumber) -> Fut,
+        Fut: Future<Output = Result<AccountData>>,
+    {
+        let mut state = self.state.lock().await;
+
+        let generation = self.generation.load(Ordering::SeqCst);
+        if state.account.as_ref() != Some(&account) {
+            *state = CacheState {
+                account: Some(account.clone()),
+                generation,
+                ..Default::default()
+            };
+        } else if state.generation != generation {
+            // Invalidated meanwhile, possibly while the last fetch was running.
+            state.fetched = None;
+            state.failures = 0;
+            state.retry_at = None;
+            state.generation = generation;
+        }
+
+        let now = Instant::now();
+
+        if state.failures == 0
+            && state
+                .fetched
+                .is_some_and(|fetched| now - fetched < ACCOUNT_DATA_TTL)
+        {
+            if let Some(cached) = state.get_cached(false) {
+                return Ok(cached);
+            }
+        }
+
+        if state.retry_at.is_some_and(|retry_at| now < retry_at) {
+            return state.get_cached(true).ok_or_else(|| {
+                anyhow!(state
+                    .last_error
+                    .clone()
+                    .unwrap_or_else(|| "Account data is unavailable".to_string()))
+            });
+        }
+
+        match fetch(account).await {
+            Ok(data) => {
+                state.data = Some(data);
+                state.updated = Some(Utc::now());
+                state.fetched = Some(Instant::now());
+                state.failures = 0;
+                state.retry_at = None;
+                state.last_error = None;
+
+                Ok(state.get_cached(false).expect("just fetched"))
+            }
+            Err(err) => {
+                state.failures += 1;
+                let backoff = state.get_backoff();
+                log::debug!("Can't fetch account data, retrying in {backoff:?}: {err:#?}");
+
+                state.retry_at = Some(Instant::now() + backoff);
+                state.last_error = Some(err.to_string());
+
+                state.get_cached(true).ok_or(err)
+            }
+        }
+    }
+
+    /// Makes the next `get` ask the daemon, e.g. after time was added.
+    /// The last known data is kept as a fallback if the refresh fails.
+    /// Doesn't wait for a fetch in flight, its result counts as outdated.
+    pub fn invalidate(&self) {
+        self.generation.fetch_add(1, Ordering::SeqCst);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicU32, Ordering};
+
+    use super::*;
+
+    const ACCOUNT: &str = "1234123412341234";
+
+    fn get_account_data(id: &str) -> AccountData {
+        AccountData {
+            id: id.to_string(),
+            expiry: Utc::now(),
+        }
+    }
+
+    /// A fetch returning `result` and counting its calls.
+    fn counting<'a>(
+        calls: &'a AtomicU32,
+        result: Result<AccountData, &'static str>,
+    ) -> impl FnOnce(AccountNumber) -> std::future::Ready<Result<AccountData>> + 'a {
+        move |_| {
+            calls.fetch_add(1, Ordering::SeqCst);
+            std::future::ready(result.map_err(|err| anyhow!(err)))
+        }
+    }
+
+    #[tokio::test]
+    async fn fresh_data_is_not_fetched_again() {
+        let cache = AccountCache::default();
+        let calls = AtomicU32::new(0);
+
+        for _ in 0..3 {
+            let cached = cache
+                .get(
+                    ACCOUNT.to_string(),
+                    counting(&calls, Ok(get_account_data("a"))),
+                )
+                .await
+                .unwrap();
+            assert_eq!(cached.data.id, "a");
+            assert!(!cached.is_stale);
+        }
+        assert_eq!(calls.load(Ordering::SeqCst), 1);
+    }
+
+    #[tokio::test]
+    async fn failures_fall_back_to_stale_data_and_back_off() {
+        let cache = AccountCache::default();
+        let calls = AtomicU32::new(0);
+
+        cache
+            .get(
+                ACCOUNT.to_string(),
+                counting(&calls, Ok(get_account_data("a"))),
+            )
+            .await
+            .unwrap();
+        cache.invalidate();
+
+        let cached = cache
+            .get(ACCOUNT.to_string(), counting(&calls, Err("API down")))
+            .await
+            .unwrap();
+        assert_eq!(cached.data.id, "a");
+        assert!(cached.is_stale);
+
+        // Backing off, the daemon isn't asked.
+        let cached = cache
+            .get(
+                ACCOUNT.to_string(),
+                counting(&calls, Ok(get_account_data("b"))),
+            )
+            .await
+            .unwrap();
+        assert_eq!(cached.data.id, "a");
+        assert!(cached.is_stale);
+        assert_eq!(calls.load(Ordering::SeqCst), 2);
+    }
+
+    #[tokio::test]
+    async fn failures_without_data_keep_the_error() {
+        let cache = AccountCache::default();
+        let calls = AtomicU32::new(0);
+
+        let err = cache
+            .get(ACCOUNT.to_string(), counting(&calls, Err("API down")))
+            .await
+            .unwrap_err();
+        assert_eq!(err.to_string(), "API down");
+
+        let err = cache
+            .get(
+                ACCOUNT.to_string(),
+                counting(&calls, Ok(get_account_data("a"))),
+            )
+            .await
+            .unwrap_err();
+        assert_eq!(err.to_string(), "API down");
+        assert_eq!(calls.load(Ordering::SeqCst), 1);
+    }
+
+    #[tokio::test]
+    async fn invalidate_ends_the_backoff() {
+        let cache = AccountCache::default();
+        let calls = AtomicU32::new(0);
+
+        let _ = cache
+            .get(ACCOUNT.to_string(), counting(&calls, Err("API down")))
+            .await;
+        cache.invalidate();
+
+        let cached = cache
+            .get(
+                ACCOUNT.to_string(),
+                counting(&calls, Ok(get_account_data("a"))),
+            )
+            .await
+            .unwrap();
+        assert_eq!(cached.data.id, "a");
+        assert!(!cached.is_stale);
+        assert_eq!(calls.load(Ordering::SeqCst), 2);
+    }
+
+    #[tokio::test]
+    async fn another_account_starts_over() {
+        let cache = AccountCache::default();
+        let calls = AtomicU32::new(0);
+
+        cache
+            .get(
+                ACCOUNT.to_string(),
+                counting(&calls, Ok(get_account_data("a"))),
+            )
+            .await
+            .unwrap();
+
+        let err = cache
+            .get(
+                "9999999999999999".to_string(),
+                counting(&calls, Err("API down")),
+            )
+            .await
+            .unwrap_err();
+        assert_eq!(err.to_string(), "API down");
+        assert_eq!(calls.load(Ordering::SeqCst), 2);
+    }
+
+    #[tokio::test]
+    async fn invalidate_doesnt_wait_for_a_fetch() {
+        let cache = AccountCache::default();
+        let calls = Arc::new(AtomicU32::new(0));
+
+        let fetching = {
+            let cache = cache.clone();
+            let calls = calls.clone();
+            tokio::spawn(async move {
+                cache
+                    .get(ACCOUNT.to_string(), |_| async move {
+                        calls.fetch_add(1, Ordering::SeqCst);
+                        tokio::time::sleep(Duration::from_millis(100)).await;
+                        Ok(get_account_data("before"))
+                    })
+                    .await
+            })
+        };
+        tokio::time::sleep(Duration::from_millis(10)).await;
+
+        cache.invalidate();
+        assert!(!fetching.is_finished());
+        assert_eq!(fetching.await.unwrap().unwrap().data.id, "before");
+
+        // What was fetched during the invalidation is outdated.
+        let cached = cache
+            .get(
+                ACCOUNT.to_string(),
+                counting(&calls, Ok(get_account_data("after"))),
+            )
+            .await
+            .unwrap();
+        assert_eq!(cached.data.id, "after");
+        assert_eq!(calls.load(Ordering::SeqCst), 2);
+    }
+
+    #[tokio::test]
+    async fn concurrent_callers_share_one_fetch() {
+        let cache = AccountCache::default();
+        let calls = Arc::new(AtomicU32::new(0));
+
+        let tasks: Vec<_> = (0..4)
+            .map(|_| {
+                let cache = cache.clone();
+                let calls = calls.clone();
+                tokio::spawn(async move {
+                    cache
+                        .get(ACCOUNT.to_string(), |_| async move {
+                            calls.fetch_add(1, Ordering::SeqCst);
+                            tokio::time::sleep(Duration::from_millis(20)).await;
+                            Ok(get_account_data("a"))
+                        })
+                        .await
+                        .unwrap()
+                })
+            })
+            .collect();
+        for task in tasks {
+            assert_eq!(task.await.unwrap().data.id, "a");
+        }
+        assert_eq!(calls.load(Ordering::SeqCst), 1);
+    }
+}
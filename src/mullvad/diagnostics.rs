@@ -0,0 +1,135 @@
+use std::{error::Error as _, io, time::Duration};
+
+use tonic::Code;
+
+use crate::tr;
+
+const MIN_RETRY_DELAY: Duration = Duration::from_secs(1);
+const MAX_RETRY_DELAY: Duration = Duration::from_secs(30);
+
+/// Why the daemon can't be reached.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ConnectionFailure {
+    /// The management socket doesn't exist, the daemon isn't running.
+    SocketMissing,
+    PermissionDenied,
+    /// The daemon speaks a different version of the management interface.
+    VersionMismatch(String),
+    /// The connection worked but then broke, or the socket was left by a dead daemon.
+    DaemonCrashed,
+    Other(String),
+}
+
+impl ConnectionFailure {
+    /// `was_connected` tells whether the event stream had been set up before the error.
+    pub fn classify(err: &anyhow::Error, was_connected: bool) -> ConnectionFailure {
+        for cause in err.chain() {
+            if let Some(io_error) = cause.downcast_ref::<io::Error>() {
+                match io_error.kind() {
+                    io::ErrorKind::NotFound => return ConnectionFailure::SocketMissing,
+                    io::ErrorKind::PermissionDenied => return ConnectionFailure::PermissionDenied,
+                    io::ErrorKind::ConnectionRefused => return ConnectionFailure::DaemonCrashed,
+                    _ => {}
+                }
+            }
+
+            if let Some(status) = cause.downcast_ref::<tonic::Status>() {
+                match status.code() {
+                    Code::Unimplemented => {
+                        return ConnectionFailure::VersionMismatch(status.message().to_string())
+                    }
+                    Code::Internal if status.message().contains("decode") => {
+                        return ConnectionFailure::VersionMismatch(status.message().to_string())
+                    }
+                    Code::PermissionDenied => return ConnectionFailure::PermissionDenied,
+                    _ => {}
+                }
+            }
+        }
+
+        if was_connected {
+            ConnectionFailure::DaemonCrashed
+        } else {
+            ConnectionFailure::Other(err.to_string())
+        }
+    }
+
+    pub fn get_description(&self) -> String {
+        match self {
+            ConnectionFailure::SocketMissing => tr!("The Mullvad system service is not running."),
+            ConnectionFailure::PermissionDenied => {
+                tr!("Not allowed to connect to the Mullvad system service.")
+            }
+            ConnectionFailure::VersionMismatch(details) => tr!(
+                "The Mullvad system service version is not supported by this app ({}).",
+                details
+            ),
+            ConnectionFailure::DaemonCrashed => {
+                tr!("The connection to the Mullvad system service was lost.")
+            }
+            ConnectionFailure::Other(details) => {
+                tr!("Can't connect to the Mullvad system service: {}", details)
+            }
+        }
+    }
+
+    pub fn get_suggestion(&self) -> String {
+        match self {
+            ConnectionFailure::SocketMissing => tr!(
+                "Make sure Mullvad VPN is installed, then start the service with <tt>systemctl start mullvad-daemon</tt>."
+            ),
+            ConnectionFailure::PermissionDenied => tr!(
+                "Check the permissions of the management socket, e.g. <tt>/var/run/mullvad-vpn</tt>."
+            ),
+            ConnectionFailure::VersionMismatch(_) => {
+                tr!("Update Mullvad VPN and this app to their latest versions.")
+            }
+            ConnectionFailure::DaemonCrashed => tr!(
+                "The service may have crashed or be restarting. See <tt>journalctl -u mullvad-daemon</tt> for details."
+            ),
+            ConnectionFailure::Other(_) => {
+                tr!("Run the app with <tt>RUST_LOG=debug</tt> for more details.")
+            }
+        }
+    }
+}
+
+/// State of the connection shared by the calls and the event stream.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub enum ConnectionHealth {
+    #[default]
+    Healthy,
+    /// The connection broke and is opened again on the next call.
+    Reconnecting,
+    Broken(ConnectionFailure),
+}
+
+/// Whether a call failed because the connection broke, rather than being refused by the daemon.
+pub fn is_transport_error(err: &anyhow::Error) -> bool {
+    err.chain().any(|cause| {
+        cause.downcast_ref::<tonic::transport::Error>().is_some()
+            || cause
+                .downcast_ref::<tonic::Status>()
+                // The daemon sends unavailable too, when it can't reach the API.
+                .is_some_and(|status| {
+                    status.code() == Code::Unavailable && status.source().is_some()
+                })
+            || cause.downcast_ref::<io::Error>().is_some_and(|io_error| {
+                matches!(
+                    io_error.kind(),
+                    io::ErrorKind::BrokenPipe
+                        | io::ErrorKind::ConnectionReset
+                        | io::ErrorKind::ConnectionRefused
+                        | io::ErrorKind::NotFound
+                )
+            })
+    })
+}
+
+/// Capped exponential backoff, `failures` counting from one.
+pub fn get_retry_delay(failures: u32) -> Duration {
+    let exponent = failures.saturating_sub(1).min(16);
+    MIN_RETRY_DELAY
+        .saturating_mul(1 << exponent)
+        .min(MAX_RETRY_DELAY)
+}
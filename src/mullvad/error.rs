@@ -0,0 +1,302 @@
+use std::{error::Error as _, io};
+
+use mullvad_management_interface::Error;
+use tonic::{Code, Status};
+
+use crate::tr;
+
+/// How the daemon passes on API errors it has no status code of its own for.
+const REST_ERROR_PREFIX: &str = "REST error: ";
+
+const HTTP_TOO_MANY_REQUESTS: u16 = 429;
+
+/// How loudly a failure is reported.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Severity {
+    Info,
+    /// The action was refused, trying again or otherwise may work.
+    Warning,
+    /// Nothing works until the user fixes something.
+    Error,
+}
+
+/// Why a daemon call failed, in the terms the user sees.
+#[derive(Debug, Clone, PartialEq)]
+pub enum DaemonError {
+    DaemonUnavailable,
+    PermissionDenied,
+    /// The daemon doesn't understand this app or the other way round.
+    UnsupportedDaemon,
+    /// The daemon can't reach the Mullvad API.
+    Network,
+    Timeout,
+    RateLimited,
+    InvalidAccount,
+    TooManyDevices,
+    AlreadyLoggedIn,
+    DeviceNotFound,
+    InvalidVoucher,
+    UsedVoucher,
+    Other(String),
+}
+
+impl DaemonError {
+    pub fn from_error(err: &anyhow::Error) -> DaemonError {
+        for cause in err.chain() {
+            if let Some(error) = cause.downcast_ref::<Error>() {
+                if let Some(daemon_error) = Self::from_interface_error(error) {
+                    return daemon_error;
+                }
+            }
+
+            if let Some(status) = cause.downcast_ref::<Status>() {
+                return Self::from_status(status);
+            }
+
+            if let Some(io_error) = cause.downcast_ref::<io::Error>() {
+                match io_error.kind() {
+                    io::ErrorKind::NotFound | io::ErrorKind::ConnectionRefused => {
+                        return DaemonError::DaemonUnavailable
+                    }
+                    io::ErrorKind::PermissionDenied => return DaemonError::PermissionDenied,
+                    _ => {}
+                }
+            }
+        }
+
+        DaemonError::Other(err.to_string())
+    }
+
+    /// `None` for errors which are better told by their source.
+    /// Every variant is listed, so new ones have to be sorted in when the interface is updated.
+    fn from_interface_error(error: &Error) -> Option<DaemonError> {
+        Some(match error {
+            Error::InvalidAccount => DaemonError::InvalidAccount,
+            Error::TooManyDevices => DaemonError::TooManyDevices,
+            Error::AlreadyLoggedIn => DaemonError::AlreadyLoggedIn,
+            Error::DeviceNotFound => DaemonError::DeviceNotFound,
+            Error::InvalidVoucher => DaemonError::InvalidVoucher,
+            Error::UsedVoucher => DaemonError::UsedVoucher,
+            Error::GrpcTransportError(_) => DaemonError::DaemonUnavailable,
+            Error::InvalidResponse(_) | Error::MissingDaemonEvent => DaemonError::UnsupportedDaemon,
+            Error::Rpc(status) => Self::from_status(status),
+            // The socket errors carry an `io::Error` telling more.
+            Error::PipeError(_)
+            | Error::StartServerError(_)
+            | Error::PermissionsError(_)
+            | Error::ObtainGidError(_)
+            | Error::SetGidError(_) => return None,
+            // Calls this app doesn't make.
+            Error::DurationTooLarge
+            | Error::PathMustBeUtf8
+            | Error::NoLocationData
+            | Error::CustomListExists
+            | Error::CustomListListNotFound
+            | Error::ApiAccessMethodSettingsNotFound
+            | Error::ApiAccessMethodNotFound => DaemonError::Other(error.to_string()),
+        })
+    }
+
+    /// The HTTP status of an API error the daemon passed on,
+    /// e.g. `REST error: Unexpected response status code 429 Too Many Requests - THROTTLED`.
+    fn get_api_status(status: &Status) -> Option<u16> {
+        status
+            .message()
+            .strip_prefix(REST_ERROR_PREFIX)?
+            .split_whitespace()
+            .filter(|word| word.len() == 3)
+            .find_map(|word| word.parse().ok().filter(|code| (100..600).contains(code)))
+    }
+
+    fn from_status(status: &Status) -> DaemonError {
+        match status.code() {
+            // The client already turns the daemon's resource exhausted into too many
+            // devices or a used voucher, the API's rate limiting arrives as unknown.
+            Code::Unknown if Self::get_api_status(status) == Some(HTTP_TOO_MANY_REQUESTS) => {
+                DaemonError::RateLimited
+            }
+            Code::DeadlineExceeded => DaemonError::Timeout,
+            // Statuses sent by the daemon have no source, the ones tonic makes
+            // up for a broken connection to the daemon carry the transport error.
+            Code::Unavailable if status.source().is_some() => DaemonError::DaemonUnavailable,
+            Code::Unavailable => DaemonError::Network,
+            Code::Unauthenticated => DaemonError::InvalidAccount,
+            Code::PermissionDenied => DaemonError::PermissionDenied,
+            Code::Unimplemented => DaemonError::UnsupportedDaemon,
+            _ => DaemonError::Other(status.message().to_string()),
+        }
+    }
+
+    pub fn get_message(&self) -> String {
+        match self {
+            DaemonError::DaemonUnavailable => {
+                tr!("The Mullvad system service is not reachable.")
+            }
+            DaemonError::PermissionDenied => {
+                tr!("Not allowed to talk to the Mullvad system service.")
+            }
+            DaemonError::UnsupportedDaemon => {
+                tr!("The Mullvad system service version is not supported by this app.")
+            }
+            DaemonError::Network => tr!("Can't reach the Mullvad servers, check your network."),
+            DaemonError::Timeout => tr!("The Mullvad servers took too long to answer."),
+            DaemonError::RateLimited => tr!("Too many attempts, please wait a bit and try again."),
+            DaemonError::InvalidAccount => tr!("The account number is invalid."),
+            DaemonError::TooManyDevices => tr!("There are too many devices on the account."),
+            DaemonError::AlreadyLoggedIn => tr!("Already logged in, log out first."),
+            DaemonError::DeviceNotFound => tr!("The device doesn't exist anymore."),
+            DaemonError::InvalidVoucher => tr!("This voucher code is invalid."),
+            DaemonError::UsedVoucher => tr!("This voucher code has already been used."),
+            DaemonError::Other(details) => tr!("Unexpected error: {}", details),
+        }
+    }
+
+    pub fn get_severity(&self) -> Severity {
+        match self {
+            DaemonError::DaemonUnavailable
+            | DaemonError::PermissionDenied
+            | DaemonError::UnsupportedDaemon
+            | DaemonError::Other(_) => Severity::Error,
+            DaemonError::Network
+            | DaemonError::Timeout
+            | DaemonError::RateLimited
+            | DaemonError::InvalidAccount
+            | DaemonError::TooManyDevices
+            | DaemonError::DeviceNotFound
+            | DaemonError::InvalidVoucher
+            | DaemonError::UsedVoucher => Severity::Warning,
+            DaemonError::AlreadyLoggedIn => Severity::Info,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fmt;
+
+    use anyhow::Context;
+
+    use super::*;
+
+    /// Stands in for the connection error tonic wraps into the statuses it makes up.
+    #[derive(Debug)]
+    struct BrokenConnection(Status);
+
+    impl fmt::Display for BrokenConnection {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            write!(f, "broken connection")
+        }
+    }
+
+    impl std::error::Error for BrokenConnection {
+        fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+            Some(&self.0)
+        }
+    }
+
+    fn classify(err: impl std::error::Error + Send + Sync + 'static) -> DaemonError {
+        DaemonError::from_error(&anyhow::Error::new(err).context("Some call failed"))
+    }
+
+    #[test]
+    fn interface_errors_keep_their_kind() {
+        assert_eq!(classify(Error::TooManyDevices), DaemonError::TooManyDevices);
+        assert_eq!(classify(Error::InvalidAccount), DaemonError::InvalidAccount);
+        assert_eq!(classify(Error::UsedVoucher), DaemonError::UsedVoucher);
+        assert_eq!(
+            classify(Error::MissingDaemonEvent),
+            DaemonError::UnsupportedDaemon
+        );
+    }
+
+    #[test]
+    fn statuses_are_told_by_their_code() {
+        let cases = [
+            (Status::deadline_exceeded("late"), DaemonError::Timeout),
+            (Status::unauthenticated("who?"), DaemonError::InvalidAccount),
+            (
+                Status::permission_denied("no"),
+                DaemonError::PermissionDenied,
+            ),
+            (
+                Status::unimplemented("what?"),
+                DaemonError::UnsupportedDaemon,
+            ),
+        ];
+        for (status, expected) in cases {
+            assert_eq!(classify(status), expected);
+        }
+    }
+
+    #[test]
+    fn api_rate_limiting_is_told_by_its_http_status() {
+        // As the daemon passes on an API error it has no status code for.
+        assert_eq!(
+            classify(Status::unknown(
+                "REST error: Unexpected response status code 429 Too Many Requests - THROTTLED"
+            )),
+            DaemonError::RateLimited
+        );
+
+        let message = "REST error: Unexpected response status code 500 Internal Server Error - ";
+        assert_eq!(
+            classify(Status::unknown(message)),
+            DaemonError::Other(message.to_string())
+        );
+    }
+
+    #[test]
+    fn other_messages_dont_decide_the_kind() {
+        assert_eq!(
+            classify(Status::internal("429 too many requests")),
+            DaemonError::Other("429 too many requests".to_string())
+        );
+        assert_eq!(
+            classify(Status::unknown("transport is connected")),
+            DaemonError::Other("transport is connected".to_string())
+        );
+        assert_eq!(
+            classify(Status::resource_exhausted("slow down")),
+            DaemonError::Other("slow down".to_string())
+        );
+    }
+
+    #[test]
+    fn unavailable_tells_the_daemon_from_the_api() {
+        // Sent by the daemon when it can't reach the API.
+        assert_eq!(
+            classify(Status::unavailable("Cannot reach the API")),
+            DaemonError::Network
+        );
+
+        // Made up by tonic when the connection to the daemon broke.
+        let status = Status::from_error(Box::new(BrokenConnection(Status::unavailable(
+            "connection refused",
+        ))));
+        assert_eq!(status.code(), Code::Unavailable);
+        assert_eq!(classify(status), DaemonError::DaemonUnavailable);
+    }
+
+    #[test]
+    fn socket_errors_are_told_by_the_io_error() {
+        let err = Err::<(), _>(io::Error::from(io::ErrorKind::NotFound))
+            .context("Connecting to the daemon failed")
+            .unwrap_err();
+        assert_eq!(
+            DaemonError::from_error(&err),
+            DaemonError::DaemonUnavailable
+        );
+
+        let err = anyhow::Error::new(io::Error::from(io::ErrorKind::PermissionDenied));
+        assert_eq!(DaemonError::from_error(&err), DaemonError::PermissionDenied);
+    }
+
+    #[test]
+    fn unknown_errors_keep_their_message() {
+        let err = anyhow::anyhow!("something odd");
+        assert_eq!(
+            DaemonError::from_error(&err),
+            DaemonError::Other("something odd".to_string())
+        );
+    }
+}
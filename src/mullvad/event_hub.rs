@@ -0,0 +1,268 @@
+use std::{
+    collections::VecDeque,
+    sync::{Arc, Mutex, MutexGuard, Once},
+};
+
+use mullvad_types::{
+    device::{DeviceEvent, DeviceEventCause},
+    relay_list::RelayList,
+    settings::Settings,
+    states::TunnelState,
+    version::AppVersionInfo,
+};
+use tokio::sync::broadcast::{self, error::RecvError};
+
+use super::{Event, MullvadDaemon};
+
+/// Events a subscriber may fall behind before it starts missing them.
+const CAPACITY: usize = 64;
+
+/// The latest daemon state, replayed to new and lagging subscribers.
+#[derive(Debug, Default)]
+struct Snapshot {
+    /// The last `ConnectingToDaemon` or `DaemonConnectionFailed`.
+    connection: Option<Event>,
+    settings: Option<Settings>,
+    tunnel_state: Option<TunnelState>,
+    device: Option<DeviceEvent>,
+    app_version_info: Option<AppVersionInfo>,
+    relay_list: Option<RelayList>,
+}
+
+impl Snapshot {
+    fn update(&mut self, event: &Event) {
+        match event {
+            Event::ConnectingToDaemon => {
+                // The state of a lost connection is stale, the daemon sends it again.
+                *self = Snapshot {
+                    connection: Some(event.clone()),
+                    ..Default::default()
+                };
+            }
+            Event::DaemonConnectionFailed { .. } => self.connection = Some(event.clone()),
+            Event::Setting(settings) => self.settings = Some(settings.clone()),
+            Event::TunnelState(tunnel_state) => self.tunnel_state = Some(tunnel_state.clone()),
+            Event::Device(device_event) => self.device = Some(device_event.clone()),
+            Event::AppVersionInfo(app_version_info) => {
+                self.app_version_info = Some(app_version_info.clone())
+            }
+            Event::RelayList(relay_list) => self.relay_list = Some(relay_list.clone()),
+            _ => {}
+        }
+    }
+
+    /// The state as events, in the order the daemon sends them after connecting.
+    fn get_events(&self) -> VecDeque<Event> {
+        let device = self.device.as_ref().map(|device_event| {
+            // A replayed login or revocation didn't just happen.
+            Event::Device(DeviceEvent {
+                cause: DeviceEventCause::Updated,
+                new_state: device_event.new_state.clone(),
+            })
+        });
+
+        [
+            self.connection.clone(),
+            self.settings.clone().map(Event::Setting),
+            self.tunnel_state.clone().map(Event::TunnelState),
+            device,
+            self.app_version_info.clone().map(Event::AppVersionInfo),
+            self.relay_list.clone().map(Event::RelayList),
+        ]
+        .into_iter()
+        .flatten()
+        .collect()
+    }
+}
+
+#[derive(Debug)]
+struct Inner {
+    daemon: Arc<dyn MullvadDaemon>,
+    sender: broadcast::Sender<Event>,
+    snapshot: Mutex<Snapshot>,
+    started: Once,
+}
+
+/// Reads the daemon events once and hands them out to any number of subscribers.
+#[derive(Debug, Clone)]
+pub struct EventHub {
+    inner: Arc<Inner>,
+}
+
+impl EventHub {
+    pub fn new(daemon: Arc<dyn MullvadDaemon>) -> Self {
+        EventHub {
+            inner: Arc::new(Inner {
+                daemon,
+                sender: broadcast::channel(CAPACITY).0,
+                snapshot: Mutex::default(),
+                started: Once::new(),
+            }),
+        }
+    }
+
+    /// The current state right away, then live events.
+    /// The daemon stream is opened by the first subscriber.
+    pub fn subscribe(&self) -> EventSubscription {
+        self.inner.started.call_once(|| {
+            tokio::spawn(pump(self.inner.clone()));
+        });
+
+        // Holding the lock keeps events from slipping between the snapshot and the subscription.
+        let snapshot = self.inner.lock_snapshot();
+        EventSubscription {
+            pending: snapshot.get_events(),
+            receiver: self.inner.sender.subscribe(),
+            inner: self.inner.clone(),
+        }
+    }
+}
+
+impl Inner {
+    fn lock_snapshot(&self) -> MutexGuard<'_, Snapshot> {
+        self.snapshot.lock().expect("event snapshot lock")
+    }
+}
+
+async fn pump(inner: Arc<Inner>) {
+    let mut events_rx = inner.daemon.events_receiver();
+
+    while let Some(event) = events_rx.recv().await {
+        let mut snapshot = inner.lock_snapshot();
+        snapshot.update(&event);
+        // Nobody subscribed is fine, the snapshot is kept for later.
+        let _ = inner.sender.send(event);
+    }
+
+    log::debug!("Daemon event stream ended");
+}
+
+#[derive(Debug)]
+pub struct EventSubscription {
+    /// Snapshot events not handed out yet.
+    pending: VecDeque<Event>,
+    receiver: broadcast::Receiver<Event>,
+    inner: Arc<Inner>,
+}
+
+impl EventSubscription {
+    /// The next event, `None` if no more can be received.
+    pub async fn recv(&mut self) -> Option<Event> {
+        loop {
+            if let Some(event) = self.pending.pop_front() {
+                return Some(event);
+            }
+
+            match self.receiver.recv().await {
+                Ok(event) => return Some(event),
+                Err(RecvError::Lagged(missed)) => {
+                    // The state is caught up from the snapshot, other missed events are lost.
+                    log::warn!("Event subscriber lagged behind by {missed} events, resyncing");
+                    let snapshot = self.inner.lock_snapshot();
+                    self.receiver = self.inner.sender.subscribe();
+                    self.pending = snapshot.get_events();
+                }
+                Err(RecvError::Closed) => return None,
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use mullvad_types::device::DeviceState;
+
+    use super::*;
+    use crate::mullvad::mock::{MockDaemon, MockState};
+
+    const ACCOUNT: &str = "1234123412341234";
+
+    fn get_hub() -> (MockDaemon, EventHub) {
+        let daemon = MockDaemon::new(MockState::default());
+        (daemon.clone(), EventHub::new(Arc::new(daemon)))
+    }
+
+    async fn recv(subscription: &mut EventSubscription) -> Event {
+        tokio::time::timeout(Duration::from_secs(1), subscription.recv())
+            .await
+            .expect("event not received in time")
+            .expect("event stream ended")
+    }
+
+    /// Skips events until one `matches` returns true for.
+    async fn wait_for(subscription: &mut EventSubscription, matches: impl Fn(&Event) -> bool) {
+        while !matches(&recv(subscription).await) {}
+    }
+
+    fn allows_lan(event: &Event) -> Option<bool> {
+        match event {
+            Event::Setting(settings) => Some(settings.allow_lan),
+            _ => None,
+        }
+    }
+
+    #[tokio::test]
+    async fn late_subscribers_get_the_current_state() {
+        let (daemon, hub) = get_hub();
+        let mut first = hub.subscribe();
+        wait_for(&mut first, |event| matches!(event, Event::Device(_))).await;
+
+        daemon.set_allow_lan(true).await.unwrap();
+        daemon.login_account(ACCOUNT.to_string()).await.unwrap();
+        wait_for(&mut first, |event| {
+            matches!(event, Event::Device(e) if matches!(e.cause, DeviceEventCause::LoggedIn))
+        })
+        .await;
+
+        let mut late = hub.subscribe();
+        assert!(matches!(recv(&mut late).await, Event::ConnectingToDaemon));
+        assert_eq!(allows_lan(&recv(&mut late).await), Some(true));
+        assert!(matches!(recv(&mut late).await, Event::TunnelState(_)));
+        let Event::Device(device_event) = recv(&mut late).await else {
+            panic!("no device event replayed");
+        };
+        // The replayed login didn't just happen.
+        assert!(matches!(device_event.cause, DeviceEventCause::Updated));
+        assert!(matches!(device_event.new_state, DeviceState::LoggedIn(_)));
+        assert!(matches!(recv(&mut late).await, Event::AppVersionInfo(_)));
+    }
+
+    #[tokio::test]
+    async fn lagging_subscribers_resync_from_the_snapshot() {
+        let (daemon, hub) = get_hub();
+        let mut lagging = hub.subscribe();
+
+        for i in 0..CAPACITY * 2 {
+            daemon.set_allow_lan(i % 2 == 0).await.unwrap();
+            // Lets the hub read each event before the mock's channel fills up.
+            tokio::time::sleep(Duration::from_millis(1)).await;
+        }
+        let last_allow_lan = (CAPACITY * 2 - 1) % 2 == 0;
+
+        assert!(matches!(
+            recv(&mut lagging).await,
+            Event::ConnectingToDaemon
+        ));
+        assert_eq!(allows_lan(&recv(&mut lagging).await), Some(last_allow_lan));
+        wait_for(&mut lagging, |event| matches!(event, Event::Device(_))).await;
+
+        // Then it's live again.
+        daemon.set_allow_lan(!last_allow_lan).await.unwrap();
+        assert_eq!(allows_lan(&recv(&mut lagging).await), Some(!last_allow_lan));
+    }
+
+    #[test]
+    fn reconnecting_forgets_the_old_state() {
+        let mut snapshot = Snapshot::default();
+        snapshot.update(&Event::ConnectingToDaemon);
+        snapshot.update(&Event::Setting(Settings::default()));
+        assert_eq!(snapshot.get_events().len(), 2);
+
+        snapshot.update(&Event::ConnectingToDaemon);
+        let events = snapshot.get_events();
+        assert_eq!(events.len(), 1);
+        assert!(matches!(events[0], Event::ConnectingToDaemon));
+    }
+}
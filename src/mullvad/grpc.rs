@@ -0,0 +1,738 @@
+use std::{
+    future::Future,
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+use anyhow::Result;
+use async_trait::async_trait;
+use futures::StreamExt;
+use mullvad_management_interface::{client::DaemonEvent, MullvadProxyClient};
+use mullvad_types::{
+    account::{AccountData, AccountNumber, VoucherSubmission},
+    device::{Device, DeviceEvent, DeviceEventCause, DeviceId, DeviceState},
+    relay_constraints::RelaySettings,
+    settings::Settings,
+    states::TunnelState,
+};
+use tokio::sync::{
+    mpsc::{self, Receiver, Sender},
+    watch, Mutex, Notify,
+};
+
+use super::{
+    diagnostics::{self, ConnectionFailure, ConnectionHealth},
+    Event, MullvadDaemon,
+};
+
+/// Whether a call may be sent again after the connection broke during it.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Retry {
+    Once,
+    Never,
+}
+
+/// The shared client, counted so a failure only drops the client it happened on.
+#[derive(Debug, Default)]
+struct ClientSlot {
+    /// The client together with the socket it was opened on.
+    client: Option<(Option<PathBuf>, MullvadProxyClient)>,
+    /// Incremented for every client opened.
+    generation: u64,
+}
+
+/// What the calls and the event stream share.
+#[derive(Debug)]
+struct Shared {
+    client: Mutex<ClientSlot>,
+    /// Cuts the wait before the next connection attempt short.
+    retry: Notify,
+    /// `None` is the default socket.
+    socket_path: watch::Sender<Option<PathBuf>>,
+    health: watch::Sender<ConnectionHealth>,
+}
+
+impl Shared {
+    /// The shared client and its generation, opened if there is none.
+    async fn get_client(&self) -> Result<(u64, MullvadProxyClient)> {
+        let mut slot = self.client.lock().await;
+        let socket_path = self.socket_path.borrow().clone();
+
+        if let Some((client_socket_path, client)) = slot.client.as_ref() {
+            if *client_socket_path == socket_path {
+                return Ok((slot.generation, client.clone()));
+            }
+        }
+
+        let new_client = connect(socket_path.as_deref()).await.inspect_err(|e| {
+            log::debug!("{e:#?}");
+            self.set_health(ConnectionHealth::Broken(ConnectionFailure::classify(
+                e, false,
+            )));
+        })?;
+        slot.generation += 1;
+        slot.client = Some((socket_path, new_client.clone()));
+
+        Ok((slot.generation, new_client))
+    }
+
+    /// Drops the client of `generation`, unless it was replaced in the meantime.
+    async fn drop_client(&self, generation: u64) {
+        let mut slot = self.client.lock().await;
+        if slot.generation == generation {
+            slot.client = None;
+        }
+    }
+
+    fn set_health(&self, health: ConnectionHealth) {
+        self.health.send_if_modified(|current| {
+            let modified = *current != health;
+            *current = health;
+            modified
+        });
+    }
+
+    fn set_healthy(&self) {
+        // The event stream is waiting to reconnect, the daemon is back so it can go now.
+        if matches!(*self.health.borrow(), ConnectionHealth::Broken(_)) {
+            self.retry.notify_one();
+        }
+        self.set_health(ConnectionHealth::Healthy);
+    }
+}
+
+/// The real daemon, reached over its management gRPC socket.
+///
+/// The calls and the event stream share one client, which is opened lazily
+/// and dropped after transport errors, so they also share one health.
+#[derive(Debug)]
+pub struct GrpcDaemon {
+    shared: Arc<Shared>,
+}
+
+impl GrpcDaemon {
+    pub fn new(socket_path: Option<PathBuf>) -> Self {
+        GrpcDaemon {
+            shared: Arc::new(Shared {
+                client: Mutex::default(),
+                retry: Notify::new(),
+                socket_path: watch::Sender::new(socket_path),
+                health: watch::Sender::new(ConnectionHealth::Healthy),
+            }),
+        }
+    }
+
+    async fn call_once<T, F, Fut>(&self, call: &F) -> Result<T>
+    where
+        F: Fn(MullvadProxyClient) -> Fut,
+        Fut: Future<Output = Result<T, mullvad_management_interface::Error>>,
+    {
+        let (generation, client) = self.shared.get_client().await?;
+        let result = call(client).await.map_err(anyhow::Error::from);
+
+        match &result {
+            Ok(_) => self.shared.set_healthy(),
+            Err(err) if diagnostics::is_transport_error(err) => {
+                log::debug!("Dropping the daemon client after a transport error: {err:#}");
+                self.shared.drop_client(generation).await;
+                self.shared.set_health(ConnectionHealth::Reconnecting);
+            }
+            // The daemon answered, so the connection is fine.
+            Err(_) => self.shared.set_healthy(),
+        }
+
+        result
+    }
+
+    /// Runs `call` on the shared client, once more on a new connection if `retry` allows it.
+    async fn call<T, F, Fut>(&self, retry: Retry, call: F) -> Result<T>
+    where
+        F: Fn(MullvadProxyClient) -> Fut,
+        Fut: Future<Output = Result<T, mullvad_management_interface::Error>>,
+    {
+        match self.call_once(&call).await {
+            Err(err) if retry == Retry::Once && diagnostics::is_transport_error(&err) => {
+                log::info!("Retrying a daemon call after a transport error: {err:#}");
+                self.call_once(&call).await
+            }
+            result => result,
+        }
+    }
+}
+
+async fn connect(socket_path: Option<&Path>) -> Result<MullvadProxyClient> {
+    Ok(match socket_path {
+        Some(socket_path) => MullvadProxyClient::from_rpc_socket_path(socket_path).await?,
+        None => MullvadProxyClient::new().await?,
+    })
+}
+
+#[async_trait]
+impl MullvadDaemon for GrpcDaemon {
+    fn events_receiver(&self) -> Receiver<Event> {
+        let (sender, receiver) = mpsc::channel(10);
+        let shared = self.shared.clone();
+        let mut socket_path = self.shared.socket_path.subscribe();
+
+        tokio::spawn(async move {
+            let mut failures = 0;
+
+            while !sender.is_closed() && (sender.send(Event::ConnectingToDaemon).await).is_ok() {
+                log::trace!("Starting listening for RPC.");
+                // The client is opened on the current socket, later changes restart the stream.
+                socket_path.borrow_and_update();
+                let mut was_connected = false;
+                let result = tokio::select! {
+                    result = events_listen(&sender, &shared, &mut was_connected) => result,
+                    Ok(()) = socket_path.changed() => {
+                        log::info!("Daemon socket changed, reconnecting");
+                        failures = 0;
+                        continue;
+                    }
+                };
+                let failure = match result {
+                    Result::Ok(_) => {
+                        log::info!("RPC listening ended Ok.");
+                        ConnectionFailure::DaemonCrashed
+                    }
+                    Err(err) => {
+                        log::warn!("RPC listening error: {err:#}");
+                        ConnectionFailure::classify(&err, was_connected)
+                    }
+                };
+
+                // A working connection starts the backoff over.
+                failures = if was_connected { 1 } else { failures + 1 };
+                let retry_in = diagnostics::get_retry_delay(failures);
+
+                shared.set_health(ConnectionHealth::Broken(failure.clone()));
+                let event = Event::DaemonConnectionFailed { failure, retry_in };
+                if sender.send(event).await.is_err() {
+                    break;
+                }
+
+                tokio::select! {
+                    _ = tokio::time::sleep(retry_in) => {}
+                    _ = shared.retry.notified() => log::debug!("Retrying to connect now"),
+                    Ok(()) = socket_path.changed() => failures = 0,
+                }
+            }
+        });
+
+        receiver
+    }
+
+    fn retry_now(&self) {
+        self.shared.retry.notify_one();
+    }
+
+    fn health_receiver(&self) -> watch::Receiver<ConnectionHealth> {
+        self.shared.health.subscribe()
+    }
+
+    fn set_socket_path(&self, socket_path: Option<PathBuf>) {
+        self.shared.socket_path.send_if_modified(|current| {
+            let modified = *current != socket_path;
+            *current = socket_path;
+            modified
+        });
+    }
+
+    async fn get_current_version(&self) -> Result<String> {
+        self.call(Retry::Once, move |mut client| async move {
+            client.get_current_version().await
+        })
+        .await
+    }
+
+    async fn login_account(&self, account: AccountNumber) -> Result<()> {
+        self.call(Retry::Never, move |mut client| {
+            let account = account.clone();
+            async move { client.login_account(account).await }
+        })
+        .await
+    }
+
+    async fn logout_account(&self) -> Result<()> {
+        self.call(Retry::Never, move |mut client| async move {
+            client.logout_account().await
+        })
+        .await
+    }
+
+    async fn create_new_account(&self) -> Result<AccountNumber> {
+        self.call(Retry::Never, move |mut client| async move {
+            client.create_new_account().await
+        })
+        .await
+    }
+
+    async fn get_account_history(&self) -> Result<Option<AccountNumber>> {
+        self.call(Retry::Once, move |mut client| async move {
+            client.get_account_history().await
+        })
+        .await
+    }
+
+    async fn clear_account_history(&self) -> Result<()> {
+        self.call(Retry::Once, move |mut client| async move {
+            client.clear_account_history().await
+        })
+        .await
+    }
+
+    async fn get_account_data(&self, account: AccountNumber) -> Result<AccountData> {
+        self.call(Retry::Once, move |mut client| {
+            let account = account.clone();
+            async move { client.get_account_data(account).await }
+        })
+        .await
+    }
+
+    async fn submit_voucher(&self, voucher: String) -> Result<VoucherSubmission> {
+        self.call(Retry::Never, move |mut client| {
+            let voucher = voucher.clone();
+            async move { client.submit_voucher(voucher).await }
+        })
+        .await
+    }
+
+    async fn get_www_auth_token(&self) -> Result<String> {
+        self.call(Retry::Once, move |mut client| async move {
+            client.get_www_auth_token().await
+        })
+        .await
+    }
+
+    async fn get_device(&self) -> Result<DeviceState> {
+        self.call(Retry::Once, move |mut client| async move {
+            client.get_device().await
+        })
+        .await
+    }
+
+    async fn list_devices(&self, account: AccountNumber) -> Result<Vec<Device>> {
+        self.call(Retry::Once, move |mut client| {
+            let account = account.clone();
+            async move { client.list_devices(account).await }
+        })
+        .await
+    }
+
+    async fn remove_device(&self, account: AccountNumber, device: DeviceId) -> Result<()> {
+        self.call(Retry::Never, move |mut client| {
+            let account = account.clone();
+            let device = device.clone();
+            async move { client.remove_device(account, device).await }
+        })
+        .await
+    }
+
+    async fn connect_tunnel(&self) -> Result<bool> {
+        self.call(Retry::Never, move |mut client| async move {
+            client.connect_tunnel().await
+        })
+        .await
+    }
+
+    async fn disconnect_tunnel(&self) -> Result<bool> {
+        self.call(Retry::Never, move |mut client| async move {
+            client.disconnect_tunnel().await
+        })
+        .await
+    }
+
+    async fn reconnect_tunnel(&self) -> Result<bool> {
+        self.call(Retry::Never, move |mut client| async move {
+            client.reconnect_tunnel().await
+        })
+        .await
+    }
+
+    async fn get_tunnel_state(&self) -> Result<TunnelState> {
+        self.call(Retry::Once, move |mut client| async move {
+            client.get_tunnel_state().await
+        })
+        .await
+    }
+
+    async fn get_settings(&self) -> Result<Settings> {
+        self.call(Retry::Once, move |mut client| async move {
+            client.get_settings().await
+        })
+        .await
+    }
+
+    async fn set_auto_connect(&self, state: bool) -> Result<()> {
+        self.call(Retry::Once, move |mut client| async move {
+            client.set_auto_connect(state).await
+        })
+        .await
+    }
+
+    async fn set_allow_lan(&self, state: bool) -> Result<()> {
+        self.call(Retry::Once, move |mut client| async move {
+            client.set_allow_lan(state).await
+        })
+        .await
+    }
+
+    async fn set_block_when_disconnected(&self, state: bool) -> Result<()> {
+        self.call(Retry::Once, move |mut client| async move {
+            client.set_block_when_disconnected(state).await
+        })
+        .await
+    }
+
+    async fn set_enable_ipv6(&self, state: bool) -> Result<()> {
+        self.call(Retry::Once, move |mut client| async move {
+            client.set_enable_ipv6(state).await
+        })
+        .await
+    }
+
+    async fn set_relay_settings(&self, update: RelaySettings) -> Result<()> {
+        self.call(Retry::Once, move |mut client| {
+            let update = update.clone();
+            async move { client.set_relay_settings(update).await }
+        })
+        .await
+    }
+}
+
+/// Forwards the events on the shared client, dropping it when the stream breaks or ends.
+/// Sets `was_connected` once the event stream is set up.
+async fn events_listen(
+    sender: &Sender<Event>,
+    shared: &Shared,
+    was_connected: &mut bool,
+) -> Result<()> {
+    let (generation, client) = shared.get_client().await?;
+    let result = forward_events(sender, client, shared, was_connected).await;
+
+    // Either way the daemon went away, so calls must not keep using the connection.
+    if result
+        .as_ref()
+        .map_or_else(diagnostics::is_transport_error, |_| true)
+    {
+        shared.drop_client(generation).await;
+    }
+    result
+}
+
+async fn forward_events(
+    sender: &Sender<Event>,
+    mut client: MullvadProxyClient,
+    shared: &Shared,
+    was_connected: &mut bool,
+) -> Result<()> {
+    let settings = client.get_settings().await?;
+    sender.send(Event::Setting(settings)).await?;
+
+    let state = client.get_tunnel_state().await?;
+    sender.send(Event::TunnelState(state)).await?;
+
+    if let Ok(device) = client.get_device().await {
+        sender
+            .send(Event::Device(DeviceEvent {
+                cause: DeviceEventCause::Updated,
+                new_state: device,
+            }))
+            .await?;
+    }
+
+    // Only pushed when it changes, which may not happen for days.
+    match client.get_version_info().await {
+        Ok(app_version_info) => sender.send(Event::AppVersionInfo(app_version_info)).await?,
+        Err(err) => log::debug!("Can't get the version info: {err:#}"),
+    }
+
+    let mut events = client.events_listen().await?;
+    *was_connected = true;
+    shared.set_health(ConnectionHealth::Healthy);
+
+    while let Some(event) = events.next().await {
+        match event? {
+            DaemonEvent::TunnelState(new_state) => {
+                log::trace!("{new_state:#?}");
+                sender.send(Event::TunnelState(new_state)).await?;
+            }
+            DaemonEvent::Settings(settings) => {
+                log::trace!("{settings:#?}");
+                sender.send(Event::Setting(settings)).await?;
+            }
+            DaemonEvent::RelayList(relay_list) => {
+                log::trace!("{relay_list:#?}");
+                sender.send(Event::RelayList(relay_list)).await?;
+            }
+            DaemonEvent::AppVersionInfo(app_version_info) => {
+                log::trace!("{app_version_info:#?}");
+                sender.send(Event::AppVersionInfo(app_version_info)).await?;
+            }
+            DaemonEvent::Device(device_event) => {
+                log::trace!("{device_event:#?}");
+                sender.send(Event::Device(device_event)).await?;
+            }
+            DaemonEvent::RemoveDevice(remove_device_event) => {
+                log::trace!("{remove_device_event:#?}");
+                sender
+                    .send(Event::RemoveDevice(remove_device_event))
+                    .await?;
+            }
+            DaemonEvent::NewAccessMethod(access_method) => {
+                log::trace!("{access_method:#?}");
+                sender.send(Event::NewAccessMethod(access_method)).await?;
+            }
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{collections::HashMap, time::Duration};
+
+    use tempfile::TempDir;
+    use tokio::net::UnixListener;
+    use tokio_stream::wrappers::UnixListenerStream;
+
+    use mullvad_types::version::AppVersionInfo;
+
+    use super::*;
+    use crate::mullvad::{
+        fake_daemon::{
+            daemon::FakeDaemon,
+            scenario::{LoginResult, Scenario},
+            service::ManagementServiceImpl,
+        },
+        mock, DaemonError,
+    };
+
+    const ACCOUNT: &str = "1234123412341234";
+    const FULL_ACCOUNT: &str = "9999999999999999";
+
+    /// Serves `scenario` on a new socket and returns a client for it.
+    /// The socket is removed with the returned directory.
+    fn serve(scenario: Scenario) -> (Arc<FakeDaemon>, GrpcDaemon, TempDir) {
+        let socket_dir = tempfile::tempdir().expect("test socket directory");
+        let socket_path = socket_dir.path().join("daemon.sock");
+        let listener = UnixListener::bind(&socket_path).expect("test socket");
+
+        let fake_daemon = Arc::new(FakeDaemon::new(scenario));
+        tokio::spawn(
+            tonic::transport::Server::builder()
+                .add_service(ManagementServiceImpl::new(fake_daemon.clone()))
+                .serve_with_incoming(UnixListenerStream::new(listener)),
+        );
+
+        (fake_daemon, GrpcDaemon::new(Some(socket_path)), socket_dir)
+    }
+
+    /// Skips events until one `matches` returns true for, failing after a second.
+    async fn wait_for(events: &mut Receiver<Event>, matches: impl Fn(&Event) -> bool) -> Event {
+        tokio::time::timeout(Duration::from_secs(1), async {
+            loop {
+                let event = events.recv().await.expect("event stream ended");
+                if matches(&event) {
+                    return event;
+                }
+            }
+        })
+        .await
+        .expect("event not received in time")
+    }
+
+    fn get_connecting_scenario(step_ms: u64) -> Scenario {
+        Scenario {
+            connect_sequence: Some(mock::get_connect_sequence()),
+            step_ms,
+            ..Default::default()
+        }
+    }
+
+    #[tokio::test]
+    async fn connect_and_disconnect_reach_the_events() {
+        let (_, daemon, _socket_dir) = serve(get_connecting_scenario(10));
+        let mut events = daemon.events_receiver();
+        wait_for(&mut events, |event| matches!(event, Event::Device(_))).await;
+
+        assert!(daemon.connect_tunnel().await.unwrap());
+        wait_for(&mut events, |event| {
+            matches!(event, Event::TunnelState(TunnelState::Connected { .. }))
+        })
+        .await;
+
+        assert!(daemon.disconnect_tunnel().await.unwrap());
+        wait_for(&mut events, |event| {
+            matches!(event, Event::TunnelState(TunnelState::Disconnected { .. }))
+        })
+        .await;
+        assert_eq!(
+            *daemon.health_receiver().borrow(),
+            ConnectionHealth::Healthy
+        );
+    }
+
+    #[tokio::test]
+    async fn scenarios_connect_by_default() {
+        let (_, daemon, _socket_dir) = serve(Scenario {
+            step_ms: 10,
+            ..Default::default()
+        });
+        let mut events = daemon.events_receiver();
+
+        assert!(daemon.connect_tunnel().await.unwrap());
+        wait_for(&mut events, |event| {
+            matches!(event, Event::TunnelState(TunnelState::Connected { .. }))
+        })
+        .await;
+    }
+
+    #[tokio::test]
+    async fn shipped_scenario_connects() {
+        let path = Path::new(env!("CARGO_MANIFEST_DIR")).join("scenarios/logged-in.json");
+        let scenario = Scenario::load(&path).unwrap();
+        assert_eq!(scenario.connect_sequence.as_ref().map(Vec::len), Some(2));
+
+        let (_, daemon, _socket_dir) = serve(Scenario {
+            step_ms: 10,
+            ..scenario
+        });
+        let mut events = daemon.events_receiver();
+
+        assert!(daemon.connect_tunnel().await.unwrap());
+        wait_for(&mut events, |event| {
+            matches!(event, Event::TunnelState(TunnelState::Connected { .. }))
+        })
+        .await;
+    }
+
+    #[tokio::test]
+    async fn disconnect_stops_the_connect_sequence() {
+        let (fake_daemon, daemon, _socket_dir) = serve(get_connecting_scenario(50));
+
+        assert!(daemon.connect_tunnel().await.unwrap());
+        assert!(daemon.reconnect_tunnel().await.unwrap());
+        assert!(daemon.disconnect_tunnel().await.unwrap());
+        tokio::time::sleep(Duration::from_millis(200)).await;
+
+        assert!(daemon.get_tunnel_state().await.unwrap().is_disconnected());
+        assert!(fake_daemon.lock_state().tunnel_state.is_disconnected());
+    }
+
+    #[tokio::test]
+    async fn login_results_follow_the_scenario() {
+        let (_, daemon, _socket_dir) = serve(Scenario {
+            login_results: HashMap::from([(FULL_ACCOUNT.to_string(), LoginResult::TooManyDevices)]),
+            ..Default::default()
+        });
+
+        let err = daemon
+            .login_account(FULL_ACCOUNT.to_string())
+            .await
+            .expect_err("the account is full");
+        assert_eq!(DaemonError::from_error(&err), DaemonError::TooManyDevices);
+
+        daemon.login_account(ACCOUNT.to_string()).await.unwrap();
+        let DeviceState::LoggedIn(account_and_device) = daemon.get_device().await.unwrap() else {
+            panic!("not logged in");
+        };
+        assert_eq!(account_and_device.account_number, ACCOUNT);
+
+        let devices = daemon.list_devices(ACCOUNT.to_string()).await.unwrap();
+        assert_eq!(devices.len(), 1);
+        assert_eq!(devices[0].id, account_and_device.device.id);
+    }
+
+    #[tokio::test]
+    async fn logged_in_scenario_lists_this_device() {
+        let (_, daemon, _socket_dir) = serve(Scenario {
+            account: Some(ACCOUNT.to_string()),
+            ..Default::default()
+        });
+
+        let DeviceState::LoggedIn(account_and_device) = daemon.get_device().await.unwrap() else {
+            panic!("not logged in");
+        };
+        let devices = daemon.list_devices(ACCOUNT.to_string()).await.unwrap();
+        assert_eq!(devices.len(), 1);
+        assert_eq!(devices[0].id, account_and_device.device.id);
+    }
+
+    #[tokio::test]
+    async fn stale_failures_keep_the_new_client() {
+        let (_, daemon, _socket_dir) = serve(Scenario::default());
+        let shared = &daemon.shared;
+
+        let (first, _) = shared.get_client().await.unwrap();
+        shared.drop_client(first).await;
+        let (second, _) = shared.get_client().await.unwrap();
+        assert_ne!(first, second);
+
+        // A call on the first client failing late doesn't drop the second one.
+        shared.drop_client(first).await;
+        assert_eq!(shared.get_client().await.unwrap().0, second);
+    }
+
+    #[tokio::test]
+    async fn health_follows_the_event_stream() {
+        let (_, daemon, socket_dir) = serve(Scenario::default());
+        let socket_path = daemon.shared.socket_path.borrow().clone();
+        let missing_path = socket_dir.path().join("missing.sock");
+        daemon.set_socket_path(Some(missing_path));
+
+        let mut events = daemon.events_receiver();
+        wait_for(&mut events, |event| {
+            matches!(event, Event::DaemonConnectionFailed { .. })
+        })
+        .await;
+        assert!(matches!(
+            *daemon.health_receiver().borrow(),
+            ConnectionHealth::Broken(_)
+        ));
+
+        daemon.set_socket_path(socket_path);
+        wait_for(&mut events, |event| matches!(event, Event::Device(_))).await;
+        assert_eq!(
+            *daemon.health_receiver().borrow(),
+            ConnectionHealth::Healthy
+        );
+    }
+
+    #[tokio::test]
+    async fn version_follows_the_scenario() {
+        let (_, daemon, _socket_dir) = serve(Scenario {
+            version: "2024.8".to_string(),
+            ..Default::default()
+        });
+
+        assert_eq!(daemon.get_current_version().await.unwrap(), "2024.8");
+    }
+
+    #[tokio::test]
+    async fn version_info_arrives_on_connect() {
+        let (_, daemon, _socket_dir) = serve(Scenario {
+            version_info: Some(AppVersionInfo {
+                supported: false,
+                latest_stable: "2025.1".to_string(),
+                latest_beta: "2025.2-beta1".to_string(),
+                suggested_upgrade: Some("2025.1".to_string()),
+            }),
+            ..Default::default()
+        });
+
+        let mut events = daemon.events_receiver();
+        let Event::AppVersionInfo(app_version_info) = wait_for(&mut events, |event| {
+            matches!(event, Event::AppVersionInfo(_))
+        })
+        .await
+        else {
+            unreachable!();
+        };
+        assert!(!app_version_info.supported);
+        assert_eq!(
+            app_version_info.suggested_upgrade.as_deref(),
+            Some("2025.1")
+        );
+    }
+}
@@ -0,0 +1,4 @@
+//! Limits of the Mullvad API, also used by the fake daemon.
+
+/// How many devices can be logged in on one account.
+pub const MAX_DEVICES: usize = 5;
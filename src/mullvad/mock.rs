@@ -0,0 +1,715 @@
+use std::{
+    collections::HashMap,
+    net::{Ipv4Addr, SocketAddr},
+    sync::{Arc, Mutex, MutexGuard},
+    time::Duration,
+};
+
+use anyhow::{anyhow, Result};
+use async_trait::async_trait;
+use chrono::{TimeDelta, Utc};
+use mullvad_management_interface::Error;
+use mullvad_types::{
+    account::{AccountData, AccountNumber, VoucherSubmission},
+    device::{
+        AccountAndDevice, Device, DeviceEvent, DeviceEventCause, DeviceId, DeviceState,
+        RemoveDeviceEvent,
+    },
+    features::FeatureIndicators,
+    relay_constraints::RelaySettings,
+    settings::Settings,
+    states::TunnelState,
+    version::AppVersionInfo,
+};
+use talpid_types::net::{
+    wireguard::PrivateKey, Endpoint, TransportProtocol, TunnelEndpoint, TunnelType,
+};
+use tokio::{
+    sync::mpsc::{self, Receiver, Sender},
+    task::JoinHandle,
+};
+use tonic::Status;
+
+use super::{Event, MullvadDaemon, MAX_DEVICES};
+
+/// Delay between the states of `MockState::connect_sequence`.
+const TUNNEL_STATE_STEP: Duration = Duration::from_millis(500);
+
+const MOCK_ACCOUNT_NUMBER: &str = "1234567890123456";
+
+/// Time added by any voucher.
+const VOUCHER_DAYS: i64 = 30;
+
+/// Why logging in fails, whatever the account.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum LoginFailure {
+    TooManyDevices,
+    /// The API refuses more attempts for now.
+    RateLimited,
+    /// The daemon can't reach the API.
+    Network,
+}
+
+/// What the mock daemon knows, changed by the calls and by the scripting methods.
+#[derive(Debug, Clone)]
+pub struct MockState {
+    pub settings: Settings,
+    pub tunnel_state: TunnelState,
+    pub device_state: DeviceState,
+    /// Data of each account, made up on the first fetch.
+    pub account_data: HashMap<AccountNumber, AccountData>,
+    pub account_history: Option<AccountNumber>,
+    /// Devices of the account, including this one when logged in.
+    pub devices: Vec<Device>,
+    /// Devices whose removal fails as if the API was unreachable.
+    pub unremovable_devices: Vec<DeviceId>,
+    /// Tunnel states emitted one after another on connect, the last one stays.
+    pub connect_sequence: Vec<TunnelState>,
+    /// Delay before each state of `connect_sequence`.
+    pub connect_step: Duration,
+    /// Accounts which can log in, any account can if it's empty.
+    pub valid_accounts: Vec<AccountNumber>,
+    pub login_failure: Option<LoginFailure>,
+    pub version_info: AppVersionInfo,
+}
+
+impl Default for MockState {
+    fn default() -> Self {
+        MockState {
+            settings: Settings::default(),
+            tunnel_state: TunnelState::Disconnected {
+                location: None,
+                locked_down: false,
+            },
+            device_state: DeviceState::LoggedOut,
+            account_data: HashMap::new(),
+            account_history: None,
+            devices: vec![],
+            unremovable_devices: vec![],
+            connect_sequence: get_connect_sequence(),
+            connect_step: TUNNEL_STATE_STEP,
+            valid_accounts: vec![],
+            login_failure: None,
+            version_info: AppVersionInfo {
+                supported: true,
+                latest_stable: super::INTERFACE_VERSION.to_string(),
+                latest_beta: super::INTERFACE_VERSION.to_string(),
+                suggested_upgrade: None,
+            },
+        }
+    }
+}
+
+#[derive(Debug)]
+struct MockInner {
+    state: Mutex<MockState>,
+    subscribers: Mutex<Vec<Sender<Event>>>,
+    /// The connect sequence being played, if any.
+    connect_task: Mutex<Option<JoinHandle<()>>>,
+}
+
+/// In-memory daemon which emits the same events the real one would.
+#[derive(Debug, Clone)]
+pub struct MockDaemon {
+    inner: Arc<MockInner>,
+}
+
+/// Connecting to and then connected to a made up WireGuard relay.
+pub(crate) fn get_connect_sequence() -> Vec<TunnelState> {
+    let endpoint = TunnelEndpoint {
+        endpoint: Endpoint {
+            address: SocketAddr::from((Ipv4Addr::new(10, 0, 0, 1), 51820)),
+            protocol: TransportProtocol::Udp,
+        },
+        tunnel_type: TunnelType::Wireguard,
+        quantum_resistant: false,
+        proxy: None,
+        obfuscation: None,
+        entry_endpoint: None,
+        tunnel_interface: Some("wg0-mullvad".to_string()),
+        daita: false,
+    };
+
+    vec![
+        TunnelState::Connecting {
+            endpoint: endpoint.clone(),
+            location: None,
+            feature_indicators: FeatureIndicators::default(),
+        },
+        TunnelState::Connected {
+            endpoint,
+            location: None,
+            feature_indicators: FeatureIndicators::default(),
+        },
+    ]
+}
+
+fn get_mock_device() -> Device {
+    Device {
+        id: "mock-device".to_string(),
+        name: "mock mole".to_string(),
+        pubkey: PrivateKey::new_from_random().public_key(),
+        hijack_dns: false,
+        created: Utc::now(),
+    }
+}
+
+impl MockDaemon {
+    pub fn new(state: MockState) -> Self {
+        MockDaemon {
+            inner: Arc::new(MockInner {
+                state: Mutex::new(state),
+                subscribers: Mutex::default(),
+                connect_task: Mutex::default(),
+            }),
+        }
+    }
+
+    fn lock_state(&self) -> MutexGuard<'_, MockState> {
+        self.inner.state.lock().expect("mock state lock")
+    }
+
+    /// Sends an event to every listener, `make_event` is called once for each of them.
+    fn emit(&self, make_event: impl Fn() -> Event) {
+        let mut subscribers = self.inner.subscribers.lock().expect("subscribers lock");
+        subscribers.retain(|sender| match sender.try_send(make_event()) {
+            Ok(()) => true,
+            Err(mpsc::error::TrySendError::Full(_)) => {
+                log::warn!("Mock daemon event dropped, the listener is too slow");
+                true
+            }
+            Err(mpsc::error::TrySendError::Closed(_)) => false,
+        });
+    }
+
+    fn set_tunnel_state(&self, tunnel_state: TunnelState) {
+        self.lock_state().tunnel_state = tunnel_state.clone();
+        self.emit(|| Event::TunnelState(tunnel_state.clone()));
+    }
+
+    fn set_device_state(&self, device_state: DeviceState, cause: DeviceEventCause) {
+        self.lock_state().device_state = device_state.clone();
+        self.emit(|| {
+            Event::Device(DeviceEvent {
+                cause,
+                new_state: device_state.clone(),
+            })
+        });
+    }
+
+    fn update_settings(&self, update: impl FnOnce(&mut Settings)) {
+        let settings = {
+            let mut state = self.lock_state();
+            update(&mut state.settings);
+            state.settings.clone()
+        };
+        self.emit(|| Event::Setting(settings.clone()));
+    }
+
+    /// Stops the connect sequence being played, so it can't undo a later state change.
+    fn cancel_connect_sequence(&self) {
+        if let Some(task) = self
+            .inner
+            .connect_task
+            .lock()
+            .expect("connect task lock")
+            .take()
+        {
+            task.abort();
+        }
+    }
+
+    fn run_connect_sequence(&self) -> bool {
+        self.cancel_connect_sequence();
+
+        let (sequence, step) = {
+            let state = self.lock_state();
+            (state.connect_sequence.clone(), state.connect_step)
+        };
+        if sequence.is_empty() {
+            log::debug!("Mock daemon has no connect sequence, staying in the current state");
+            return false;
+        }
+
+        let daemon = self.clone();
+        let task = tokio::spawn(async move {
+            for tunnel_state in sequence {
+                tokio::time::sleep(step).await;
+                daemon.set_tunnel_state(tunnel_state);
+            }
+        });
+        *self.inner.connect_task.lock().expect("connect task lock") = Some(task);
+        true
+    }
+
+    fn get_logged_in_account(&self) -> Result<AccountNumber> {
+        match &self.lock_state().device_state {
+            DeviceState::LoggedIn(account_and_device) => {
+                Ok(account_and_device.account_number.clone())
+            }
+            _ => Err(anyhow!("Not logged in")),
+        }
+    }
+}
+
+/// Scripting for tests, the app only drives the mock through `MullvadDaemon`.
+#[cfg(test)]
+impl MockDaemon {
+    pub fn get_state(&self) -> MockState {
+        self.lock_state().clone()
+    }
+
+    pub fn set_settings(&self, settings: Settings) {
+        self.lock_state().settings = settings.clone();
+        self.emit(|| Event::Setting(settings.clone()));
+    }
+
+    /// Account data isn't pushed by the daemon, it's returned by the next fetch.
+    pub fn set_account_data(&self, account: AccountNumber, account_data: AccountData) {
+        self.lock_state().account_data.insert(account, account_data);
+    }
+
+    pub fn set_login_failure(&self, login_failure: Option<LoginFailure>) {
+        self.lock_state().login_failure = login_failure;
+    }
+
+    pub fn set_version_info(&self, version_info: AppVersionInfo) {
+        self.lock_state().version_info = version_info.clone();
+        self.emit(|| Event::AppVersionInfo(version_info.clone()));
+    }
+}
+
+#[async_trait]
+impl MullvadDaemon for MockDaemon {
+    fn events_receiver(&self) -> Receiver<Event> {
+        let (sender, receiver) = mpsc::channel(10);
+
+        let state = self.lock_state().clone();
+        for event in [
+            Event::ConnectingToDaemon,
+            Event::Setting(state.settings),
+            Event::TunnelState(state.tunnel_state),
+            Event::Device(DeviceEvent {
+                cause: DeviceEventCause::Updated,
+                new_state: state.device_state,
+            }),
+            Event::AppVersionInfo(state.version_info),
+        ] {
+            let _ = sender.try_send(event);
+        }
+
+        self.inner
+            .subscribers
+            .lock()
+            .expect("subscribers lock")
+            .push(sender);
+
+        receiver
+    }
+
+    async fn get_current_version(&self) -> Result<String> {
+        Ok(super::INTERFACE_VERSION.to_string())
+    }
+
+    async fn login_account(&self, account: AccountNumber) -> Result<()> {
+        let device = {
+            let mut state = self.lock_state();
+            if !state.valid_accounts.is_empty() && !state.valid_accounts.contains(&account) {
+                return Err(Error::InvalidAccount.into());
+            }
+            match state.login_failure {
+                Some(LoginFailure::TooManyDevices) => return Err(Error::TooManyDevices.into()),
+                Some(LoginFailure::RateLimited) => {
+                    return Err(Status::resource_exhausted("mock API rate limit").into())
+                }
+                Some(LoginFailure::Network) => {
+                    return Err(Status::unavailable("mock API unreachable").into())
+                }
+                None => {}
+            }
+            if state.devices.len() >= MAX_DEVICES {
+                return Err(Error::TooManyDevices.into());
+            }
+            let device = get_mock_device();
+            state.devices.push(device.clone());
+            state.account_history = Some(account.clone());
+            device
+        };
+
+        let device_state = DeviceState::LoggedIn(AccountAndDevice {
+            account_number: account,
+            device,
+        });
+        self.set_device_state(device_state, DeviceEventCause::LoggedIn);
+        Ok(())
+    }
+
+    async fn logout_account(&self) -> Result<()> {
+        {
+            let mut state = self.lock_state();
+            if let DeviceState::LoggedIn(account_and_device) = &state.device_state {
+                let device_id = account_and_device.device.id.clone();
+                state.devices.retain(|device| device.id != device_id);
+            }
+        }
+        self.set_device_state(DeviceState::LoggedOut, DeviceEventCause::LoggedOut);
+        Ok(())
+    }
+
+    async fn create_new_account(&self) -> Result<AccountNumber> {
+        let account = MOCK_ACCOUNT_NUMBER.to_string();
+        self.lock_state().account_data.insert(
+            account.clone(),
+            AccountData {
+                id: "mock".to_string(),
+                expiry: Utc::now(),
+            },
+        );
+        self.login_account(account.clone()).await?;
+        Ok(account)
+    }
+
+    async fn get_account_history(&self) -> Result<Option<AccountNumber>> {
+        Ok(self.lock_state().account_history.clone())
+    }
+
+    async fn clear_account_history(&self) -> Result<()> {
+        self.lock_state().account_history = None;
+        Ok(())
+    }
+
+    async fn get_account_data(&self, account: AccountNumber) -> Result<AccountData> {
+        let mut state = self.lock_state();
+        if !state.valid_accounts.is_empty() && !state.valid_accounts.contains(&account) {
+            return Err(Error::InvalidAccount.into());
+        }
+        Ok(state
+            .account_data
+            .entry(account.clone())
+            .or_insert_with(|| AccountData {
+                id: format!("mock-{account}"),
+                expiry: Utc::now() + TimeDelta::days(VOUCHER_DAYS),
+            })
+            .clone())
+    }
+
+    async fn submit_voucher(&self, _voucher: String) -> Result<VoucherSubmission> {
+        let account = self.get_logged_in_account()?;
+        let mut account_data = self.get_account_data(account.clone()).await?;
+
+        let time_added = TimeDelta::days(VOUCHER_DAYS);
+        account_data.expiry = account_data.expiry.max(Utc::now()) + time_added;
+        self.lock_state()
+            .account_data
+            .insert(account, account_data.clone());
+
+        Ok(VoucherSubmission {
+            time_added: time_added.num_seconds() as u64,
+            new_expiry: account_data.expiry,
+        })
+    }
+
+    async fn get_www_auth_token(&self) -> Result<String> {
+        self.get_logged_in_account()?;
+        Ok("mock-token".to_string())
+    }
+
+    async fn get_device(&self) -> Result<DeviceState> {
+        Ok(self.lock_state().device_state.clone())
+    }
+
+    async fn list_devices(&self, _account: AccountNumber) -> Result<Vec<Device>> {
+        Ok(self.lock_state().devices.clone())
+    }
+
+    async fn remove_device(&self, account: AccountNumber, device: DeviceId) -> Result<()> {
+        let new_devices = {
+            let mut state = self.lock_state();
+            if state.unremovable_devices.contains(&device) {
+                return Err(Status::unavailable("mock API unreachable").into());
+            }
+            let count = state.devices.len();
+            state.devices.retain(|d| d.id != device);
+            if state.devices.len() == count {
+                return Err(Error::DeviceNotFound.into());
+            }
+            state.devices.clone()
+        };
+        self.emit(|| {
+            Event::RemoveDevice(RemoveDeviceEvent {
+                account_number: account.clone(),
+                new_devices: new_devices.clone(),
+            })
+        });
+        Ok(())
+    }
+
+    async fn connect_tunnel(&self) -> Result<bool> {
+        Ok(self.run_connect_sequence())
+    }
+
+    async fn disconnect_tunnel(&self) -> Result<bool> {
+        self.cancel_connect_sequence();
+        let locked_down = self.lock_state().settings.block_when_disconnected;
+        self.set_tunnel_state(TunnelState::Disconnected {
+            location: None,
+            locked_down,
+        });
+        Ok(true)
+    }
+
+    async fn reconnect_tunnel(&self) -> Result<bool> {
+        Ok(self.run_connect_sequence())
+    }
+
+    async fn get_tunnel_state(&self) -> Result<TunnelState> {
+        Ok(self.lock_state().tunnel_state.clone())
+    }
+
+    async fn get_settings(&self) -> Result<Settings> {
+        Ok(self.lock_state().settings.clone())
+    }
+
+    async fn set_auto_connect(&self, state: bool) -> Result<()> {
+        self.update_settings(|settings| settings.auto_connect = state);
+        Ok(())
+    }
+
+    async fn set_allow_lan(&self, state: bool) -> Result<()> {
+        self.update_settings(|settings| settings.allow_lan = state);
+        Ok(())
+    }
+
+    async fn set_block_when_disconnected(&self, state: bool) -> Result<()> {
+        self.update_settings(|settings| settings.block_when_disconnected = state);
+        Ok(())
+    }
+
+    async fn set_enable_ipv6(&self, state: bool) -> Result<()> {
+        self.update_settings(|settings| settings.tunnel_options.generic.enable_ipv6 = state);
+        Ok(())
+    }
+
+    async fn set_relay_settings(&self, update: RelaySettings) -> Result<()> {
+        self.update_settings(|settings| settings.relay_settings = update);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mullvad::{DaemonConnector, DaemonError, EventSubscription};
+
+    const ACCOUNT: &str = "1234123412341234";
+
+    fn get_connector(state: MockState) -> (MockDaemon, DaemonConnector) {
+        let daemon = MockDaemon::new(state);
+        let daemon_connector = DaemonConnector::new(Arc::new(daemon.clone()));
+        (daemon, daemon_connector)
+    }
+
+    /// Skips events until one `matches` returns true for, failing after a second.
+    async fn wait_for(events: &mut EventSubscription, matches: impl Fn(&Event) -> bool) -> Event {
+        tokio::time::timeout(Duration::from_secs(1), async {
+            loop {
+                let event = events.recv().await.expect("event stream ended");
+                if matches(&event) {
+                    return event;
+                }
+            }
+        })
+        .await
+        .expect("event not received in time")
+    }
+
+    fn get_error(result: Result<()>) -> DaemonError {
+        DaemonError::from_error(&result.expect_err("the call should fail"))
+    }
+
+    #[tokio::test]
+    async fn login_adds_the_device() {
+        let (daemon, daemon_connector) = get_connector(MockState::default());
+        let mut events = daemon_connector.subscribe();
+
+        daemon_connector
+            .login_account(ACCOUNT.to_string())
+            .await
+            .unwrap();
+
+        let Event::Device(device_event) = wait_for(
+            &mut events,
+            |event| matches!(event, Event::Device(e) if matches!(e.cause, DeviceEventCause::LoggedIn)),
+        )
+        .await
+        else {
+            unreachable!();
+        };
+        let DeviceState::LoggedIn(account_and_device) = device_event.new_state else {
+            panic!("not logged in: {device_event:?}");
+        };
+        assert_eq!(account_and_device.account_number, ACCOUNT);
+
+        let state = daemon.get_state();
+        let device_ids: Vec<_> = state.devices.iter().map(|device| &device.id).collect();
+        assert_eq!(device_ids, [&account_and_device.device.id]);
+        assert_eq!(state.account_history.as_deref(), Some(ACCOUNT));
+    }
+
+    #[tokio::test]
+    async fn login_fails_for_unknown_accounts() {
+        let (daemon, daemon_connector) = get_connector(MockState {
+            valid_accounts: vec![ACCOUNT.to_string()],
+            ..Default::default()
+        });
+
+        let result = daemon_connector
+            .login_account("9999999999999999".to_string())
+            .await;
+        assert_eq!(get_error(result), DaemonError::InvalidAccount);
+        assert!(matches!(
+            daemon.get_state().device_state,
+            DeviceState::LoggedOut
+        ));
+    }
+
+    #[tokio::test]
+    async fn login_fails_with_too_many_devices() {
+        let devices = (0..MAX_DEVICES).map(|_| get_mock_device()).collect();
+        let (daemon, daemon_connector) = get_connector(MockState {
+            devices,
+            ..Default::default()
+        });
+
+        let result = daemon_connector.login_account(ACCOUNT.to_string()).await;
+        assert_eq!(get_error(result), DaemonError::TooManyDevices);
+        assert_eq!(daemon.get_state().devices.len(), MAX_DEVICES);
+    }
+
+    #[tokio::test]
+    async fn login_fails_as_scripted() {
+        let (daemon, daemon_connector) = get_connector(MockState::default());
+
+        for (login_failure, error) in [
+            (LoginFailure::TooManyDevices, DaemonError::TooManyDevices),
+            (LoginFailure::RateLimited, DaemonError::RateLimited),
+            (LoginFailure::Network, DaemonError::Network),
+        ] {
+            daemon.set_login_failure(Some(login_failure));
+            let result = daemon_connector.login_account(ACCOUNT.to_string()).await;
+            assert_eq!(get_error(result), error, "{login_failure:?}");
+        }
+
+        daemon.set_login_failure(None);
+        daemon_connector
+            .login_account(ACCOUNT.to_string())
+            .await
+            .unwrap();
+        assert!(matches!(
+            daemon.get_state().device_state,
+            DeviceState::LoggedIn(_)
+        ));
+    }
+
+    #[tokio::test]
+    async fn account_data_belongs_to_the_account() {
+        let (daemon, daemon_connector) = get_connector(MockState {
+            valid_accounts: vec![ACCOUNT.to_string()],
+            ..Default::default()
+        });
+        let expiry = Utc::now() + TimeDelta::days(3);
+        daemon.set_account_data(
+            ACCOUNT.to_string(),
+            AccountData {
+                id: "scripted".to_string(),
+                expiry,
+            },
+        );
+
+        let account_data = daemon_connector
+            .get_account_data(ACCOUNT.to_string())
+            .await
+            .unwrap();
+        assert_eq!(account_data.data.expiry, expiry);
+
+        let result = daemon
+            .get_account_data("9999999999999999".to_string())
+            .await
+            .map(|_| ());
+        assert_eq!(get_error(result), DaemonError::InvalidAccount);
+    }
+
+    #[tokio::test]
+    async fn connect_plays_the_connect_sequence() {
+        let (daemon, daemon_connector) = get_connector(MockState {
+            connect_step: Duration::from_millis(10),
+            ..Default::default()
+        });
+        let mut events = daemon_connector.subscribe();
+
+        assert!(daemon_connector.secure_my_connection().await.unwrap());
+
+        wait_for(&mut events, |event| {
+            matches!(event, Event::TunnelState(TunnelState::Connecting { .. }))
+        })
+        .await;
+        wait_for(&mut events, |event| {
+            matches!(event, Event::TunnelState(TunnelState::Connected { .. }))
+        })
+        .await;
+        assert!(daemon.get_state().tunnel_state.is_connected());
+    }
+
+    #[tokio::test]
+    async fn disconnect_stops_the_connect_sequence() {
+        let (daemon, daemon_connector) = get_connector(MockState {
+            connect_step: Duration::from_millis(50),
+            ..Default::default()
+        });
+        let mut settings = daemon.get_state().settings;
+        settings.block_when_disconnected = true;
+        daemon.set_settings(settings);
+
+        assert!(daemon_connector.secure_my_connection().await.unwrap());
+        assert!(daemon_connector.disconnect().await.unwrap());
+        tokio::time::sleep(Duration::from_millis(200)).await;
+
+        assert!(matches!(
+            daemon.get_state().tunnel_state,
+            TunnelState::Disconnected {
+                locked_down: true,
+                ..
+            }
+        ));
+    }
+
+    #[tokio::test]
+    async fn version_info_is_sent_on_subscribe_and_change() {
+        let (daemon, daemon_connector) = get_connector(MockState::default());
+        let mut events = daemon_connector.subscribe();
+
+        let Event::AppVersionInfo(app_version_info) = wait_for(&mut events, |event| {
+            matches!(event, Event::AppVersionInfo(_))
+        })
+        .await
+        else {
+            unreachable!();
+        };
+        assert!(app_version_info.supported);
+        assert_eq!(app_version_info.suggested_upgrade, None);
+
+        daemon.set_version_info(AppVersionInfo {
+            supported: false,
+            suggested_upgrade: Some("2099.1".to_string()),
+            ..app_version_info
+        });
+        let Event::AppVersionInfo(app_version_info) = wait_for(&mut events, |event| {
+            matches!(event, Event::AppVersionInfo(_))
+        })
+        .await
+        else {
+            unreachable!();
+        };
+        assert!(!app_version_info.supported);
+    }
+}
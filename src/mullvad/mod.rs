@@ -0,0 +1,298 @@
+mod account_cache;
+mod diagnostics;
+mod error;
+mod event_hub;
+mod grpc;
+mod limits;
+mod mock;
+mod version;
+
+/// The fake daemon binary, so tests can serve it to `GrpcDaemon` in-process.
+#[cfg(test)]
+#[allow(dead_code)] // Parts only the binary uses, like loading scenario files.
+#[path = "../bin/mullvadwaita-fake-daemon"]
+mod fake_daemon {
+    pub mod daemon;
+    pub mod scenario;
+    pub mod service;
+
+    use super::limits;
+}
+
+use std::{fmt::Debug, path::PathBuf, sync::Arc, time::Duration};
+
+use anyhow::Result;
+use async_trait::async_trait;
+
+use account_cache::AccountCache;
+pub use account_cache::CachedAccountData;
+pub use diagnostics::{ConnectionFailure, ConnectionHealth};
+pub use error::{DaemonError, Severity};
+use event_hub::EventHub;
+pub use event_hub::EventSubscription;
+pub use grpc::GrpcDaemon;
+pub use limits::MAX_DEVICES;
+#[cfg(test)]
+pub(crate) use mock::get_connect_sequence;
+pub use mock::{MockDaemon, MockState};
+pub use version::{compare_with_daemon, InterfaceMismatch, INTERFACE_VERSION};
+
+use mullvad_types::{
+    access_method::AccessMethodSetting,
+    account::{AccountData, AccountNumber, VoucherSubmission},
+    device::{Device, DeviceEvent, DeviceId, DeviceState, RemoveDeviceEvent},
+    relay_constraints::RelaySettings,
+    relay_list::RelayList,
+    settings::Settings,
+    states::TunnelState,
+    version::AppVersionInfo,
+};
+use tokio::sync::{mpsc::Receiver, watch};
+
+/// Selects the daemon implementation, `mock` runs the app without Mullvad installed.
+const DAEMON_ENV_VAR: &str = "MULLVADWAITA_DAEMON";
+
+#[derive(Debug, Clone)]
+#[allow(clippy::large_enum_variant)]
+#[allow(dead_code)]
+pub enum Event {
+    TunnelState(TunnelState),
+    Setting(Settings),
+    RelayList(RelayList),
+    AppVersionInfo(AppVersionInfo),
+    Device(DeviceEvent),
+    RemoveDevice(RemoveDeviceEvent),
+    AccountData(CachedAccountData),
+    NewAccessMethod(AccessMethodSetting),
+    ConnectingToDaemon,
+    DaemonConnectionFailed {
+        failure: ConnectionFailure,
+        retry_in: Duration,
+    },
+}
+
+/// Everything the app needs from the Mullvad daemon.
+#[async_trait]
+pub trait MullvadDaemon: Debug + Send + Sync {
+    /// Sends `Event::ConnectingToDaemon` followed by the current settings, tunnel state,
+    /// device state and version info every time the connection is (re)established,
+    /// then the daemon events.
+    /// Only read by the event hub, everyone else subscribes through `DaemonConnector`.
+    fn events_receiver(&self) -> Receiver<Event>;
+
+    /// Skips the wait before the next connection attempt, if there is one.
+    fn retry_now(&self) {}
+
+    /// Switches to another management socket, `None` is the default one.
+    fn set_socket_path(&self, _socket_path: Option<PathBuf>) {}
+
+    /// Follows the health of the connection, always healthy unless overridden.
+    fn health_receiver(&self) -> watch::Receiver<ConnectionHealth> {
+        watch::channel(ConnectionHealth::Healthy).1
+    }
+
+    async fn get_current_version(&self) -> Result<String>;
+
+    async fn login_account(&self, account: AccountNumber) -> Result<()>;
+    async fn logout_account(&self) -> Result<()>;
+    async fn create_new_account(&self) -> Result<AccountNumber>;
+    async fn get_account_history(&self) -> Result<Option<AccountNumber>>;
+    async fn clear_account_history(&self) -> Result<()>;
+    async fn get_account_data(&self, account: AccountNumber) -> Result<AccountData>;
+    async fn submit_voucher(&self, voucher: String) -> Result<VoucherSubmission>;
+    async fn get_www_auth_token(&self) -> Result<String>;
+
+    async fn get_device(&self) -> Result<DeviceState>;
+    async fn list_devices(&self, account: AccountNumber) -> Result<Vec<Device>>;
+    async fn remove_device(&self, account: AccountNumber, device: DeviceId) -> Result<()>;
+
+    async fn connect_tunnel(&self) -> Result<bool>;
+    async fn disconnect_tunnel(&self) -> Result<bool>;
+    async fn reconnect_tunnel(&self) -> Result<bool>;
+    async fn get_tunnel_state(&self) -> Result<TunnelState>;
+
+    async fn get_settings(&self) -> Result<Settings>;
+    async fn set_auto_connect(&self, state: bool) -> Result<()>;
+    async fn set_allow_lan(&self, state: bool) -> Result<()>;
+    async fn set_block_when_disconnected(&self, state: bool) -> Result<()>;
+    async fn set_enable_ipv6(&self, state: bool) -> Result<()>;
+    async fn set_relay_settings(&self, update: RelaySettings) -> Result<()>;
+}
+
+fn get_daemon(socket_path: Option<PathBuf>) -> Arc<dyn MullvadDaemon> {
+    match std::env::var(DAEMON_ENV_VAR).as_deref() {
+        Ok("mock") => {
+            log::info!("Using the in-memory mock daemon");
+            Arc::new(MockDaemon::new(MockState::default()))
+        }
+        _ => Arc::new(GrpcDaemon::new(socket_path)),
+    }
+}
+
+/// Cheap to clone, all clones share the daemon connection, the events and the account cache.
+#[derive(Debug, Clone)]
+pub struct DaemonConnector {
+    daemon: Arc<dyn MullvadDaemon>,
+    event_hub: EventHub,
+    account_cache: AccountCache,
+}
+
+impl Default for DaemonConnector {
+    fn default() -> Self {
+        Self::new(get_daemon(None))
+    }
+}
+
+#[allow(dead_code)]
+impl DaemonConnector {
+    pub fn new(daemon: Arc<dyn MullvadDaemon>) -> Self {
+        DaemonConnector {
+            event_hub: EventHub::new(daemon.clone()),
+            daemon,
+            account_cache: AccountCache::default(),
+        }
+    }
+
+    /// Talks to the daemon listening on `socket_path`, or on the default socket.
+    pub fn with_socket_path(socket_path: Option<PathBuf>) -> Self {
+        Self::new(get_daemon(socket_path))
+    }
+
+    pub fn set_socket_path(&self, socket_path: Option<PathBuf>) {
+        // The account data may be from another daemon.
+        self.account_cache.invalidate();
+        self.daemon.set_socket_path(socket_path);
+    }
+
+    /// The current daemon state followed by live events, any number of subscribers can listen.
+    pub fn subscribe(&self) -> EventSubscription {
+        self.event_hub.subscribe()
+    }
+
+    pub fn retry_now(&self) {
+        self.daemon.retry_now();
+    }
+
+    pub fn health_receiver(&self) -> watch::Receiver<ConnectionHealth> {
+        self.daemon.health_receiver()
+    }
+
+    pub async fn get_current_version(&self) -> Result<String> {
+        self.daemon.get_current_version().await
+    }
+
+    pub async fn login_account(&self, account: AccountNumber) -> Result<()> {
+        self.account_cache.invalidate();
+        self.daemon.login_account(account).await
+    }
+
+    pub async fn logout_account(&self) -> Result<()> {
+        self.account_cache.invalidate();
+        self.daemon.logout_account().await
+    }
+
+    pub async fn secure_my_connection(&self) -> Result<bool> {
+        self.daemon.connect_tunnel().await
+    }
+
+    pub async fn disconnect(&self) -> Result<bool> {
+        self.daemon.disconnect_tunnel().await
+    }
+
+    pub async fn reconnect(&self) -> Result<bool> {
+        self.daemon.reconnect_tunnel().await
+    }
+
+    /// Account data from the cache, only asking the daemon when it's outdated.
+    pub async fn get_account_data(&self, account: String) -> Result<CachedAccountData> {
+        let daemon = self.daemon.clone();
+        self.account_cache
+            .get(account, |account| async move {
+                daemon.get_account_data(account).await
+            })
+            .await
+    }
+
+    pub fn invalidate_account_data(&self) {
+        self.account_cache.invalidate();
+    }
+
+    pub async fn submit_voucher(&self, voucher: String) -> Result<VoucherSubmission> {
+        let submission = self.daemon.submit_voucher(voucher).await?;
+        self.account_cache.invalidate();
+        Ok(submission)
+    }
+
+    pub async fn get_settings(&self) -> Result<Settings> {
+        self.daemon.get_settings().await
+    }
+
+    pub async fn set_auto_connect(&self, state: bool) -> Result<()> {
+        self.daemon.set_auto_connect(state).await
+    }
+
+    pub async fn set_allow_lan(&self, state: bool) -> Result<()> {
+        self.daemon.set_allow_lan(state).await
+    }
+
+    pub async fn set_block_when_disconnected(&self, state: bool) -> Result<()> {
+        self.daemon.set_block_when_disconnected(state).await
+    }
+
+    pub async fn set_enable_ipv6(&self, state: bool) -> Result<()> {
+        self.daemon.set_enable_ipv6(state).await
+    }
+
+    pub async fn get_tunnel_state(&self) -> Result<TunnelState> {
+        self.daemon.get_tunnel_state().await
+    }
+
+    pub async fn get_device(&self) -> Result<DeviceState> {
+        self.daemon.get_device().await
+    }
+
+    pub async fn get_account_history(&self) -> Result<Option<AccountNumber>> {
+        self.daemon.get_account_history().await
+    }
+
+    pub async fn clear_account_history(&self) -> Result<()> {
+        self.daemon.clear_account_history().await
+    }
+
+    pub async fn list_devices(&self, account: AccountNumber) -> Result<Vec<Device>> {
+        self.daemon.list_devices(account).await
+    }
+
+    pub async fn remove_device(&self, account: AccountNumber, device: DeviceId) -> Result<()> {
+        self.daemon.remove_device(account, device).await
+    }
+
+    /// Frees device slots on the account and then logs in to it.
+    pub async fn remove_devices_and_login(
+        &self,
+        account: AccountNumber,
+        devices: Vec<DeviceId>,
+    ) -> Result<()> {
+        // One removal failing doesn't mean the others weren't enough. If they
+        // weren't, the login fails with the devices left.
+        for device in devices {
+            if let Err(err) = self.remove_device(account.clone(), device.clone()).await {
+                log::warn!("Can't remove device {device}: {err:#}");
+            }
+        }
+        self.login_account(account).await
+    }
+
+    /// One-time token which logs the website in to the current account.
+    pub async fn get_www_auth_token(&self) -> Result<String> {
+        self.daemon.get_www_auth_token().await
+    }
+
+    pub async fn create_new_account(&self) -> Result<AccountNumber> {
+        self.daemon.create_new_account().await
+    }
+
+    pub async fn set_relay_settings(&self, update: RelaySettings) -> Result<()> {
+        self.daemon.set_relay_settings(update).await
+    }
+}
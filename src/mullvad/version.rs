@@ -0,0 +1,85 @@
+use std::cmp::Ordering;
+
+/// Version of the Mullvad sources the management interface was built from.
+pub const INTERFACE_VERSION: &str = mullvad_version::VERSION;
+
+/// How the daemon's management interface differs from the bundled one.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum InterfaceMismatch {
+    DaemonOlder,
+    DaemonNewer,
+}
+
+/// Year and number of a release like `2024.8`, betas and dev builds count as their release.
+fn parse_release(version: &str) -> Option<(u32, u32)> {
+    let (year, rest) = version.trim().split_once('.')?;
+    let number: String = rest.chars().take_while(char::is_ascii_digit).collect();
+    Some((year.parse().ok()?, number.parse().ok()?))
+}
+
+fn compare(daemon: (u32, u32), interface: (u32, u32)) -> Option<InterfaceMismatch> {
+    match daemon.cmp(&interface) {
+        Ordering::Less => Some(InterfaceMismatch::DaemonOlder),
+        Ordering::Greater => Some(InterfaceMismatch::DaemonNewer),
+        Ordering::Equal => None,
+    }
+}
+
+/// `None` if the daemon runs the release the interface was built from,
+/// or either version can't be parsed.
+pub fn compare_with_daemon(daemon_version: &str) -> Option<InterfaceMismatch> {
+    compare(
+        parse_release(daemon_version)?,
+        parse_release(INTERFACE_VERSION)?,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn releases_are_parsed() {
+        assert_eq!(parse_release("2024.8"), Some((2024, 8)));
+        assert_eq!(parse_release("2024.8-beta2"), Some((2024, 8)));
+        assert_eq!(parse_release("2025.1-dev-8b3c2a"), Some((2025, 1)));
+        assert_eq!(parse_release(" 2024.10\n"), Some((2024, 10)));
+        assert_eq!(parse_release("unknown"), None);
+        assert_eq!(parse_release("2024"), None);
+    }
+
+    #[test]
+    fn releases_are_compared_by_year_then_number() {
+        assert_eq!(compare((2024, 8), (2024, 8)), None);
+        assert_eq!(
+            compare((2024, 8), (2024, 10)),
+            Some(InterfaceMismatch::DaemonOlder)
+        );
+        assert_eq!(
+            compare((2025, 1), (2024, 10)),
+            Some(InterfaceMismatch::DaemonNewer)
+        );
+    }
+
+    #[test]
+    fn daemon_is_compared_with_the_bundled_interface() {
+        let (year, number) = parse_release(INTERFACE_VERSION).expect("bundled version");
+
+        assert_eq!(compare_with_daemon(INTERFACE_VERSION), None);
+        assert_eq!(compare_with_daemon(&format!("{year}.{number}-beta1")), None);
+        assert_eq!(
+            compare_with_daemon(&format!("{}.{number}", year - 1)),
+            Some(InterfaceMismatch::DaemonOlder)
+        );
+        assert_eq!(
+            compare_with_daemon(&format!("{year}.{}", number + 1)),
+            Some(InterfaceMismatch::DaemonNewer)
+        );
+    }
+
+    #[test]
+    fn unparsable_versions_are_no_mismatch() {
+        assert_eq!(compare_with_daemon("unknown"), None);
+        assert_eq!(compare_with_daemon(""), None);
+    }
+}
@@ -0,0 +1,184 @@
+use std::{fs, io, path::PathBuf};
+
+use anyhow::Result;
+use chrono::TimeDelta;
+use serde::{Deserialize, Serialize};
+
+const STATE_FILE_NAME: &str = "state.json";
+
+/// What the app remembers between runs which isn't a setting.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SavedState {
+    /// Expiry notification thresholds (in hours) which already fired.
+    pub notified_expiry_hours: Vec<u32>,
+}
+
+impl SavedState {
+    fn get_path() -> PathBuf {
+        gtk::glib::user_data_dir()
+            .join("mullvadwaita")
+            .join(STATE_FILE_NAME)
+    }
+
+    /// Loads the state, starting over if it is missing or broken.
+    pub fn load() -> SavedState {
+        let path = Self::get_path();
+        match fs::read_to_string(&path) {
+            Ok(json) => serde_json::from_str(&json)
+                .inspect_err(|err| log::warn!("Can't parse state {path:?}: {err}"))
+                .unwrap_or_default(),
+            Err(err) if err.kind() == io::ErrorKind::NotFound => SavedState::default(),
+            Err(err) => {
+                log::warn!("Can't read state {path:?}: {err}");
+                SavedState::default()
+            }
+        }
+    }
+
+    pub fn save(&self) -> Result<()> {
+        let path = Self::get_path();
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+
+        // Write to a temporary file first so a crash can't leave a truncated state.
+        let tmp_path = path.with_extension("json.tmp");
+        fs::write(&tmp_path, serde_json::to_string_pretty(self)?)?;
+        fs::rename(tmp_path, path)?;
+
+        Ok(())
+    }
+
+    /// Marks the `thresholds` (in hours) passed with `left` time left as
+    /// notified, returning the smallest one if any of them is new.
+    pub fn pass_expiry_thresholds(&mut self, thresholds: &[u32], left: TimeDelta) -> Option<u32> {
+        let is_passed = |hours: &u32| left <= TimeDelta::hours(*hours as i64);
+
+        // Time was added, so the passed thresholds can fire again later.
+        self.notified_expiry_hours.retain(is_passed);
+
+        if left <= TimeDelta::zero() {
+            return None;
+        }
+
+        let hours = thresholds
+            .iter()
+            .filter(|hours| is_passed(hours))
+            .filter(|hours| !self.notified_expiry_hours.contains(hours))
+            .min()
+            .copied()?;
+
+        // Thresholds passed at the same time only need one notification.
+        for passed in thresholds.iter().filter(|hours| is_passed(hours)) {
+            if !self.notified_expiry_hours.contains(passed) {
+                self.notified_expiry_hours.push(*passed);
+            }
+        }
+
+        Some(hours)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const THRESHOLDS: [u32; 3] = [72, 24, 1];
+
+    #[test]
+    fn nothing_before_the_first_threshold() {
+        let mut state = SavedState::default();
+
+        assert_eq!(
+            state.pass_expiry_thresholds(&THRESHOLDS, TimeDelta::hours(100)),
+            None
+        );
+        assert!(state.notified_expiry_hours.is_empty());
+    }
+
+    #[test]
+    fn each_threshold_fires_once() {
+        let mut state = SavedState::default();
+
+        assert_eq!(
+            state.pass_expiry_thresholds(&THRESHOLDS, TimeDelta::hours(70)),
+            Some(72)
+        );
+        assert_eq!(
+            state.pass_expiry_thresholds(&THRESHOLDS, TimeDelta::hours(69)),
+            None
+        );
+        assert_eq!(
+            state.pass_expiry_thresholds(&THRESHOLDS, TimeDelta::hours(23)),
+            Some(24)
+        );
+        assert_eq!(
+            state.pass_expiry_thresholds(&THRESHOLDS, TimeDelta::minutes(30)),
+            Some(1)
+        );
+        assert_eq!(
+            state.pass_expiry_thresholds(&THRESHOLDS, TimeDelta::minutes(10)),
+            None
+        );
+    }
+
+    #[test]
+    fn thresholds_passed_together_fire_once() {
+        let mut state = SavedState::default();
+
+        assert_eq!(
+            state.pass_expiry_thresholds(&THRESHOLDS, TimeDelta::hours(12)),
+            Some(24)
+        );
+        assert_eq!(state.notified_expiry_hours, [72, 24]);
+        assert_eq!(
+            state.pass_expiry_thresholds(&THRESHOLDS, TimeDelta::hours(11)),
+            None
+        );
+    }
+
+    #[test]
+    fn added_time_rearms_thresholds() {
+        let mut state = SavedState::default();
+        state.pass_expiry_thresholds(&THRESHOLDS, TimeDelta::hours(12));
+
+        assert_eq!(
+            state.pass_expiry_thresholds(&THRESHOLDS, TimeDelta::days(30)),
+            None
+        );
+        assert!(state.notified_expiry_hours.is_empty());
+        assert_eq!(
+            state.pass_expiry_thresholds(&THRESHOLDS, TimeDelta::hours(48)),
+            Some(72)
+        );
+    }
+
+    #[test]
+    fn expired_account_isnt_notified() {
+        let mut state = SavedState::default();
+
+        assert_eq!(
+            state.pass_expiry_thresholds(&THRESHOLDS, TimeDelta::zero()),
+            None
+        );
+        assert_eq!(
+            state.pass_expiry_thresholds(&THRESHOLDS, -TimeDelta::hours(1)),
+            None
+        );
+    }
+
+    #[test]
+    fn survives_a_round_trip() {
+        let state = SavedState {
+            notified_expiry_hours: vec![72, 24],
+        };
+        let json = serde_json::to_string(&state).unwrap();
+
+        assert_eq!(serde_json::from_str::<SavedState>(&json).unwrap(), state);
+        assert_eq!(
+            serde_json::from_str::<SavedState>("{}").unwrap(),
+            SavedState::default()
+        );
+    }
+}
@@ -0,0 +1,399 @@
+use std::time::Duration;
+
+use chrono::{prelude::*, Days, LocalResult, TimeDelta};
+use serde::{Deserialize, Serialize};
+use tokio::sync::{
+    mpsc::{self, Receiver},
+    watch,
+};
+
+use crate::mullvad::DaemonConnector;
+
+/// The longest time the scheduler sleeps without re-checking the clock,
+/// so suspend/resume and clock changes are noticed in time.
+const MAX_SLEEP: Duration = Duration::from_secs(60);
+
+/// Clocks never skip more than a day, and gaps end on a whole minute.
+const MAX_GAP_MINUTES: i64 = 24 * 60;
+
+pub const WEEKDAYS: [Weekday; 7] = [
+    Weekday::Mon,
+    Weekday::Tue,
+    Weekday::Wed,
+    Weekday::Thu,
+    Weekday::Fri,
+    Weekday::Sat,
+    Weekday::Sun,
+];
+
+/// A set of rules describing when the tunnel should be connected.
+/// Outside of every rule the tunnel should be disconnected.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Schedule {
+    pub enabled: bool,
+    pub rules: Vec<ScheduleRule>,
+}
+
+/// Connected from `start` to `end` (local time) on the given weekdays.
+/// If `end` is not after `start` the range continues into the next day.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ScheduleRule {
+    pub weekdays: Vec<Weekday>,
+    pub start: NaiveTime,
+    pub end: NaiveTime,
+}
+
+/// In local time, other time zones are for the tests.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ScheduledChange<Tz: TimeZone = Local> {
+    pub at: DateTime<Tz>,
+    pub connect: bool,
+}
+
+impl<Tz: TimeZone> Copy for ScheduledChange<Tz> where Tz::Offset: Copy {}
+
+impl Default for ScheduleRule {
+    fn default() -> Self {
+        ScheduleRule {
+            weekdays: WEEKDAYS[..5].to_vec(),
+            start: NaiveTime::from_hms_opt(8, 0, 0).unwrap_or_default(),
+            end: NaiveTime::from_hms_opt(18, 0, 0).unwrap_or_default(),
+        }
+    }
+}
+
+impl ScheduleRule {
+    fn is_active_at<Tz: TimeZone>(&self, time: &DateTime<Tz>) -> bool {
+        let weekday = time.weekday();
+        let time = time.time();
+
+        if self.start < self.end {
+            self.weekdays.contains(&weekday) && self.start <= time && time < self.end
+        } else {
+            (self.weekdays.contains(&weekday) && time >= self.start)
+                || (self.weekdays.contains(&weekday.pred()) && time < self.end)
+        }
+    }
+
+    /// Every moment in `days` days starting from `from`'s date at which this
+    /// rule starts or ends, in `from`'s time zone.
+    fn get_boundaries<Tz: TimeZone>(&self, from: &DateTime<Tz>, days: u64) -> Vec<DateTime<Tz>> {
+        let mut boundaries = vec![];
+        let timezone = from.timezone();
+        let first_date = from.date_naive() - Days::new(1);
+
+        for day in 0..=days {
+            let Some(date) = first_date.checked_add_days(Days::new(day)) else {
+                continue;
+            };
+            if !self.weekdays.contains(&date.weekday()) {
+                continue;
+            }
+            let end_date = if self.start < self.end {
+                Some(date)
+            } else {
+                date.succ_opt()
+            };
+
+            boundaries.extend(to_timezone(date.and_time(self.start), &timezone));
+            boundaries
+                .extend(end_date.and_then(|date| to_timezone(date.and_time(self.end), &timezone)));
+        }
+
+        boundaries
+    }
+
+    pub fn get_weekdays_label(&self) -> String {
+        WEEKDAYS
+            .iter()
+            .filter(|weekday| self.weekdays.contains(weekday))
+            .map(|weekday| weekday.to_string())
+            .collect::<Vec<_>>()
+            .join(", ")
+    }
+
+    pub fn get_time_range_label(&self) -> String {
+        format!(
+            "{}–{}",
+            self.start.format("%H:%M"),
+            self.end.format("%H:%M")
+        )
+    }
+}
+
+impl Schedule {
+    pub fn is_active(&self) -> bool {
+        self.enabled && !self.rules.is_empty()
+    }
+
+    /// Whether the tunnel should be connected at the given time.
+    pub fn should_be_connected_at<Tz: TimeZone>(&self, time: &DateTime<Tz>) -> bool {
+        self.rules.iter().any(|rule| rule.is_active_at(time))
+    }
+
+    /// The first moment after `now` at which the desired tunnel state changes.
+    pub fn get_next_change<Tz: TimeZone>(&self, now: &DateTime<Tz>) -> Option<ScheduledChange<Tz>> {
+        if !self.is_active() {
+            return None;
+        }
+
+        let connected_now = self.should_be_connected_at(now);
+
+        let mut boundaries: Vec<_> = self
+            .rules
+            .iter()
+            .flat_map(|rule| rule.get_boundaries(now, 8))
+            .filter(|boundary| boundary > now)
+            .collect();
+        boundaries.sort();
+
+        // The desired state is constant between boundaries, so the first one
+        // with a different state is the next change.
+        boundaries
+            .into_iter()
+            .find(|boundary| self.should_be_connected_at(boundary) != connected_now)
+            .map(|at| ScheduledChange {
+                at,
+                connect: !connected_now,
+            })
+    }
+}
+
+/// The moment a wall clock time happens in `timezone`,
+/// or the first moment after it if the clocks skip it.
+fn to_timezone<Tz: TimeZone>(time: NaiveDateTime, timezone: &Tz) -> Option<DateTime<Tz>> {
+    (0..=MAX_GAP_MINUTES).find_map(|minutes| {
+        // Not `earliest()`, `Local` doesn't return repeated times in order.
+        match timezone.from_local_datetime(&time.checked_add_signed(TimeDelta::minutes(minutes))?) {
+            LocalResult::Single(time) => Some(time),
+            LocalResult::Ambiguous(first, second) => Some(first.min(second)),
+            LocalResult::None => None,
+        }
+    })
+}
+
+/// Spawns a task which connects or disconnects the tunnel at the schedule
+/// boundaries and reports the next scheduled change whenever it's known.
+///
+/// Nothing is done between the boundaries, so manual connects and disconnects
+/// stay in effect until the next one.
+pub fn scheduler(
+    mut schedule_rx: watch::Receiver<Schedule>,
+    daemon_connector: DaemonConnector,
+) -> Receiver<Option<ScheduledChange>> {
+    let (sender, receiver) = mpsc::channel(1);
+
+    tokio::spawn(async move {
+        let mut schedule = schedule_rx.borrow_and_update().clone();
+        let mut next_change = schedule.get_next_change(&Local::now());
+
+        while sender.send(next_change).await.is_ok() {
+            loop {
+                let sleep_for = next_change
+                    .and_then(|change| (change.at - Local::now()).to_std().ok())
+                    .map_or(MAX_SLEEP, |until| until.min(MAX_SLEEP));
+
+                tokio::select! {
+                    changed = schedule_rx.changed() => {
+                        if changed.is_err() {
+                            return;
+                        }
+                        schedule = schedule_rx.borrow_and_update().clone();
+                        break;
+                    }
+                    _ = tokio::time::sleep(sleep_for) => {
+                        if let Some(change) = next_change.filter(|change| change.at <= Local::now()) {
+                            log::info!("Scheduled change: connect = {}", change.connect);
+                            let result = if change.connect {
+                                daemon_connector.secure_my_connection().await
+                            } else {
+                                daemon_connector.disconnect().await
+                            };
+                            if let Err(err) = result {
+                                log::warn!("Scheduled change failed: {err}");
+                            }
+                            break;
+                        }
+                    }
+                }
+            }
+
+            next_change = schedule.get_next_change(&Local::now());
+        }
+    });
+
+    receiver
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono_tz::{Europe::Stockholm, Tz};
+
+    use super::*;
+
+    /// In Central European time, the clocks skip 02:00–03:00 on the last
+    /// Sunday of March and repeat 02:00–03:00 on the last Sunday of October.
+    fn at(month: u32, day: u32, hour: u32, minute: u32) -> DateTime<Tz> {
+        NaiveDate::from_ymd_opt(2026, month, day)
+            .and_then(|date| date.and_hms_opt(hour, minute, 0))
+            .and_then(|time| to_timezone(time, &Stockholm))
+            .expect("valid local time")
+    }
+
+    fn rule(weekdays: &[Weekday], start: (u32, u32), end: (u32, u32)) -> ScheduleRule {
+        ScheduleRule {
+            weekdays: weekdays.to_vec(),
+            start: NaiveTime::from_hms_opt(start.0, start.1, 0).unwrap(),
+            end: NaiveTime::from_hms_opt(end.0, end.1, 0).unwrap(),
+        }
+    }
+
+    fn schedule(rules: Vec<ScheduleRule>) -> Schedule {
+        Schedule {
+            enabled: true,
+            rules,
+        }
+    }
+
+    #[test]
+    fn daytime_rule_is_active_between_start_and_end() {
+        let rule = rule(&[Weekday::Mon], (8, 0), (18, 0));
+
+        assert!(!rule.is_active_at(&at(10, 12, 7, 59)));
+        assert!(rule.is_active_at(&at(10, 12, 8, 0)));
+        assert!(rule.is_active_at(&at(10, 12, 17, 59)));
+        assert!(!rule.is_active_at(&at(10, 12, 18, 0)));
+        assert!(!rule.is_active_at(&at(10, 13, 12, 0)));
+    }
+
+    #[test]
+    fn overnight_rule_continues_into_the_next_day() {
+        let rule = rule(&[Weekday::Fri], (22, 0), (6, 0));
+
+        assert!(!rule.is_active_at(&at(10, 16, 21, 59)));
+        assert!(rule.is_active_at(&at(10, 16, 23, 0)));
+        assert!(rule.is_active_at(&at(10, 17, 5, 59)));
+        assert!(!rule.is_active_at(&at(10, 17, 6, 0)));
+        // Saturday isn't one of the rule's days.
+        assert!(!rule.is_active_at(&at(10, 17, 23, 0)));
+        // Neither is Thursday, so Friday morning is off.
+        assert!(!rule.is_active_at(&at(10, 16, 1, 0)));
+    }
+
+    #[test]
+    fn no_change_when_disabled_or_empty() {
+        let mut disabled = schedule(vec![ScheduleRule::default()]);
+        disabled.enabled = false;
+
+        assert_eq!(disabled.get_next_change(&at(10, 12, 12, 0)), None);
+        assert_eq!(schedule(vec![]).get_next_change(&at(10, 12, 12, 0)), None);
+    }
+
+    #[test]
+    fn next_change_within_a_day() {
+        let schedule = schedule(vec![rule(&[Weekday::Mon], (8, 0), (18, 0))]);
+
+        assert_eq!(
+            schedule.get_next_change(&at(10, 12, 7, 0)),
+            Some(ScheduledChange {
+                at: at(10, 12, 8, 0),
+                connect: true,
+            })
+        );
+        assert_eq!(
+            schedule.get_next_change(&at(10, 12, 8, 0)),
+            Some(ScheduledChange {
+                at: at(10, 12, 18, 0),
+                connect: false,
+            })
+        );
+    }
+
+    #[test]
+    fn next_change_of_an_overnight_rule_is_on_the_next_day() {
+        let schedule = schedule(vec![rule(&[Weekday::Sun], (22, 0), (2, 0))]);
+
+        assert_eq!(
+            schedule.get_next_change(&at(10, 18, 23, 0)),
+            Some(ScheduledChange {
+                at: at(10, 19, 2, 0),
+                connect: false,
+            })
+        );
+    }
+
+    #[test]
+    fn next_change_rolls_over_to_next_week() {
+        let schedule = schedule(vec![rule(&[Weekday::Mon], (8, 0), (18, 0))]);
+
+        assert_eq!(
+            schedule.get_next_change(&at(10, 12, 18, 0)),
+            Some(ScheduledChange {
+                at: at(10, 19, 8, 0),
+                connect: true,
+            })
+        );
+    }
+
+    #[test]
+    fn adjacent_rules_are_one_connected_range() {
+        let schedule = schedule(vec![
+            rule(&[Weekday::Mon], (8, 0), (12, 0)),
+            rule(&[Weekday::Mon], (12, 0), (18, 0)),
+        ]);
+
+        assert_eq!(
+            schedule.get_next_change(&at(10, 12, 9, 0)),
+            Some(ScheduledChange {
+                at: at(10, 12, 18, 0),
+                connect: false,
+            })
+        );
+    }
+
+    #[test]
+    fn start_skipped_by_the_clocks_moves_to_the_end_of_the_gap() {
+        let schedule = schedule(vec![rule(&[Weekday::Sun], (2, 30), (5, 0))]);
+
+        assert_eq!(
+            schedule.get_next_change(&at(3, 29, 1, 0)),
+            Some(ScheduledChange {
+                at: at(3, 29, 3, 0),
+                connect: true,
+            })
+        );
+        assert!(schedule.should_be_connected_at(&at(3, 29, 3, 0)));
+    }
+
+    #[test]
+    fn end_skipped_by_the_clocks_moves_to_the_end_of_the_gap() {
+        let schedule = schedule(vec![rule(&[Weekday::Sat], (22, 0), (2, 30))]);
+
+        assert_eq!(
+            schedule.get_next_change(&at(3, 28, 23, 0)),
+            Some(ScheduledChange {
+                at: at(3, 29, 3, 0),
+                connect: false,
+            })
+        );
+    }
+
+    #[test]
+    fn repeated_time_uses_its_first_occurrence() {
+        let schedule = schedule(vec![rule(&[Weekday::Sun], (2, 30), (4, 0))]);
+        let change = schedule.get_next_change(&at(10, 25, 0, 0));
+
+        assert_eq!(
+            change,
+            Some(ScheduledChange {
+                at: at(10, 25, 2, 30),
+                connect: true,
+            })
+        );
+        assert_eq!(
+            change.map(|change| change.at.offset().fix().local_minus_utc()),
+            Some(2 * 3600)
+        );
+    }
+}
@@ -0,0 +1,371 @@
+use anyhow::Result;
+use futures::StreamExt;
+use tokio::sync::mpsc::Sender;
+use zbus::{proxy::MethodFlags, zvariant::OwnedObjectPath, Connection};
+
+use crate::tr;
+
+pub const DAEMON_UNIT: &str = "mullvad-daemon.service";
+
+#[zbus::proxy(
+    interface = "org.freedesktop.systemd1.Manager",
+    default_service = "org.freedesktop.systemd1",
+    default_path = "/org/freedesktop/systemd1"
+)]
+trait Manager {
+    fn load_unit(&self, name: &str) -> zbus::Result<OwnedObjectPath>;
+
+    fn subscribe(&self) -> zbus::Result<()>;
+
+    /// Sent when unit files were enabled or disabled.
+    #[zbus(signal)]
+    fn unit_files_changed(&self) -> zbus::Result<()>;
+}
+
+#[zbus::proxy(
+    interface = "org.freedesktop.systemd1.Unit",
+    default_service = "org.freedesktop.systemd1"
+)]
+trait Unit {
+    #[zbus(property)]
+    fn load_state(&self) -> zbus::Result<String>;
+
+    #[zbus(property)]
+    fn active_state(&self) -> zbus::Result<String>;
+
+    #[zbus(property)]
+    fn sub_state(&self) -> zbus::Result<String>;
+
+    /// systemd doesn't send changes of this one, it's read again on `UnitFilesChanged`.
+    #[zbus(property(emits_changed_signal = "false"))]
+    fn unit_file_state(&self) -> zbus::Result<String>;
+}
+
+#[zbus::proxy(
+    interface = "org.freedesktop.systemd1.Service",
+    default_service = "org.freedesktop.systemd1"
+)]
+trait Service {
+    /// `success` or why the service failed last time, e.g. `exit-code`.
+    #[zbus(property)]
+    fn result(&self) -> zbus::Result<String>;
+}
+
+/// State of the daemon unit as systemd sees it.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ServiceStatus {
+    pub load_state: String,
+    pub active_state: String,
+    pub sub_state: String,
+    pub unit_file_state: String,
+    pub result: String,
+}
+
+impl ServiceStatus {
+    pub fn is_installed(&self) -> bool {
+        self.load_state != "not-found"
+    }
+
+    pub fn can_start(&self) -> bool {
+        self.is_installed() && matches!(self.active_state.as_str(), "inactive" | "failed")
+    }
+
+    pub fn can_enable(&self) -> bool {
+        self.is_installed() && self.unit_file_state == "disabled"
+    }
+
+    pub fn get_label(&self) -> String {
+        if !self.is_installed() {
+            return tr!("The {} unit is not installed.", DAEMON_UNIT);
+        }
+
+        let state = format!("{} ({})", self.active_state, self.sub_state);
+        if self.active_state == "failed" {
+            tr!(
+                "{} is {}, last result: {}.",
+                DAEMON_UNIT,
+                state,
+                self.result
+            )
+        } else {
+            tr!("{} is {}.", DAEMON_UNIT, state)
+        }
+    }
+}
+
+/// Talks to systemd on the system bus, or on `bus_address`
+/// (e.g. a private bus with a mock manager).
+#[derive(Debug, Clone)]
+pub struct SystemdClient {
+    connection: Connection,
+}
+
+impl SystemdClient {
+    pub async fn connect(bus_address: Option<&str>) -> Result<Self> {
+        let connection = match bus_address {
+            Some(address) => zbus::connection::Builder::address(address)?.build().await?,
+            None => Connection::system().await?,
+        };
+        Ok(SystemdClient { connection })
+    }
+
+    async fn get_unit(&self) -> Result<(UnitProxy<'static>, ServiceProxy<'static>)> {
+        let manager = ManagerProxy::new(&self.connection).await?;
+        let path = manager.load_unit(DAEMON_UNIT).await?;
+
+        let unit = UnitProxy::builder(&self.connection)
+            .path(path.clone())?
+            .build()
+            .await?;
+        let service = ServiceProxy::builder(&self.connection)
+            .path(path)?
+            .build()
+            .await?;
+
+        Ok((unit, service))
+    }
+
+    async fn get_status(unit: &UnitProxy<'_>, service: &ServiceProxy<'_>) -> Result<ServiceStatus> {
+        Ok(ServiceStatus {
+            load_state: unit.load_state().await?,
+            active_state: unit.active_state().await?,
+            sub_state: unit.sub_state().await?,
+            unit_file_state: unit.unit_file_state().await.unwrap_or_default(),
+            result: service.result().await.unwrap_or_default(),
+        })
+    }
+
+    /// Sends the unit status now and on every change, until the receiver is dropped.
+    pub async fn watch(&self, sender: Sender<ServiceStatus>) -> Result<()> {
+        let manager = ManagerProxy::new(&self.connection).await?;
+        // Without a subscriber systemd doesn't emit property changes.
+        manager.subscribe().await?;
+
+        let mut unit_files_changes = manager.receive_unit_files_changed().await?;
+
+        let (unit, service) = self.get_unit().await?;
+        let mut active_state_changes = unit.receive_active_state_changed().await;
+
+        loop {
+            let status = Self::get_status(&unit, &service).await?;
+            if sender.send(status).await.is_err() {
+                return Ok(());
+            }
+
+            tokio::select! {
+                Some(_) = active_state_changes.next() => {}
+                Some(_) = unit_files_changes.next() => {}
+                else => return Ok(()),
+            }
+        }
+    }
+
+    /// Starts the daemon now. systemd asks polkit, which may show an authentication dialog.
+    pub async fn start_service(&self) -> Result<()> {
+        let manager = ManagerProxy::new(&self.connection).await?;
+        let _job: Option<OwnedObjectPath> = manager
+            .inner()
+            .call_with_flags(
+                "StartUnit",
+                MethodFlags::AllowInteractiveAuth.into(),
+                &(DAEMON_UNIT, "replace"),
+            )
+            .await?;
+        Ok(())
+    }
+
+    /// Makes the daemon start at boot, authorized the same way as `start_service`.
+    pub async fn enable_service(&self) -> Result<()> {
+        let manager = ManagerProxy::new(&self.connection).await?;
+        let _changes: Option<(bool, Vec<(String, String, String)>)> = manager
+            .inner()
+            .call_with_flags(
+                "EnableUnitFiles",
+                MethodFlags::AllowInteractiveAuth.into(),
+                &(vec![DAEMON_UNIT], false, false),
+            )
+            .await?;
+
+        // The unit is enabled, reloading only makes systemd see it now instead of later.
+        let reload: zbus::Result<Option<()>> = manager
+            .inner()
+            .call_with_flags("Reload", MethodFlags::AllowInteractiveAuth.into(), &())
+            .await;
+        if let Err(err) = reload {
+            log::warn!("Can't reload systemd after enabling {DAEMON_UNIT}: {err:#}");
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use tokio::{net::UnixStream, sync::mpsc};
+    use zbus::{interface, object_server::SignalContext, zvariant::ObjectPath, Guid, ObjectServer};
+
+    use super::*;
+
+    const UNIT_PATH: &str = "/org/freedesktop/systemd1/unit/mullvad_2ddaemon_2eservice";
+
+    fn get_path(path: &'static str) -> OwnedObjectPath {
+        ObjectPath::from_static_str_unchecked(path).into()
+    }
+
+    /// The parts of the systemd manager `SystemdClient` uses, with a stopped, disabled daemon unit.
+    struct MockManager;
+
+    #[interface(name = "org.freedesktop.systemd1.Manager")]
+    impl MockManager {
+        fn load_unit(&self, name: &str) -> zbus::fdo::Result<OwnedObjectPath> {
+            if name != DAEMON_UNIT {
+                return Err(zbus::fdo::Error::FileNotFound(name.to_string()));
+            }
+            Ok(get_path(UNIT_PATH))
+        }
+
+        fn subscribe(&self) {}
+
+        async fn reload(
+            &self,
+            #[zbus(signal_context)] context: SignalContext<'_>,
+        ) -> zbus::fdo::Result<()> {
+            Self::unit_files_changed(&context).await?;
+            Ok(())
+        }
+
+        async fn start_unit(
+            &self,
+            _name: &str,
+            _mode: &str,
+            #[zbus(object_server)] server: &ObjectServer,
+        ) -> zbus::fdo::Result<OwnedObjectPath> {
+            let unit = server.interface::<_, MockUnit>(UNIT_PATH).await?;
+            unit.get_mut().await.active_state = "active".to_string();
+            unit.get()
+                .await
+                .active_state_changed(unit.signal_context())
+                .await?;
+            Ok(get_path("/org/freedesktop/systemd1/job/1"))
+        }
+
+        /// Like systemd, only announced by `UnitFilesChanged` after a reload.
+        async fn enable_unit_files(
+            &self,
+            _files: Vec<String>,
+            _runtime: bool,
+            _force: bool,
+            #[zbus(object_server)] server: &ObjectServer,
+        ) -> zbus::fdo::Result<(bool, Vec<(String, String, String)>)> {
+            let unit = server.interface::<_, MockUnit>(UNIT_PATH).await?;
+            unit.get_mut().await.unit_file_state = "enabled".to_string();
+            Ok((false, vec![]))
+        }
+
+        #[zbus(signal)]
+        async fn unit_files_changed(context: &SignalContext<'_>) -> zbus::Result<()>;
+    }
+
+    struct MockUnit {
+        active_state: String,
+        unit_file_state: String,
+    }
+
+    #[interface(name = "org.freedesktop.systemd1.Unit")]
+    impl MockUnit {
+        #[zbus(property)]
+        fn load_state(&self) -> String {
+            "loaded".to_string()
+        }
+
+        #[zbus(property)]
+        fn active_state(&self) -> String {
+            self.active_state.clone()
+        }
+
+        #[zbus(property)]
+        fn sub_state(&self) -> String {
+            match self.active_state.as_str() {
+                "active" => "running",
+                _ => "dead",
+            }
+            .to_string()
+        }
+
+        #[zbus(property(emits_changed_signal = "false"))]
+        fn unit_file_state(&self) -> String {
+            self.unit_file_state.clone()
+        }
+    }
+
+    struct MockService;
+
+    #[interface(name = "org.freedesktop.systemd1.Service")]
+    impl MockService {
+        #[zbus(property)]
+        fn result(&self) -> String {
+            "success".to_string()
+        }
+    }
+
+    /// A client connected straight to the mock manager, which serves while its connection is kept.
+    async fn get_client() -> (Connection, SystemdClient) {
+        let (server_stream, client_stream) = UnixStream::pair().unwrap();
+        let server = zbus::connection::Builder::unix_stream(server_stream)
+            .server(Guid::generate())
+            .unwrap()
+            .p2p()
+            .serve_at("/org/freedesktop/systemd1", MockManager)
+            .unwrap()
+            .serve_at(
+                UNIT_PATH,
+                MockUnit {
+                    active_state: "inactive".to_string(),
+                    unit_file_state: "disabled".to_string(),
+                },
+            )
+            .unwrap()
+            .serve_at(UNIT_PATH, MockService)
+            .unwrap()
+            .build();
+        let client = zbus::connection::Builder::unix_stream(client_stream)
+            .p2p()
+            .build();
+
+        let (server, connection) = tokio::try_join!(server, client).unwrap();
+        (server, SystemdClient { connection })
+    }
+
+    async fn next_status(statuses: &mut mpsc::Receiver<ServiceStatus>) -> ServiceStatus {
+        tokio::time::timeout(Duration::from_secs(1), statuses.recv())
+            .await
+            .expect("status not received in time")
+            .expect("watch ended")
+    }
+
+    #[tokio::test]
+    async fn watch_follows_enabling_and_starting() {
+        let (_server, client) = get_client().await;
+        let (sender, mut statuses) = mpsc::channel(4);
+        tokio::spawn({
+            let client = client.clone();
+            async move { client.watch(sender).await }
+        });
+
+        let status = next_status(&mut statuses).await;
+        assert!(status.can_start());
+        assert!(status.can_enable());
+
+        client.enable_service().await.unwrap();
+        let status = next_status(&mut statuses).await;
+        assert_eq!(status.unit_file_state, "enabled");
+        assert!(!status.can_enable());
+
+        client.start_service().await.unwrap();
+        let status = next_status(&mut statuses).await;
+        assert_eq!(status.active_state, "active");
+        assert_eq!(status.sub_state, "running");
+        assert!(!status.can_start());
+    }
+}
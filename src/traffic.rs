@@ -0,0 +1,356 @@
+use std::{
+    collections::VecDeque,
+    fs, io,
+    path::{Path, PathBuf},
+    time::Instant,
+};
+
+use talpid_types::net::TunnelType;
+
+/// How many rate samples are kept for the graph.
+pub const HISTORY_LENGTH: usize = 60;
+
+const WIREGUARD_INTERFACE: &str = "wg0-mullvad";
+
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct TrafficCounters {
+    pub rx_bytes: u64,
+    pub tx_bytes: u64,
+}
+
+/// Reads the tunnel interface byte counters from
+/// `<sysfs_root>/class/net/<iface>/statistics`.
+#[derive(Debug, Clone)]
+pub struct TrafficSampler {
+    sysfs_root: PathBuf,
+}
+
+impl TrafficSampler {
+    pub fn new(sysfs_root: impl Into<PathBuf>) -> Self {
+        Self {
+            sysfs_root: sysfs_root.into(),
+        }
+    }
+
+    fn get_net_dir(&self) -> PathBuf {
+        self.sysfs_root.join("class").join("net")
+    }
+
+    /// Finds the interface used by the tunnel of the given type.
+    ///
+    /// OpenVPN gets the first free `tun<n>`, so a tun device is only picked
+    /// if it's the only one up. Otherwise it may be some other VPN's.
+    pub fn find_tunnel_interface(&self, tunnel_type: TunnelType) -> Option<String> {
+        let net_dir = self.get_net_dir();
+
+        match tunnel_type {
+            TunnelType::Wireguard => net_dir
+                .join(WIREGUARD_INTERFACE)
+                .exists()
+                .then(|| WIREGUARD_INTERFACE.to_string()),
+            TunnelType::OpenVpn => {
+                let mut interfaces: Vec<String> = fs::read_dir(&net_dir)
+                    .ok()?
+                    .filter_map(|entry| entry.ok()?.file_name().into_string().ok())
+                    .filter(|name| name.starts_with("tun") && self.is_tun_device_up(name))
+                    .collect();
+                match interfaces.len() {
+                    1 => interfaces.pop(),
+                    0 => None,
+                    _ => {
+                        log::debug!("Can't tell the OpenVPN interface from {interfaces:?}");
+                        None
+                    }
+                }
+            }
+        }
+    }
+
+    fn is_tun_device_up(&self, interface: &str) -> bool {
+        let interface_dir = self.get_net_dir().join(interface);
+        // Only tun/tap devices have `tun_flags`, they report an unknown state when up.
+        interface_dir.join("tun_flags").exists()
+            && fs::read_to_string(interface_dir.join("operstate"))
+                .is_ok_and(|state| state.trim() != "down")
+    }
+
+    pub fn read_counters(&self, interface: &str) -> io::Result<TrafficCounters> {
+        let statistics_dir = self.get_net_dir().join(interface).join("statistics");
+
+        Ok(TrafficCounters {
+            rx_bytes: read_counter(&statistics_dir.join("rx_bytes"))?,
+            tx_bytes: read_counter(&statistics_dir.join("tx_bytes"))?,
+        })
+    }
+}
+
+fn read_counter(path: &Path) -> io::Result<u64> {
+    fs::read_to_string(path)?
+        .trim()
+        .parse()
+        .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct TrafficRate {
+    pub rx_bytes_per_sec: f64,
+    pub tx_bytes_per_sec: f64,
+}
+
+/// Traffic of the current tunnel session.
+#[derive(Debug, Default)]
+pub struct TrafficStats {
+    interface: Option<String>,
+    totals: Option<TrafficCounters>,
+    last_sample: Option<(Instant, TrafficCounters)>,
+    history: VecDeque<TrafficRate>,
+}
+
+impl TrafficStats {
+    pub fn get_interface(&self) -> Option<&str> {
+        self.interface.as_deref()
+    }
+
+    pub fn start(&mut self, interface: String) {
+        *self = TrafficStats {
+            interface: Some(interface),
+            ..Default::default()
+        };
+    }
+
+    pub fn reset(&mut self) {
+        *self = TrafficStats::default();
+    }
+
+    pub fn add_sample(&mut self, at: Instant, counters: TrafficCounters) {
+        let Some((last_at, last)) = self.last_sample.replace((at, counters)) else {
+            // The session starts at the first sample.
+            self.totals = Some(TrafficCounters::default());
+            return;
+        };
+        let totals = self.totals.get_or_insert_with(TrafficCounters::default);
+
+        // Counters start over from zero if the interface is recreated. The
+        // traffic since then still belongs to the session, the rate is unknown.
+        if counters.rx_bytes < last.rx_bytes || counters.tx_bytes < last.tx_bytes {
+            totals.rx_bytes += counters.rx_bytes;
+            totals.tx_bytes += counters.tx_bytes;
+            return;
+        }
+
+        let rx_bytes = counters.rx_bytes - last.rx_bytes;
+        let tx_bytes = counters.tx_bytes - last.tx_bytes;
+        totals.rx_bytes += rx_bytes;
+        totals.tx_bytes += tx_bytes;
+
+        let secs = at.duration_since(last_at).as_secs_f64();
+        if secs > 0.0 {
+            if self.history.len() == HISTORY_LENGTH {
+                self.history.pop_front();
+            }
+            self.history.push_back(TrafficRate {
+                rx_bytes_per_sec: rx_bytes as f64 / secs,
+                tx_bytes_per_sec: tx_bytes as f64 / secs,
+            });
+        }
+    }
+
+    pub fn get_rate(&self) -> Option<TrafficRate> {
+        self.history.back().copied()
+    }
+
+    pub fn get_totals(&self) -> Option<TrafficCounters> {
+        self.totals
+    }
+
+    pub fn get_history(&self) -> &VecDeque<TrafficRate> {
+        &self.history
+    }
+}
+
+pub fn format_bytes(bytes: f64) -> String {
+    const UNITS: [&str; 5] = ["B", "KB", "MB", "GB", "TB"];
+
+    let mut value = bytes;
+    let mut unit = 0;
+    while value >= 1000.0 && unit < UNITS.len() - 1 {
+        value /= 1000.0;
+        unit += 1;
+    }
+
+    if unit == 0 {
+        format!("{value:.0} {}", UNITS[unit])
+    } else {
+        format!("{value:.1} {}", UNITS[unit])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+
+    /// A throwaway `/sys` with only the files the sampler reads.
+    struct FakeSysfs {
+        root: PathBuf,
+    }
+
+    impl FakeSysfs {
+        fn new(name: &str) -> Self {
+            let root = std::env::temp_dir()
+                .join(format!("mullvadwaita-sysfs-{}-{name}", std::process::id()));
+            let _ = fs::remove_dir_all(&root);
+            fs::create_dir_all(root.join("class").join("net")).unwrap();
+            FakeSysfs { root }
+        }
+
+        fn add_interface(&self, name: &str, is_tun: bool, operstate: &str) {
+            let dir = self.root.join("class").join("net").join(name);
+            fs::create_dir_all(dir.join("statistics")).unwrap();
+            fs::write(dir.join("operstate"), format!("{operstate}\n")).unwrap();
+            if is_tun {
+                fs::write(dir.join("tun_flags"), "0x1001\n").unwrap();
+            }
+        }
+
+        fn set_counters(&self, name: &str, rx_bytes: u64, tx_bytes: u64) {
+            let dir = self
+                .root
+                .join("class")
+                .join("net")
+                .join(name)
+                .join("statistics");
+            fs::write(dir.join("rx_bytes"), format!("{rx_bytes}\n")).unwrap();
+            fs::write(dir.join("tx_bytes"), format!("{tx_bytes}\n")).unwrap();
+        }
+
+        fn get_sampler(&self) -> TrafficSampler {
+            TrafficSampler::new(&self.root)
+        }
+    }
+
+    impl Drop for FakeSysfs {
+        fn drop(&mut self) {
+            let _ = fs::remove_dir_all(&self.root);
+        }
+    }
+
+    fn counters(rx_bytes: u64, tx_bytes: u64) -> TrafficCounters {
+        TrafficCounters { rx_bytes, tx_bytes }
+    }
+
+    #[test]
+    fn finds_the_wireguard_interface() {
+        let sysfs = FakeSysfs::new("wireguard");
+        let sampler = sysfs.get_sampler();
+
+        assert_eq!(sampler.find_tunnel_interface(TunnelType::Wireguard), None);
+
+        sysfs.add_interface(WIREGUARD_INTERFACE, false, "unknown");
+        assert_eq!(
+            sampler.find_tunnel_interface(TunnelType::Wireguard),
+            Some(WIREGUARD_INTERFACE.to_string())
+        );
+    }
+
+    #[test]
+    fn finds_the_only_tun_device_that_is_up() {
+        let sysfs = FakeSysfs::new("openvpn");
+        let sampler = sysfs.get_sampler();
+        sysfs.add_interface("eth0", false, "up");
+        sysfs.add_interface("tunnel0", false, "up");
+        sysfs.add_interface("tun0", true, "down");
+
+        assert_eq!(sampler.find_tunnel_interface(TunnelType::OpenVpn), None);
+
+        sysfs.add_interface("tun1", true, "unknown");
+        assert_eq!(
+            sampler.find_tunnel_interface(TunnelType::OpenVpn),
+            Some("tun1".to_string())
+        );
+    }
+
+    #[test]
+    fn ambiguous_tun_devices_are_not_guessed() {
+        let sysfs = FakeSysfs::new("ambiguous");
+        sysfs.add_interface("tun0", true, "unknown");
+        sysfs.add_interface("tun1", true, "unknown");
+
+        assert_eq!(
+            sysfs
+                .get_sampler()
+                .find_tunnel_interface(TunnelType::OpenVpn),
+            None
+        );
+    }
+
+    #[test]
+    fn reads_counters() {
+        let sysfs = FakeSysfs::new("counters");
+        let sampler = sysfs.get_sampler();
+        sysfs.add_interface(WIREGUARD_INTERFACE, false, "unknown");
+
+        assert!(sampler.read_counters(WIREGUARD_INTERFACE).is_err());
+
+        sysfs.set_counters(WIREGUARD_INTERFACE, 1500, 700);
+        assert_eq!(
+            sampler.read_counters(WIREGUARD_INTERFACE).unwrap(),
+            counters(1500, 700)
+        );
+    }
+
+    #[test]
+    fn rates_and_totals_of_a_session() {
+        let mut stats = TrafficStats::default();
+        let start = Instant::now();
+
+        stats.add_sample(start, counters(1000, 500));
+        assert_eq!(stats.get_rate(), None);
+        assert_eq!(stats.get_totals(), Some(counters(0, 0)));
+
+        stats.add_sample(start + Duration::from_secs(2), counters(3000, 1500));
+        assert_eq!(
+            stats.get_rate(),
+            Some(TrafficRate {
+                rx_bytes_per_sec: 1000.0,
+                tx_bytes_per_sec: 500.0,
+            })
+        );
+        assert_eq!(stats.get_totals(), Some(counters(2000, 1000)));
+    }
+
+    #[test]
+    fn counter_reset_keeps_the_session_total() {
+        let mut stats = TrafficStats::default();
+        let start = Instant::now();
+
+        stats.add_sample(start, counters(1000, 1000));
+        stats.add_sample(start + Duration::from_secs(1), counters(5000, 3000));
+        // The interface was recreated and counts from zero again.
+        stats.add_sample(start + Duration::from_secs(2), counters(200, 100));
+        assert_eq!(stats.get_totals(), Some(counters(4200, 2100)));
+        assert_eq!(stats.get_history().len(), 1);
+
+        stats.add_sample(start + Duration::from_secs(3), counters(1200, 600));
+        assert_eq!(stats.get_totals(), Some(counters(5200, 2600)));
+        assert_eq!(
+            stats.get_rate(),
+            Some(TrafficRate {
+                rx_bytes_per_sec: 1000.0,
+                tx_bytes_per_sec: 500.0,
+            })
+        );
+    }
+
+    #[test]
+    fn history_is_capped() {
+        let mut stats = TrafficStats::default();
+        let start = Instant::now();
+
+        for i in 0..=HISTORY_LENGTH as u64 + 10 {
+            stats.add_sample(start + Duration::from_secs(i), counters(i * 10, i));
+        }
+        assert_eq!(stats.get_history().len(), HISTORY_LENGTH);
+    }
+}
@@ -1,9 +1,9 @@
-use crate::icon_names;
+use crate::{icon_names, mullvad::INTERFACE_VERSION, tr};
 
 use adw::prelude::*;
 use relm4::prelude::*;
 
-pub fn show_about_dialog(root: &impl IsA<gtk::Window>) {
+pub fn show_about_dialog(root: &impl IsA<gtk::Window>, daemon_version: Option<&str>) {
     let dialog = adw::AboutWindow::builder()
         .icon_name(icon_names::BACKGROUND_APP_GHOST)
         .application_icon(icon_names::BACKGROUND_APP_GHOST)
@@ -14,11 +14,26 @@ pub fn show_about_dialog(root: &impl IsA<gtk::Window>) {
         .license_type(gtk::License::Gpl30)
         .website("https://github.com/lessneek/mullvadwaita")
         .issue_url("https://github.com/lessneek/mullvadwaita/issues")
-        .version(env!("CARGO_PKG_VERSION"))
+        .version(match daemon_version {
+            Some(daemon_version) => tr!(
+                "{} (Mullvad daemon {})",
+                env!("CARGO_PKG_VERSION"),
+                daemon_version
+            ),
+            None => env!("CARGO_PKG_VERSION").to_string(),
+        })
         .modal(true)
         .transient_for(root)
         .developers(vec!["Lessneek", "aiska"])
         .comments("Mullvad VPN daemon controller.")
+        .debug_info(format!(
+            "{}\n{}",
+            tr!(
+                "Mullvad daemon: {}",
+                daemon_version.unwrap_or(&tr!("not connected"))
+            ),
+            tr!("Management interface: {}", INTERFACE_VERSION)
+        ))
         .build();
     dialog.present();
 }
@@ -1,7 +1,7 @@
 use crate::icon_names;
 use adw::prelude::*;
 use chrono::Local;
-use mullvad_types::{account::AccountData, device::AccountAndDevice};
+use mullvad_types::device::{AccountAndDevice, Device, DeviceId};
 use relm4::{
     component::{AsyncComponentParts, SimpleAsyncComponent},
     *,
@@ -9,6 +9,7 @@ use relm4::{
 
 use smart_default::SmartDefault;
 
+use crate::mullvad::CachedAccountData;
 use crate::tr;
 
 use super::app::AppInput;
@@ -19,9 +20,18 @@ use super::widgets::InfoButton;
 pub struct AccountModel {
     window: adw::PreferencesWindow,
 
+    #[do_not_track]
+    devices_list: gtk::ListBox,
+
     device_name: String,
     account_number: String,
     paid_until: Option<String>,
+
+    #[do_not_track]
+    current_device_id: Option<DeviceId>,
+
+    #[no_eq]
+    devices: Vec<Device>,
 }
 
 #[derive(Debug)]
@@ -29,7 +39,73 @@ pub enum AccountMsg {
     Show,
     Close,
     UpdateAccountAndDevice(AccountAndDevice),
-    UpdateAccountData(AccountData),
+    UpdateAccountData(CachedAccountData),
+    UpdateDevices(Vec<Device>),
+    RemoveDevice(Device),
+}
+
+impl AccountModel {
+    fn render_devices(&self, sender: &AsyncComponentSender<Self>) {
+        self.devices_list.remove_all();
+
+        for device in &self.devices {
+            let is_current = self.current_device_id.as_ref() == Some(&device.id);
+            let created = device.created.with_timezone(Local::now().offset());
+            let subtitle = if is_current {
+                tr!("Created {} · This device", created.format("%Y-%m-%d"))
+            } else {
+                tr!("Created {}", created.format("%Y-%m-%d"))
+            };
+
+            relm4::view! {
+                #[name = "action_row"]
+                adw::ActionRow {
+                    set_title: &device.pretty_name(),
+                    set_subtitle: &subtitle,
+                    add_css_class: "property",
+
+                    #[name = "remove_button"]
+                    add_suffix = &gtk::Button {
+                        set_icon_name: icon_names::CROSS_LARGE_CIRCLE_FILLED,
+                        set_valign: gtk::Align::Center,
+                        set_css_classes: &["flat"],
+                        set_tooltip_text: Some(&tr!("Remove device")),
+                        set_visible: !is_current,
+                    }
+                }
+            }
+
+            {
+                let sender = sender.clone();
+                let device = device.clone();
+                remove_button.connect_clicked(move |_| {
+                    sender.input(AccountMsg::RemoveDevice(device.clone()));
+                });
+            }
+
+            self.devices_list.append(&action_row);
+        }
+
+        self.devices_list.set_visible(!self.devices.is_empty());
+    }
+
+    async fn confirm_device_removal(&self, device: &Device) -> bool {
+        let dialog = adw::AlertDialog::new(
+            Some(&tr!("Remove “{}”?", device.pretty_name())),
+            Some(&tr!(
+                "The device will be logged out and its WireGuard key will be removed from the account."
+            )),
+        );
+        dialog.add_responses(&[
+            ("cancel", tr!("Cancel").as_str()),
+            ("remove", tr!("Remove").as_str()),
+        ]);
+        dialog.set_response_appearance("remove", adw::ResponseAppearance::Destructive);
+        dialog.set_default_response(Some("cancel"));
+        dialog.set_close_response("cancel");
+
+        dialog.choose_future(&self.window).await == "remove"
+    }
 }
 
 #[relm4::component(async, pub)]
@@ -105,6 +181,41 @@ impl SimpleAsyncComponent for AccountModel {
 
                 },
 
+                add = &adw::PreferencesGroup {
+                    add = &gtk::Box {
+                        set_orientation: gtk::Orientation::Vertical,
+                        set_spacing: 12,
+
+                        gtk::Button {
+                            set_label: &tr!("Buy more credit / Manage account"),
+                            set_css_classes: &["suggested-action"],
+
+                            connect_clicked[sender] => move |_| {
+                                let _ = sender.output(AppInput::ManageAccount);
+                            },
+                        },
+
+                        gtk::Button {
+                            set_label: &tr!("Redeem voucher"),
+
+                            connect_clicked[sender] => move |_| {
+                                let _ = sender.output(AppInput::ShowVoucherDialog);
+                            },
+                        },
+                    },
+                },
+
+                add = &adw::PreferencesGroup {
+                    set_title: &tr!("Devices"),
+                    set_description: Some(&tr!("Devices logged in on this account.")),
+
+                    #[local_ref]
+                    add = devices_list -> gtk::ListBox {
+                        add_css_class: "boxed-list",
+                        set_selection_mode: gtk::SelectionMode::None,
+                    },
+                },
+
                 add = &adw::PreferencesGroup {
                     add = &gtk::Button {
                         connect_clicked[sender] => move |_| {
@@ -130,26 +241,53 @@ impl SimpleAsyncComponent for AccountModel {
             ..Default::default()
         };
 
+        let devices_list = &model.devices_list;
+
         let widgets = view_output!();
 
         AsyncComponentParts { model, widgets }
     }
 
-    async fn update(&mut self, message: Self::Input, _sender: AsyncComponentSender<Self>) {
+    async fn update(&mut self, message: Self::Input, sender: AsyncComponentSender<Self>) {
         self.reset();
 
         match message {
-            AccountMsg::Show => self.window.present(),
+            AccountMsg::Show => {
+                let _ = sender.output(AppInput::FetchDevices);
+                self.window.present();
+            }
             AccountMsg::Close => self.window.set_visible(false),
             AccountMsg::UpdateAccountAndDevice(account_and_device) => {
                 self.set_device_name(account_and_device.device.pretty_name());
                 self.set_account_number(account_and_device.account_number);
+                self.current_device_id = Some(account_and_device.device.id);
+                self.render_devices(&sender);
             }
             AccountMsg::UpdateAccountData(account_data) => {
-                let paid_until = account_data.expiry.with_timezone(Local::now().offset());
-                let paid_until = paid_until.naive_local().to_string();
+                let paid_until = account_data
+                    .data
+                    .expiry
+                    .with_timezone(Local::now().offset());
+                let mut paid_until = paid_until.naive_local().to_string();
+                if account_data.is_stale {
+                    let updated = account_data.updated.with_timezone(Local::now().offset());
+                    paid_until = tr!(
+                        "{} (last updated {})",
+                        paid_until,
+                        updated.format("%Y-%m-%d %H:%M")
+                    );
+                }
                 self.set_paid_until(Some(paid_until));
             }
+            AccountMsg::UpdateDevices(devices) => {
+                self.set_devices(devices);
+                self.render_devices(&sender);
+            }
+            AccountMsg::RemoveDevice(device) => {
+                if self.confirm_device_removal(&device).await {
+                    let _ = sender.output(AppInput::RemoveDevice(device.id));
+                }
+            }
         }
     }
 }
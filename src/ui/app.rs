@@ -1,18 +1,42 @@
+use std::cell::RefCell;
+use std::collections::HashSet;
 use std::convert::identity;
+use std::path::PathBuf;
+use std::rc::Rc;
+use std::time::{Duration, Instant};
 
 use super::about;
 use super::account::{AccountModel, AccountMsg};
+use super::extensions::EntryExt as _;
+use super::history::{HistoryModel, HistoryMsg};
+use super::login_view::ACCOUNT_NUMBER_LENGTH;
 use super::main_window::MainWindow;
-use super::preferences::{Pref, PreferencesModel, PreferencesMsg};
-
-use crate::extensions::{ToStr, TunnelStateExt};
-use crate::mullvad::{self, DaemonConnector, Event};
+use super::preferences::{Pref, PreferencesInit, PreferencesModel, PreferencesMsg};
+use super::qr_code;
+use super::schedule::{ScheduleModel, ScheduleMsg};
+use super::sparkline;
+use super::too_many_devices::{TooManyDevicesDialog, TooManyDevicesMsg};
+use super::voucher_dialog::{VoucherDialog, VoucherDialogMsg};
+
+use crate::config::Config;
+use crate::connection_check::{self, ConnectionCheckReport};
+use crate::extensions::{CodeFormatExt, DurationExt, ToStr, TunnelStateExt};
+use crate::history::{self, HistoryLog};
+use crate::hooks;
+use crate::mullvad::{
+    self, ConnectionFailure, ConnectionHealth, DaemonConnector, DaemonError, Event,
+    InterfaceMismatch, Severity,
+};
+use crate::saved_state::SavedState;
+use crate::schedule::{self, Schedule, ScheduledChange};
+use crate::systemd::{ServiceStatus, SystemdClient};
+use crate::traffic::{self, TrafficSampler, TrafficStats};
 
 use crate::tr;
 
 use chrono::prelude::*;
+use chrono::TimeDelta;
 use futures::FutureExt;
-use mullvad_management_interface::Error;
 use smart_default::SmartDefault;
 
 use relm4::actions::{AccelsPlus, RelmAction, RelmActionGroup};
@@ -20,10 +44,12 @@ use relm4::prelude::*;
 
 use adw::prelude::*;
 
-use mullvad_types::account::{AccountData, AccountNumber};
-use mullvad_types::device::{AccountAndDevice, DeviceState};
+use mullvad_types::account::{AccountData, AccountNumber, VoucherSubmission};
+use mullvad_types::device::{AccountAndDevice, Device, DeviceEventCause, DeviceId, DeviceState};
 use mullvad_types::states::TunnelState;
+use mullvad_types::version::AppVersionInfo;
 use talpid_types::tunnel::ActionAfterDisconnect;
+use tokio::sync::watch;
 
 #[derive(Debug)]
 pub enum AppInput {
@@ -33,19 +59,56 @@ pub enum AppInput {
     Reconnect,
     Account,
     Preferences,
+    Schedule,
+    History,
     About,
     Set(Pref),
+    SetSchedule(Schedule),
     Login(AccountNumber),
+    RemoveDevicesAndLogin(AccountNumber, Vec<DeviceId>),
     Logout,
+    LogoutAndUnblock,
+    LoginAgain,
     CreateAccount,
+    CopyAccountNumber,
+    SaveAccountNumber,
+    ConfirmAccountSaved,
     ClearAccountHistory,
+    VerifyConnection,
+    FetchDevices,
+    RemoveDevice(DeviceId),
+    ShowVoucherDialog,
+    RedeemVoucher(String),
+    ManageAccount,
+    RetryDaemonConnection,
+    StartDaemonService,
+    EnableDaemonService,
+    SetDaemonSocketPath(Option<PathBuf>),
+    DismissVersionNotice,
 }
 
 #[derive(Debug)]
 pub enum AppMsg {
     DaemonEvent(Event),
+    DaemonVersion(String),
     LoginError(String),
+    TooManyDevices(AccountNumber, Vec<Device>),
+    AccountCreated(AccountNumber),
     CreateAccountError(String),
+    ScheduledChange(Option<ScheduledChange>),
+    ConnectionChecked(Result<ConnectionCheckReport, String>),
+    Devices(Vec<Device>),
+    DaemonServiceStatus(ServiceStatus),
+    ConnectionHealth(ConnectionHealth),
+    /// A background call failed, reported like the ones made in `update`.
+    Failed {
+        action: String,
+        error: anyhow::Error,
+    },
+    /// Only reported when it starts failing, the views show outdated data meanwhile.
+    RefreshFailed(Refresh, anyhow::Error),
+    VoucherRedeemed(Result<VoucherSubmission, String>),
+    Tick,
     Ignore,
 }
 
@@ -58,13 +121,37 @@ pub struct AppModel {
     #[no_eq]
     tunnel_state: Option<TunnelState>,
     account_data: Option<AccountData>,
+    /// When the shown account data was fetched, if refreshing it is failing.
+    account_data_stale_since: Option<DateTime<Utc>>,
     account_history: Option<AccountNumber>,
+    /// Shown until the user confirms having saved it.
+    new_account: Option<AccountNumber>,
 
     lockdown_mode: bool,
 
+    daemon_connection_failure: Option<ConnectionFailure>,
+    daemon_retry_label: Option<String>,
+    daemon_service_status: Option<ServiceStatus>,
+    daemon_health: ConnectionHealth,
+    daemon_version: Option<String>,
+    version_notice: Option<VersionNotice>,
+    #[do_not_track]
+    app_version_info: Option<AppVersionInfo>,
+    /// Notices closed by the user, they don't come back until the app restarts.
+    #[do_not_track]
+    dismissed_version_notices: Vec<VersionNotice>,
+    /// The socket in use, `None` is the default one.
+    daemon_socket_path: Option<PathBuf>,
+    #[do_not_track]
+    daemon_socket_override: Option<PathBuf>,
+
+    #[do_not_track]
+    daemon_retry_at: Option<Instant>,
+
     banner_label: Option<String>,
     device_name: Option<String>,
     time_left: Option<String>,
+    expiry_warning: bool,
     tunnel_state_label: Option<String>,
     country: Option<String>,
     city: Option<String>,
@@ -72,6 +159,31 @@ pub struct AppModel {
     tunnel_protocol: Option<String>,
     tunnel_in: Option<String>,
     tunnel_out: Option<String>,
+    scheduled_change: Option<String>,
+    traffic_rate: Option<String>,
+    traffic_total: Option<String>,
+    session_duration: Option<String>,
+    last_reconnect: Option<String>,
+    connection_check: Option<String>,
+    /// What the last connection check found wrong, until the tunnel exit changes.
+    connection_warning: Option<String>,
+    checking_connection: bool,
+
+    #[do_not_track]
+    session: Option<Session>,
+
+    #[do_not_track]
+    connecting_since: Option<DateTime<Utc>>,
+
+    #[do_not_track]
+    account_data_fetched: Option<Instant>,
+
+    /// Refreshes whose failure was reported, until they work again.
+    #[do_not_track]
+    failing_refreshes: HashSet<Refresh>,
+
+    #[do_not_track]
+    saved_state: SavedState,
 
     #[no_eq]
     components: Option<AppComponents>,
@@ -79,13 +191,173 @@ pub struct AppModel {
     #[do_not_track]
     daemon_connector: DaemonConnector,
 
+    #[do_not_track]
+    config: Config,
+
+    #[do_not_track]
+    schedule_sender: Option<watch::Sender<Schedule>>,
+
+    #[do_not_track]
+    traffic_sampler: Option<TrafficSampler>,
+
+    #[do_not_track]
+    traffic_stats: Rc<RefCell<TrafficStats>>,
+
     #[no_eq]
     account_action: Option<RelmAction<AccountAction>>,
+
+    #[do_not_track]
+    toast_overlay: Option<adw::ToastOverlay>,
 }
 
 pub struct AppComponents {
     account: AsyncController<AccountModel>,
     preferences: AsyncController<PreferencesModel>,
+    schedule: AsyncController<ScheduleModel>,
+    history: AsyncController<HistoryModel>,
+    too_many_devices: Controller<TooManyDevicesDialog>,
+    voucher: Controller<VoucherDialog>,
+}
+
+/// Something about the daemon version the user should know.
+#[derive(Debug, Clone, PartialEq)]
+pub struct VersionNotice {
+    message: String,
+    /// The installed daemon isn't supported anymore, which can't be dismissed.
+    is_critical: bool,
+}
+
+impl VersionNotice {
+    /// The most important notice, if any.
+    fn get(
+        app_version_info: Option<&AppVersionInfo>,
+        daemon_version: Option<&str>,
+    ) -> Option<VersionNotice> {
+        let mismatch = daemon_version.and_then(mullvad::compare_with_daemon);
+
+        if app_version_info.is_some_and(|info| !info.supported) {
+            let message = match app_version_info.and_then(|info| info.suggested_upgrade.as_ref()) {
+                Some(upgrade) => tr!(
+                    "The installed Mullvad VPN version is no longer supported, update to {} now.",
+                    upgrade
+                ),
+                None => {
+                    tr!("The installed Mullvad VPN version is no longer supported, update it now.")
+                }
+            };
+            return Some(VersionNotice {
+                message,
+                is_critical: true,
+            });
+        }
+
+        if let Some(mismatch) = mismatch {
+            let daemon_version = daemon_version.unwrap_or_default();
+            let message = match mismatch {
+                InterfaceMismatch::DaemonOlder => tr!(
+                    "The Mullvad daemon {} is older than this app expects ({}), some features may not work.",
+                    daemon_version,
+                    mullvad::INTERFACE_VERSION
+                ),
+                InterfaceMismatch::DaemonNewer => tr!(
+                    "The Mullvad daemon {} is newer than this app expects ({}), consider updating Mullvadwaita.",
+                    daemon_version,
+                    mullvad::INTERFACE_VERSION
+                ),
+            };
+            return Some(VersionNotice {
+                message,
+                is_critical: false,
+            });
+        }
+
+        app_version_info
+            .and_then(|info| info.suggested_upgrade.as_ref())
+            .map(|upgrade| VersionNotice {
+                message: tr!("Mullvad VPN {} is available.", upgrade),
+                is_critical: false,
+            })
+    }
+}
+
+/// A toast telling why `action` failed, serious errors stay until dismissed.
+pub fn get_error_toast(action: &str, error: &DaemonError) -> adw::Toast {
+    let severity = error.get_severity();
+    adw::Toast::builder()
+        .title(tr!(
+            // Translators: What failed, e.g. "Connecting failed.", then why.
+            "error toast" => "{} {}",
+            action,
+            error.get_message()
+        ))
+        .use_markup(false)
+        .priority(if severity >= Severity::Error {
+            adw::ToastPriority::High
+        } else {
+            adw::ToastPriority::Normal
+        })
+        .timeout(match severity {
+            Severity::Info => 2,
+            Severity::Warning => 5,
+            Severity::Error => 0,
+        })
+        .build()
+}
+
+/// How often the account data is refetched while out of time, to notice time added elsewhere.
+const OUT_OF_TIME_REFRESH_INTERVAL: Duration = Duration::from_secs(30);
+
+/// Below this much time left the expiry is shown as a warning.
+const EXPIRY_WARNING_DAYS: i64 = 3;
+
+/// Data fetched again and again in the background.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Refresh {
+    AccountData,
+    Devices,
+}
+
+impl Refresh {
+    fn get_failed_action(self) -> String {
+        match self {
+            Refresh::AccountData => tr!("Fetching the account data failed."),
+            Refresh::Devices => tr!("Listing the devices failed."),
+        }
+    }
+}
+
+/// `url` with the `token` query parameter added to the ones it has.
+fn add_token_to_url(url: &str, token: &str) -> Result<String, gtk::glib::Error> {
+    use gtk::glib::{Uri, UriFlags};
+
+    // Encoded, so the rest of the URL is kept as it was written.
+    let uri = Uri::parse(url, UriFlags::ENCODED)?;
+    let token = format!("token={}", Uri::escape_string(token, None, false));
+    let query = match uri.query().filter(|query| !query.is_empty()) {
+        Some(query) => format!("{query}&{token}"),
+        None => token,
+    };
+
+    Ok(Uri::join(
+        uri.flags(),
+        Some(&uri.scheme()),
+        uri.userinfo().as_deref(),
+        uri.host().as_deref(),
+        uri.port(),
+        &uri.path(),
+        Some(&query),
+        uri.fragment().as_deref(),
+    )
+    .to_string())
+}
+
+/// The current connected session.
+#[derive(Debug)]
+struct Session {
+    started: DateTime<Utc>,
+    /// The tunnel was already connected when the app started,
+    /// so the real start of the session is unknown.
+    since_app_start: bool,
 }
 
 #[derive(Debug, SmartDefault)]
@@ -93,6 +365,7 @@ pub struct AppComponents {
 enum AppState {
     LoggedIn(AccountAndDevice),
     Login(LoginState),
+    DeviceRevoked,
     #[default]
     ConnectingToDaemon,
 }
@@ -130,6 +403,12 @@ impl AppModel {
         )
     }
 
+    fn is_account_expired(&self) -> bool {
+        self.get_account_data()
+            .as_ref()
+            .is_some_and(|data| data.expiry <= Utc::now())
+    }
+
     fn can_secure_connection(&self) -> bool {
         matches!(
             self.get_tunnel_state(),
@@ -142,6 +421,12 @@ impl AppModel {
         )
     }
 
+    fn is_blocking(&self) -> bool {
+        self.get_tunnel_state()
+            .as_ref()
+            .is_some_and(|ts| ts.is_blocking())
+    }
+
     fn is_connected(&self) -> bool {
         self.get_tunnel_state()
             .as_ref()
@@ -189,15 +474,222 @@ impl AppModel {
             .map(|acc| acc.account_number.clone())
     }
 
-    fn fetch_account_data(&self, sender: AsyncComponentSender<Self>) {
+    /// Cheap to call often, the daemon is only asked when the cached data is outdated.
+    fn fetch_account_data(&mut self, sender: AsyncComponentSender<Self>) {
+        if let Some(account_token) = self.get_account_token() {
+            self.account_data_fetched = Some(Instant::now());
+            let daemon_connector = self.daemon_connector.clone();
+
+            sender.oneshot_command(async move {
+                match daemon_connector.get_account_data(account_token).await {
+                    Ok(account_data) => AppMsg::DaemonEvent(Event::AccountData(account_data)),
+                    Err(error) => AppMsg::RefreshFailed(Refresh::AccountData, error),
+                }
+            });
+        }
+    }
+
+    /// Starts, continues or ends the session timer on a tunnel state change.
+    fn update_session(&mut self, new_tunnel_state: &TunnelState) {
+        let now = Utc::now();
+        let was_connected = self.is_connected();
+
+        if new_tunnel_state.is_connected() {
+            if !was_connected {
+                let since_app_start = self.get_tunnel_state().is_none();
+
+                if let Some(connecting_since) = self.connecting_since.take() {
+                    let took = (now - connecting_since).num_milliseconds() as f64 / 1000.0;
+                    self.set_last_reconnect(Some(tr!("Last reconnect took {:.1} s", took)));
+                }
+
+                self.session = Some(Session {
+                    started: now,
+                    since_app_start,
+                });
+            }
+        } else if new_tunnel_state.is_connecting_or_reconnecting() {
+            self.session = None;
+            // Only connects following a connected session are reconnects.
+            if was_connected || self.connecting_since.is_some() {
+                self.connecting_since.get_or_insert(now);
+            }
+        } else if new_tunnel_state.is_in_error_state() {
+            // Blocked, not connected, but getting out of it counts as a reconnect.
+            self.session = None;
+            if was_connected {
+                self.connecting_since.get_or_insert(now);
+            }
+        } else {
+            self.session = None;
+            self.connecting_since = None;
+        }
+
+        self.update_session_duration();
+    }
+
+    fn update_session_duration(&mut self) {
+        self.set_session_duration(self.session.as_ref().map(|session| {
+            let duration = (Utc::now() - session.started).to_human_string();
+            if session.since_app_start {
+                tr!("Connected for {} (since app start)", duration)
+            } else {
+                tr!("Connected for {}", duration)
+            }
+        }));
+    }
+
+    fn is_version_notice_critical(&self) -> bool {
+        self.get_version_notice()
+            .as_ref()
+            .is_some_and(|notice| notice.is_critical)
+    }
+
+    fn update_version_notice(&mut self) {
+        let notice = VersionNotice::get(
+            self.app_version_info.as_ref(),
+            self.get_daemon_version().as_deref(),
+        )
+        .filter(|notice| !self.dismissed_version_notices.contains(notice));
+        self.set_version_notice(notice);
+    }
+
+    /// Logs the error and shows it in a toast, `action` says what failed.
+    fn report_error(&self, action: &str, err: &anyhow::Error) {
+        log::warn!("{action} {err:#}");
+        if let Some(toast_overlay) = &self.toast_overlay {
+            toast_overlay.add_toast(get_error_toast(action, &DaemonError::from_error(err)));
+        }
+    }
+
+    /// Reloads the account numbers offered for logging in again.
+    async fn update_account_history(&mut self) {
+        match self.daemon_connector.get_account_history().await {
+            Ok(account_history) => self.set_account_history(account_history),
+            Err(err) => self.report_error(&tr!("Reading the account history failed."), &err),
+        }
+    }
+
+    fn update_daemon_retry_label(&mut self) {
+        self.set_daemon_retry_label(self.daemon_retry_at.map(|retry_at| {
+            let left = retry_at.saturating_duration_since(Instant::now());
+            if left.is_zero() {
+                tr!("Retrying...")
+            } else {
+                tr!("Retrying in {} s", left.as_secs_f64().ceil() as u64)
+            }
+        }));
+    }
+
+    fn update_time_left(&mut self) {
+        let left = self
+            .get_account_data()
+            .as_ref()
+            .map(|data| data.expiry - Utc::now());
+
+        self.set_expiry_warning(left.is_some_and(|left| left.num_days() < EXPIRY_WARNING_DAYS));
+        let stale_since = *self.get_account_data_stale_since();
+        self.set_time_left(left.map(|left| {
+            let time_left = if left <= TimeDelta::zero() {
+                tr!("<b>Expired</b>")
+            } else if left.num_days() < 1 {
+                tr!("<b>Time left</b>: {}", left.to_human_string())
+            } else {
+                tr!("<b>Time left</b>: 1 day" | "<b>Time left</b>: {n} days" % left.num_days())
+                    .to_string()
+            };
+
+            match stale_since {
+                Some(updated) => tr!(
+                    "{} (last updated {})",
+                    time_left,
+                    updated.with_timezone(&Local).format("%H:%M")
+                ),
+                None => time_left,
+            }
+        }));
+    }
+
+    /// Sends a desktop notification for every newly passed expiry threshold.
+    fn notify_expiry(&mut self) {
+        let Some(left) = self
+            .get_account_data()
+            .as_ref()
+            .map(|data| data.expiry - Utc::now())
+        else {
+            return;
+        };
+
+        let notified_before = self.saved_state.clone();
+        let hours = self
+            .saved_state
+            .pass_expiry_thresholds(&self.config.expiry_notification_hours, left);
+        // Kept over restarts so the same threshold isn't notified again.
+        if self.saved_state != notified_before {
+            if let Err(err) = self.saved_state.save() {
+                log::warn!("Can't save the app state: {err}");
+            }
+        }
+
+        let Some(hours) = hours else {
+            return;
+        };
+        log::debug!("Account expiry notification at {hours} h");
+
+        let notification = gtk::gio::Notification::new(&tr!("Mullvad account expires soon"));
+        notification.set_body(Some(&tr!(
+            "Your account has {} left. Add more time to stay connected.",
+            left.to_human_string()
+        )));
+        relm4::main_application().send_notification(Some("account-expiry"), &notification);
+    }
+
+    fn update_traffic(&mut self) {
+        let traffic_stats = self.traffic_stats.clone();
+        let mut stats = traffic_stats.borrow_mut();
+
+        match (self.get_tunnel_state(), &self.traffic_sampler) {
+            (Some(TunnelState::Connected { endpoint, .. }), Some(sampler)) => {
+                if stats.get_interface().is_none() {
+                    if let Some(interface) = sampler.find_tunnel_interface(endpoint.tunnel_type) {
+                        stats.start(interface);
+                    }
+                }
+                if let Some(interface) = stats.get_interface().map(str::to_string) {
+                    match sampler.read_counters(&interface) {
+                        Ok(counters) => stats.add_sample(Instant::now(), counters),
+                        Err(err) => log::debug!("Can't read {interface} counters: {err}"),
+                    }
+                }
+            }
+            _ => stats.reset(),
+        }
+
+        self.set_traffic_rate(stats.get_rate().map(|rate| {
+            format!(
+                "↓ {}/s  ↑ {}/s",
+                traffic::format_bytes(rate.rx_bytes_per_sec),
+                traffic::format_bytes(rate.tx_bytes_per_sec)
+            )
+        }));
+        self.set_traffic_total(stats.get_totals().map(|totals| {
+            format!(
+                "↓ {}  ↑ {}",
+                traffic::format_bytes(totals.rx_bytes as f64),
+                traffic::format_bytes(totals.tx_bytes as f64)
+            )
+        }));
+    }
+
+    fn fetch_devices(&self, sender: AsyncComponentSender<Self>) {
         if let Some(account_token) = self.get_account_token() {
-            let mut daemon_connector = self.daemon_connector.clone();
+            let daemon_connector = self.daemon_connector.clone();
 
             sender.oneshot_command(async move {
-                if let Ok(account_data) = daemon_connector.get_account_data(account_token).await {
-                    return AppMsg::DaemonEvent(Event::AccountData(account_data));
+                match daemon_connector.list_devices(account_token).await {
+                    Ok(devices) => AppMsg::Devices(devices),
+                    Err(error) => AppMsg::RefreshFailed(Refresh::Devices, error),
                 }
-                AppMsg::Ignore
             });
         }
     }
@@ -217,7 +709,11 @@ impl AppModel {
             let tunnel_in = ts.get_tunnel_in();
             let tunnel_out = ts.get_tunnel_out();
 
+            if tunnel_out != self.tunnel_out {
+                self.set_connection_warning(None);
+            }
             self.set_banner_label(banner_label);
+            self.set_connection_check(None);
             self.set_tunnel_state_label(tunnel_state_label);
             self.set_country(country);
             self.set_city(city);
@@ -227,16 +723,7 @@ impl AppModel {
             self.set_tunnel_out(tunnel_out);
         }
 
-        self.set_time_left(self.get_account_data().as_ref().map(|data| {
-            let now = Utc::now();
-            if now >= data.expiry {
-                tr!("<b>Expired</b>")
-            } else {
-                let left = data.expiry - now;
-                tr!("<b>Time left</b>: 1 day" | "<b>Time left</b>: {n} days" % left.num_days())
-                    .to_string()
-            }
-        }));
+        self.update_time_left();
 
         if self.state_changed() {
             if let Some(account_action) = &self.account_action {
@@ -256,8 +743,11 @@ impl AppModel {
     fn get_current_view_name(&self) -> &'static str {
         match (self.get_state(), self.get_tunnel_state().is_some()) {
             // Main page.
+            (AppState::LoggedIn(_), true) if self.get_new_account().is_some() => "new_account",
+            (AppState::LoggedIn(_), true) if self.is_account_expired() => "out_of_time",
             (AppState::LoggedIn(_), true) => "logged_in",
             (AppState::Login(_), ..) => "login",
+            (AppState::DeviceRevoked, true) => "device_revoked",
             (AppState::ConnectingToDaemon, ..) | (_, false) => "connecting_to_daemon",
         }
     }
@@ -282,7 +772,8 @@ impl AppModel {
 
 #[relm4::component(async, pub)]
 impl AsyncComponent for AppModel {
-    type Init = ();
+    /// The `--socket` command-line option.
+    type Init = Option<PathBuf>;
     type Input = AppInput;
     type Output = ();
     type CommandOutput = AppMsg;
@@ -306,6 +797,32 @@ impl AsyncComponent for AppModel {
                 set_revealed: model.get_banner_label().is_some(),
             },
 
+            #[template_child]
+            version_banner {
+                connect_button_clicked => AppInput::DismissVersionNotice,
+
+                #[track = "model.changed(AppModel::version_notice())"]
+                set_title: model
+                    .get_version_notice()
+                    .as_ref()
+                    .map(|notice| notice.message.as_str())
+                    .unwrap_or_default(),
+
+                #[track = "model.changed(AppModel::version_notice())"]
+                set_revealed: model.get_version_notice().is_some(),
+
+                // Critical notices stay until the daemon is updated.
+                #[track = "model.changed(AppModel::version_notice())"]
+                set_button_label: if model.is_version_notice_critical() {
+                    None
+                } else {
+                    Some(&*tr!("Dismiss"))
+                },
+
+                #[track = "model.changed(AppModel::version_notice())"]
+                set_class_active[model.is_version_notice_critical()]: "unsupported_version_banner",
+            },
+
             #[template_child]
             view_stack {
                 #[watch]
@@ -322,6 +839,9 @@ impl AsyncComponent for AppModel {
             logged_in_view.time_left_label {
                 #[track = "model.changed(AppModel::time_left())"]
                 set_label: model.get_time_left().to_str(),
+
+                #[track = "model.changed(AppModel::expiry_warning())"]
+                set_class_active[model.expiry_warning]: "warning",
             },
 
             #[template_child]
@@ -339,6 +859,24 @@ impl AsyncComponent for AppModel {
                 set_class_active[model.is_connected()]: "connected_state_label"
             },
 
+            #[template_child]
+            logged_in_view.session_duration_label {
+                #[track = "model.changed(AppModel::session_duration())"]
+                set_label: model.get_session_duration().to_str(),
+
+                #[track = "model.changed(AppModel::session_duration())"]
+                set_visible: model.get_session_duration().is_some(),
+            },
+
+            #[template_child]
+            logged_in_view.last_reconnect_label {
+                #[track = "model.changed(AppModel::last_reconnect())"]
+                set_label: model.get_last_reconnect().to_str(),
+
+                #[track = "model.changed(AppModel::last_reconnect())"]
+                set_visible: model.get_last_reconnect().is_some(),
+            },
+
             #[template_child]
             logged_in_view.country_label {
                 #[track = "model.changed(AppModel::country())"]
@@ -381,6 +919,74 @@ impl AsyncComponent for AppModel {
                 set_subtitle: model.get_tunnel_out().to_str(),
             },
 
+            #[template_child]
+            logged_in_view.scheduled_change_label {
+                #[track = "model.changed(AppModel::scheduled_change())"]
+                set_label: model.get_scheduled_change().to_str(),
+
+                #[track = "model.changed(AppModel::scheduled_change())"]
+                set_visible: model.get_scheduled_change().is_some(),
+            },
+
+            #[template_child]
+            logged_in_view.connection_check_row {
+                #[track = "model.changed(AppModel::connection_check())"]
+                set_subtitle: model.get_connection_check().to_str(),
+            },
+
+            #[template_child]
+            logged_in_view.connection_warning_row {
+                #[track = "model.changed(AppModel::connection_warning())"]
+                set_subtitle: model.get_connection_warning().to_str(),
+
+                #[track = "model.changed(AppModel::connection_warning())"]
+                set_visible: model.get_connection_warning().is_some(),
+            },
+
+            #[template_child]
+            logged_in_view.verify_connection_stack {
+                #[track = "model.changed(AppModel::checking_connection())"]
+                set_visible_child_name: if model.checking_connection { "checking" } else { "default" },
+            },
+
+            #[template_child]
+            logged_in_view.verify_connection_button {
+                connect_clicked => AppInput::VerifyConnection,
+
+                #[track = "model.tunnel_state_changed()"]
+                set_sensitive: model.is_connected(),
+            },
+
+            #[template_child]
+            logged_in_view.traffic_rate_row {
+                #[track = "model.changed(AppModel::traffic_rate())"]
+                set_subtitle: model.get_traffic_rate().to_str(),
+
+                #[track = "model.changed(AppModel::traffic_rate())"]
+                set_visible: model.get_traffic_rate().is_some(),
+            },
+
+            #[template_child]
+            logged_in_view.traffic_total_row {
+                #[track = "model.changed(AppModel::traffic_total())"]
+                set_subtitle: model.get_traffic_total().to_str(),
+
+                #[track = "model.changed(AppModel::traffic_total())"]
+                set_visible: model.get_traffic_total().is_some(),
+            },
+
+            #[template_child]
+            logged_in_view.traffic_graph_row {
+                #[track = "model.changed(AppModel::traffic_rate())"]
+                set_visible: model.get_traffic_rate().is_some(),
+            },
+
+            #[template_child]
+            logged_in_view.traffic_graph {
+                #[track = "model.changed(AppModel::traffic_rate())"]
+                queue_draw: (),
+            },
+
             #[template_child]
             logged_in_view.secure_my_connection_button {
                 connect_clicked => AppInput::SecureMyConnection,
@@ -414,92 +1020,371 @@ impl AsyncComponent for AppModel {
             },
 
             #[template_child]
-            login_view {
-                #[watch]
-                set_sensitive: !(model.is_logging_in() | model.is_creating_account()),
+            device_revoked_view.blocking_label {
+                #[track = "model.tunnel_state_changed()"]
+                set_label: &if model.is_blocking() {
+                    tr!("<b>Your internet traffic is currently blocked.</b>")
+                } else {
+                    tr!("Your internet traffic is not blocked.")
+                },
             },
 
             #[template_child]
-            login_view.disable_lockdown_mode_bin {
-                #[track = "model.changed(AppModel::lockdown_mode())"]
-                set_visible: model.lockdown_mode,
+            device_revoked_view.login_again_button {
+                connect_clicked => AppInput::LoginAgain,
+
+                #[track = "model.changed(AppModel::account_history())"]
+                set_visible: model.get_account_history().is_some(),
             },
 
             #[template_child]
-            login_view.disable_lockdown_mode_button {
-                connect_clicked[sender] => move |_| {
-                    sender.input(AppInput::Set(Pref::LockdownMode(false)));
-                }
+            device_revoked_view.logout_and_unblock_button {
+                connect_clicked => AppInput::LogoutAndUnblock,
             },
 
             #[template_child]
-            login_view.account_number {
-                #[track = "model.is_logged_in()"]
-                set_text: "",
-
-                connect_entry_activated[main_window] => move |_| {
-                    main_window.login_view.login_button.emit_clicked();
+            out_of_time_view.blocking_label {
+                #[track = "model.tunnel_state_changed()"]
+                set_label: &if model.is_blocking() {
+                    tr!("<b>Your internet traffic is currently blocked.</b>")
+                } else {
+                    tr!("Your internet traffic is not blocked.")
                 },
             },
 
             #[template_child]
-            login_view.login_button_stack {
-                set_visible_child_name: if model.is_logging_in() { "logging_in" } else { "default" }
+            out_of_time_view.buy_more_credit_button {
+                connect_clicked => AppInput::ManageAccount,
             },
 
             #[template_child]
-            login_view.login_button {
-                connect_clicked[sender, main_window] => move |_| {
-                    sender.input(AppInput::Login(main_window.login_view.account_number.text().into()));
-                }
+            out_of_time_view.redeem_voucher_button {
+                connect_clicked => AppInput::ShowVoucherDialog,
             },
 
             #[template_child]
-            login_view.account_history_row {
-                #[track = "model.changed(AppModel::account_history())"]
-                set_title: model.get_account_history().to_str(),
+            out_of_time_view.disconnect_button {
+                connect_clicked => AppInput::Disconnect,
 
-                #[track = "model.changed(AppModel::account_history())"]
-                set_visible: model.get_account_history().is_some(),
+                #[track = "model.tunnel_state_changed()"]
+                set_label: &if model.can_disconnect() || model.is_connecting_or_reconnecting() {
+                    tr!("Disconnect")
+                } else {
+                    tr!("Unblock")
+                },
+
+                #[track = "model.tunnel_state_changed()"]
+                set_visible: model.can_disconnect()
+                    || model.is_connecting_or_reconnecting()
+                    || model.is_blocking(),
             },
 
             #[template_child]
-            login_view.clear_account_history_button {
-                connect_clicked[sender] => move |_| {
-                    sender.input(AppInput::ClearAccountHistory);
-                }
+            new_account_view.account_number_label {
+                #[track = "model.changed(AppModel::new_account())"]
+                set_label: &model
+                    .get_new_account()
+                    .as_deref()
+                    .map(|account_number| account_number.group_by_four(' '))
+                    .unwrap_or_default(),
             },
 
             #[template_child]
-            login_view.create_account_button {
-                connect_clicked[sender] => move |_| {
-                    sender.input(AppInput::CreateAccount);
-                },
+            new_account_view.qr_code {
+                #[track = "model.changed(AppModel::new_account())"]
+                set_draw_func: qr_code::get_draw_func(model.get_new_account().as_deref()),
             },
-        }
-    }
 
-    menu! {
-        primary_menu: {
-            section! {
-                &tr!("Account") => AccountAction,
-                &tr!("Preferences") => PreferencesAction,
-                &tr!("About") => AboutAction,
+            #[template_child]
+            new_account_view.copy_button {
+                connect_clicked => AppInput::CopyAccountNumber,
             },
-        }
-    }
-
-    async fn init(
-        _: Self::Init,
-        root: Self::Root,
-        sender: AsyncComponentSender<Self>,
-    ) -> AsyncComponentParts<Self> {
-        sender.command(|out, shutdown| {
-            shutdown
-                .register(listen_to_mullvad_events(out))
-                .drop_on_shutdown()
-                .boxed()
-        });
+
+            #[template_child]
+            new_account_view.save_button {
+                connect_clicked => AppInput::SaveAccountNumber,
+            },
+
+            #[template_child]
+            new_account_view.saved_check_button {
+                #[track = "model.changed(AppModel::new_account())"]
+                set_active: false,
+            },
+
+            #[template_child]
+            new_account_view.continue_button {
+                connect_clicked => AppInput::ConfirmAccountSaved,
+            },
+
+            #[template_child]
+            connecting_view.failure_box {
+                #[track = "model.changed(AppModel::daemon_connection_failure())"]
+                set_visible: model.get_daemon_connection_failure().is_some(),
+            },
+
+            #[template_child]
+            connecting_view.failure_label {
+                #[track = "model.changed(AppModel::daemon_connection_failure())"]
+                set_label: &model
+                    .get_daemon_connection_failure()
+                    .as_ref()
+                    .map(ConnectionFailure::get_description)
+                    .unwrap_or_default(),
+            },
+
+            #[template_child]
+            connecting_view.suggestion_label {
+                #[track = "model.changed(AppModel::daemon_connection_failure())"]
+                set_label: &model
+                    .get_daemon_connection_failure()
+                    .as_ref()
+                    .map(ConnectionFailure::get_suggestion)
+                    .unwrap_or_default(),
+            },
+
+            #[template_child]
+            connecting_view.retry_label {
+                #[track = "model.changed(AppModel::daemon_retry_label())"]
+                set_label: model.get_daemon_retry_label().to_str(),
+            },
+
+            #[template_child]
+            connecting_view.retry_button {
+                connect_clicked => AppInput::RetryDaemonConnection,
+            },
+
+            #[template_child]
+            health_spinner {
+                #[track = "model.changed(AppModel::daemon_health())"]
+                set_visible: *model.get_daemon_health() == ConnectionHealth::Reconnecting,
+                #[track = "model.changed(AppModel::daemon_health())"]
+                set_spinning: *model.get_daemon_health() == ConnectionHealth::Reconnecting,
+                set_tooltip_text: Some(&tr!("Reconnecting to the Mullvad system service…")),
+            },
+
+            #[template_child]
+            health_icon {
+                #[track = "model.changed(AppModel::daemon_health())"]
+                set_visible: matches!(model.get_daemon_health(), ConnectionHealth::Broken(_)),
+                #[track = "model.changed(AppModel::daemon_health())"]
+                set_tooltip_text: match model.get_daemon_health() {
+                    ConnectionHealth::Broken(failure) => Some(failure.get_description()),
+                    _ => None,
+                }
+                .as_deref(),
+            },
+
+            #[template_child]
+            window_title {
+                #[track = "model.changed(AppModel::daemon_socket_path())"]
+                set_subtitle: &match model.get_daemon_socket_path() {
+                    Some(socket_path) => tr!("via {}", socket_path.display()),
+                    None => tr!("for Mullvad VPN"),
+                },
+            },
+
+            #[template_child]
+            connecting_view.service_box {
+                #[track = "model.changed(AppModel::daemon_service_status())"]
+                set_visible: model.get_daemon_service_status().is_some(),
+            },
+
+            #[template_child]
+            connecting_view.service_label {
+                #[track = "model.changed(AppModel::daemon_service_status())"]
+                set_label: &model
+                    .get_daemon_service_status()
+                    .as_ref()
+                    .map(ServiceStatus::get_label)
+                    .unwrap_or_default(),
+            },
+
+            #[template_child]
+            connecting_view.start_service_button {
+                connect_clicked => AppInput::StartDaemonService,
+
+                #[track = "model.changed(AppModel::daemon_service_status())"]
+                set_visible: model
+                    .get_daemon_service_status()
+                    .as_ref()
+                    .is_some_and(ServiceStatus::can_start),
+            },
+
+            #[template_child]
+            connecting_view.enable_service_button {
+                connect_clicked => AppInput::EnableDaemonService,
+
+                #[track = "model.changed(AppModel::daemon_service_status())"]
+                set_visible: model
+                    .get_daemon_service_status()
+                    .as_ref()
+                    .is_some_and(ServiceStatus::can_enable),
+            },
+
+            #[template_child]
+            login_view {
+                #[watch]
+                set_sensitive: !(model.is_logging_in() | model.is_creating_account()),
+            },
+
+            #[template_child]
+            login_view.disable_lockdown_mode_bin {
+                #[track = "model.changed(AppModel::lockdown_mode())"]
+                set_visible: model.lockdown_mode,
+            },
+
+            #[template_child]
+            login_view.disable_lockdown_mode_button {
+                connect_clicked[sender] => move |_| {
+                    sender.input(AppInput::Set(Pref::LockdownMode(false)));
+                }
+            },
+
+            #[template_child]
+            login_view.account_number {
+                #[track = "model.is_logged_in()"]
+                set_text: "",
+
+                connect_entry_activated[main_window] => move |_| {
+                    let login_button = &main_window.login_view.login_button;
+                    if login_button.is_sensitive() {
+                        login_button.emit_clicked();
+                    }
+                },
+            },
+
+            #[template_child]
+            login_view.login_button_stack {
+                set_visible_child_name: if model.is_logging_in() { "logging_in" } else { "default" }
+            },
+
+            #[template_child]
+            login_view.login_button {
+                connect_clicked[sender, main_window] => move |_| {
+                    let account_number = main_window.login_view.account_number.text().replace(' ', "");
+                    sender.input(AppInput::Login(account_number));
+                }
+            },
+
+            #[template_child]
+            login_view.account_history_row {
+                connect_activated => AppInput::LoginAgain,
+
+                #[track = "model.changed(AppModel::account_history())"]
+                set_title: &model
+                    .get_account_history()
+                    .as_deref()
+                    .map(|account_number| account_number.group_by_four(' '))
+                    .unwrap_or_default(),
+
+                #[track = "model.changed(AppModel::account_history())"]
+                set_visible: model.get_account_history().is_some(),
+            },
+
+            #[template_child]
+            login_view.clear_account_history_button {
+                connect_clicked[sender] => move |_| {
+                    sender.input(AppInput::ClearAccountHistory);
+                }
+            },
+
+            #[template_child]
+            login_view.create_account_button {
+                connect_clicked[sender] => move |_| {
+                    sender.input(AppInput::CreateAccount);
+                },
+            },
+        }
+    }
+
+    menu! {
+        primary_menu: {
+            section! {
+                &tr!("Account") => AccountAction,
+                &tr!("Preferences") => PreferencesAction,
+                &tr!("Schedule") => ScheduleAction,
+                &tr!("History") => HistoryAction,
+                &tr!("About") => AboutAction,
+            },
+        }
+    }
+
+    async fn init(
+        daemon_socket_override: Self::Init,
+        root: Self::Root,
+        sender: AsyncComponentSender<Self>,
+    ) -> AsyncComponentParts<Self> {
+        let config = Config::load();
+        let daemon_socket_path = daemon_socket_override
+            .clone()
+            .or_else(|| config.daemon_socket_path.clone());
+        let daemon_connector = DaemonConnector::with_socket_path(daemon_socket_path.clone());
+        {
+            let daemon_connector = daemon_connector.clone();
+            sender.command(|out, shutdown| {
+                shutdown
+                    .register(listen_to_mullvad_events(out, daemon_connector))
+                    .drop_on_shutdown()
+                    .boxed()
+            });
+        }
+
+        {
+            let health_receiver = daemon_connector.health_receiver();
+            sender.command(|out, shutdown| {
+                shutdown
+                    .register(listen_to_connection_health(out, health_receiver))
+                    .drop_on_shutdown()
+                    .boxed()
+            });
+        }
+
+        sender.command(|out, shutdown| shutdown.register(tick(out)).drop_on_shutdown().boxed());
+
+        {
+            let events = daemon_connector.subscribe();
+            sender.command(|_out, shutdown| {
+                shutdown
+                    .register(hooks::run_hooks(events))
+                    .drop_on_shutdown()
+                    .boxed()
+            });
+        }
+
+        let history_log = HistoryLog::default();
+        {
+            let events = daemon_connector.subscribe();
+            let history_log = history_log.clone();
+            sender.command(|_out, shutdown| {
+                shutdown
+                    .register(history::record_history(events, history_log))
+                    .drop_on_shutdown()
+                    .boxed()
+            });
+        }
+
+        let traffic_sampler = TrafficSampler::new(&config.sysfs_root);
+
+        let (schedule_sender, schedule_receiver) = watch::channel(config.schedule.clone());
+        {
+            let daemon_connector = daemon_connector.clone();
+            sender.command(|out, shutdown| {
+                shutdown
+                    .register(listen_to_schedule(out, schedule_receiver, daemon_connector))
+                    .drop_on_shutdown()
+                    .boxed()
+            });
+        }
+
+        {
+            let bus_address = config.systemd_bus_address.clone();
+            sender.command(|out, shutdown| {
+                shutdown
+                    .register(listen_to_systemd(out, bus_address))
+                    .drop_on_shutdown()
+                    .boxed()
+            });
+        }
 
         // Actions
         let mut group = RelmActionGroup::<WindowActionGroup>::new();
@@ -526,6 +1411,22 @@ impl AsyncComponent for AppModel {
                 }));
             }
 
+            // Schedule
+            {
+                let sender = sender.clone();
+                group.add_action(RelmAction::<ScheduleAction>::new_stateless(move |_| {
+                    sender.input(AppInput::Schedule);
+                }));
+            }
+
+            // History
+            {
+                let sender = sender.clone();
+                group.add_action(RelmAction::<HistoryAction>::new_stateless(move |_| {
+                    sender.input(AppInput::History);
+                }));
+            }
+
             // About
             {
                 let sender = sender.clone();
@@ -535,7 +1436,7 @@ impl AsyncComponent for AppModel {
             }
         }
 
-        let model = AppModel {
+        let mut model = AppModel {
             components: Some(AppComponents {
                 account: AccountModel::builder()
                     .transient_for(&*root)
@@ -543,16 +1444,73 @@ impl AsyncComponent for AppModel {
                     .forward(sender.input_sender(), identity),
                 preferences: PreferencesModel::builder()
                     .transient_for(&*root)
+                    .launch(PreferencesInit {
+                        daemon_socket_path: config.daemon_socket_path.clone(),
+                        daemon_socket_override: daemon_socket_override.clone(),
+                    })
+                    .forward(sender.input_sender(), identity),
+                schedule: ScheduleModel::builder()
+                    .transient_for(&*root)
+                    .launch(config.schedule.clone())
+                    .forward(sender.input_sender(), identity),
+                history: HistoryModel::builder()
+                    .transient_for(&*root)
+                    .launch(history_log)
+                    .detach(),
+                too_many_devices: TooManyDevicesDialog::builder()
+                    .launch(())
+                    .forward(sender.input_sender(), |output| {
+                        AppInput::RemoveDevicesAndLogin(output.account, output.devices)
+                    }),
+                voucher: VoucherDialog::builder()
                     .launch(())
                     .forward(sender.input_sender(), identity),
             }),
             account_action: Some(account_action),
+            daemon_connector,
+            daemon_socket_path,
+            daemon_socket_override,
+            config,
+            saved_state: SavedState::load(),
+            schedule_sender: Some(schedule_sender),
+            traffic_sampler: Some(traffic_sampler),
             ..Default::default()
         };
 
         let widgets = view_output!();
 
+        {
+            let login_button = widgets.main_window.login_view.login_button.clone();
+            widgets
+                .main_window
+                .login_view
+                .account_number
+                .enable_grouped_digits_behavior(ACCOUNT_NUMBER_LENGTH, move |digits| {
+                    login_button.set_sensitive(digits.len() == ACCOUNT_NUMBER_LENGTH);
+                });
+        }
+
+        {
+            let traffic_stats = model.traffic_stats.clone();
+            widgets
+                .main_window
+                .logged_in_view
+                .traffic_graph
+                .set_draw_func(move |_, cr, width, height| {
+                    sparkline::draw_traffic_sparkline(
+                        cr,
+                        width,
+                        height,
+                        traffic_stats.borrow().get_history(),
+                    );
+                });
+
+            let traffic_graph = widgets.main_window.logged_in_view.traffic_graph.clone();
+            adw::StyleManager::default().connect_dark_notify(move |_| traffic_graph.queue_draw());
+        }
+
         group.register_for_widget(&*widgets.main_window);
+        model.toast_overlay = Some(widgets.main_window.toast_overlay.clone());
 
         AsyncComponentParts { model, widgets }
     }
@@ -572,65 +1530,234 @@ impl AsyncComponent for AppModel {
                 self.set_banner_label(None);
                 self.set_state(AppState::Login(LoginState::LoggingIn));
 
-                let mut daemon_connector = self.daemon_connector.clone();
+                let daemon_connector = self.daemon_connector.clone();
                 sender.oneshot_command(async move {
-                    let login_result =
-                        daemon_connector
-                            .login_account(account_token)
-                            .await
-                            .map_err(|err| {
-                                log::debug!("Login error: {:#?}", err);
-                                match err.downcast_ref() {
-                                    Some(Error::InvalidAccount) => {
-                                        tr!("Login failed. Invalid account number.")
-                                    }
-                                    Some(Error::TooManyDevices) => {
-                                        tr!("Login failed. Too many devices.")
-                                    }
-                                    // TODO: process other errors.
-                                    _ => tr!("Login failed"),
-                                }
-                            });
-                    match login_result {
-                        Ok(_) => AppMsg::Ignore,
-                        Err(error) => AppMsg::LoginError(error),
-                    }
+                    let login_result = daemon_connector.login_account(account_token.clone()).await;
+                    process_login_result(login_result, account_token, daemon_connector).await
+                });
+            }
+            AppInput::RemoveDevicesAndLogin(account_token, device_ids) => {
+                self.set_banner_label(None);
+                self.set_state(AppState::Login(LoginState::LoggingIn));
+
+                let daemon_connector = self.daemon_connector.clone();
+                sender.oneshot_command(async move {
+                    let login_result = daemon_connector
+                        .remove_devices_and_login(account_token.clone(), device_ids)
+                        .await;
+                    process_login_result(login_result, account_token, daemon_connector).await
                 });
             }
             AppInput::Logout => {
-                let _ = self.daemon_connector.logout_account().await;
+                if let Err(err) = self.daemon_connector.logout_account().await {
+                    self.report_error(&tr!("Logging out failed."), &err);
+                }
+            }
+            AppInput::LogoutAndUnblock => {
+                if let Err(err) = self.daemon_connector.logout_account().await {
+                    self.report_error(&tr!("Logging out failed."), &err);
+                }
+                if let Err(err) = self.daemon_connector.disconnect().await {
+                    self.report_error(&tr!("Disconnecting failed."), &err);
+                }
             }
+            AppInput::LoginAgain => match self.get_account_history().clone() {
+                Some(account_token) => sender.input(AppInput::Login(account_token)),
+                None => sender.input(AppInput::Logout),
+            },
             AppInput::CreateAccount => {
                 self.set_banner_label(None);
                 self.set_state(AppState::Login(LoginState::CreatingAccount));
 
-                let mut daemon_connector = self.daemon_connector.clone();
+                let daemon_connector = self.daemon_connector.clone();
                 sender.oneshot_command(async move {
                     let result = daemon_connector.create_new_account().await.map_err(|err| {
                         log::debug!("{:#?}", err);
-                        // TODO: process other errors.
-                        tr!("Creating account failed")
+                        tr!(
+                            "Creating account failed. {}",
+                            DaemonError::from_error(&err).get_message()
+                        )
                     });
                     match result {
-                        Ok(_) => AppMsg::Ignore,
+                        Ok(account_number) => AppMsg::AccountCreated(account_number),
                         Err(error) => AppMsg::CreateAccountError(error),
                     }
                 });
             }
+            AppInput::CopyAccountNumber => {
+                if let Some(account_number) = self.get_new_account() {
+                    root.clipboard().set_text(account_number);
+                }
+            }
+            AppInput::SaveAccountNumber => {
+                if let Some(account_number) = self.get_new_account().clone() {
+                    let parent: &gtk::Window = root.as_ref();
+                    let dialog = gtk::FileDialog::builder()
+                        .title(tr!("Save account number"))
+                        .initial_name("mullvad-account-number.txt")
+                        .modal(true)
+                        .build();
+
+                    match dialog.save_future(Some(parent)).await {
+                        Ok(file) => {
+                            let text = format!(
+                                "{}\n",
+                                tr!(
+                                    "Mullvad account number: {}",
+                                    account_number.group_by_four(' ')
+                                )
+                            );
+                            // Written asynchronously, so the main loop keeps running.
+                            if let Err((_, err)) = file
+                                .replace_contents_future(
+                                    text,
+                                    None,
+                                    false,
+                                    gtk::gio::FileCreateFlags::REPLACE_DESTINATION,
+                                )
+                                .await
+                            {
+                                // Only the message, the daemon errors don't apply to a file.
+                                self.report_error(
+                                    &tr!("Saving the account number failed."),
+                                    &anyhow::anyhow!("{}: {err}", file.uri()),
+                                );
+                            }
+                        }
+                        Err(err) => log::debug!("Saving the account number cancelled: {err}"),
+                    }
+                }
+            }
+            AppInput::ConfirmAccountSaved => self.set_new_account(None),
             AppInput::ClearAccountHistory => {
-                let _ = self.daemon_connector.clear_account_history().await;
-                if let Ok(token) = self.daemon_connector.get_account_history().await {
-                    self.set_account_history(token);
+                if let Err(err) = self.daemon_connector.clear_account_history().await {
+                    self.report_error(&tr!("Clearing the account history failed."), &err);
                 }
+                self.update_account_history().await;
             }
             AppInput::SecureMyConnection => {
-                let _ = self.daemon_connector.secure_my_connection().await;
+                if let Err(err) = self.daemon_connector.secure_my_connection().await {
+                    self.report_error(&tr!("Connecting failed."), &err);
+                }
             }
             AppInput::Reconnect => {
-                let _ = self.daemon_connector.reconnect().await;
+                if let Err(err) = self.daemon_connector.reconnect().await {
+                    self.report_error(&tr!("Reconnecting failed."), &err);
+                }
             }
             AppInput::CancelConnection | AppInput::Disconnect => {
-                let _ = self.daemon_connector.disconnect().await;
+                if let Err(err) = self.daemon_connector.disconnect().await {
+                    self.report_error(&tr!("Disconnecting failed."), &err);
+                }
+            }
+            AppInput::VerifyConnection => {
+                self.set_checking_connection(true);
+                self.set_connection_check(Some(tr!("Checking...")));
+                self.set_connection_warning(None);
+
+                let config = self.config.connection_check.clone();
+                let tunnel_out = self
+                    .get_tunnel_state()
+                    .as_ref()
+                    .and_then(|ts| ts.get_tunnel_out());
+                sender.oneshot_command(async move {
+                    let result = connection_check::check_connection(&config, tunnel_out)
+                        .await
+                        .map_err(|err| {
+                            log::debug!("Connection check error: {err:#?}");
+                            tr!("Connection check failed: {}", err)
+                        });
+                    AppMsg::ConnectionChecked(result)
+                });
+            }
+            AppInput::FetchDevices => self.fetch_devices(sender),
+            AppInput::ShowVoucherDialog => {
+                if let Some(components) = self.get_components() {
+                    let parent: &gtk::Window = root.as_ref();
+                    components
+                        .voucher
+                        .emit(VoucherDialogMsg::Open(parent.clone().upcast()));
+                }
+            }
+            AppInput::RetryDaemonConnection => self.daemon_connector.retry_now(),
+            AppInput::StartDaemonService | AppInput::EnableDaemonService => {
+                let bus_address = self.config.systemd_bus_address.clone();
+                let enable = matches!(message, AppInput::EnableDaemonService);
+                sender.oneshot_command(async move {
+                    let client = match SystemdClient::connect(bus_address.as_deref()).await {
+                        Ok(client) => client,
+                        Err(error) => {
+                            return AppMsg::Failed {
+                                action: tr!("Connecting to systemd failed."),
+                                error,
+                            }
+                        }
+                    };
+                    let result = if enable {
+                        client.enable_service().await
+                    } else {
+                        client.start_service().await
+                    };
+                    match result {
+                        Ok(()) => AppMsg::Ignore,
+                        Err(error) => AppMsg::Failed {
+                            action: tr!("Changing the service failed."),
+                            error,
+                        },
+                    }
+                });
+            }
+            AppInput::ManageAccount => {
+                let url = match self.daemon_connector.get_www_auth_token().await {
+                    Ok(token) => match add_token_to_url(&self.config.account_url, &token) {
+                        Ok(url) => url,
+                        Err(err) => {
+                            log::warn!("Can't add the token to the account page URL: {err}");
+                            self.config.account_url.clone()
+                        }
+                    },
+                    Err(err) => {
+                        // The page still opens, it just asks for the account number.
+                        self.report_error(&tr!("Signing in to the account page failed."), &err);
+                        self.config.account_url.clone()
+                    }
+                };
+
+                let parent: &gtk::Window = root.as_ref();
+                gtk::UriLauncher::new(&url).launch(
+                    Some(parent),
+                    None::<&gtk::gio::Cancellable>,
+                    |result| {
+                        if let Err(err) = result {
+                            log::warn!("Can't open the account page: {err}");
+                        }
+                    },
+                );
+            }
+            AppInput::RedeemVoucher(voucher) => {
+                let daemon_connector = self.daemon_connector.clone();
+                sender.oneshot_command(async move {
+                    let result = daemon_connector
+                        .submit_voucher(voucher)
+                        .await
+                        .map_err(|err| {
+                            log::debug!("Voucher error: {err:#?}");
+                            DaemonError::from_error(&err).get_message()
+                        });
+                    AppMsg::VoucherRedeemed(result)
+                });
+            }
+            AppInput::RemoveDevice(device_id) => {
+                if let Some(account_token) = self.get_account_token() {
+                    // The list is refreshed by the `RemoveDevice` daemon event.
+                    if let Err(err) = self
+                        .daemon_connector
+                        .remove_device(account_token, device_id)
+                        .await
+                    {
+                        self.report_error(&tr!("Removing the device failed."), &err);
+                    }
+                }
             }
             AppInput::Account => {
                 if let Some(components) = self.get_components() {
@@ -642,30 +1769,89 @@ impl AsyncComponent for AppModel {
                     components.preferences.emit(PreferencesMsg::Show);
                 }
             }
-            AppInput::Set(pref) => match pref {
-                Pref::AutoConnect(value) => {
-                    self.daemon_connector.set_auto_connect(value).await.ok();
+            AppInput::Schedule => {
+                if let Some(components) = self.get_components() {
+                    components.schedule.emit(ScheduleMsg::Show);
                 }
-                Pref::LocalNetworkSharing(value) => {
-                    self.daemon_connector.set_allow_lan(value).await.ok();
+            }
+            AppInput::History => {
+                if let Some(components) = self.get_components() {
+                    components.history.emit(HistoryMsg::Show);
                 }
-                Pref::LockdownMode(value) => {
-                    self.daemon_connector
-                        .set_block_when_disconnected(value)
-                        .await
-                        .ok();
+            }
+            AppInput::SetSchedule(schedule) => {
+                self.config.schedule = schedule.clone();
+                if let Err(err) = self.config.save() {
+                    log::warn!("Can't save config: {err}");
                 }
-                Pref::EnableIPv6(value) => {
-                    self.daemon_connector.set_enable_ipv6(value).await.ok();
+
+                if let Some(schedule_sender) = &self.schedule_sender {
+                    schedule_sender.send_replace(schedule.clone());
                 }
-                Pref::RelaySettings(relay_settings) => {
-                    self.daemon_connector
-                        .set_relay_settings(*relay_settings)
-                        .await
-                        .ok();
+
+                if let Some(components) = self.get_components() {
+                    components
+                        .schedule
+                        .emit(ScheduleMsg::UpdateSchedule(schedule));
                 }
-            },
-            AppInput::About => about::show_about_dialog(&**root),
+            }
+            AppInput::SetDaemonSocketPath(socket_path) => {
+                self.config.daemon_socket_path = socket_path.clone();
+                if let Err(err) = self.config.save() {
+                    log::warn!("Can't save config: {err}");
+                }
+
+                // The command line wins until the next start.
+                if self.daemon_socket_override.is_none() {
+                    self.daemon_connector.set_socket_path(socket_path.clone());
+                    self.set_daemon_socket_path(socket_path);
+                }
+            }
+            AppInput::Set(pref) => {
+                let result = match pref {
+                    Pref::AutoConnect(value) => self.daemon_connector.set_auto_connect(value).await,
+                    Pref::LocalNetworkSharing(value) => {
+                        self.daemon_connector.set_allow_lan(value).await
+                    }
+                    Pref::LockdownMode(value) => {
+                        self.daemon_connector
+                            .set_block_when_disconnected(value)
+                            .await
+                    }
+                    Pref::EnableIPv6(value) => self.daemon_connector.set_enable_ipv6(value).await,
+                    Pref::RelaySettings(relay_settings) => {
+                        self.daemon_connector
+                            .set_relay_settings(*relay_settings)
+                            .await
+                    }
+                };
+
+                if let Err(err) = result {
+                    log::warn!("Can't change a setting: {err:#}");
+                    // Put the preferences back to what the daemon has.
+                    let settings = self.daemon_connector.get_settings().await;
+                    if let Some(components) = self.get_components() {
+                        components.preferences.emit(PreferencesMsg::ShowError(
+                            tr!("Changing the setting failed."),
+                            DaemonError::from_error(&err),
+                        ));
+                        if let Ok(settings) = settings {
+                            components
+                                .preferences
+                                .emit(PreferencesMsg::UpdateSettings(settings));
+                        }
+                    }
+                }
+            }
+            AppInput::About => {
+                about::show_about_dialog(&**root, self.get_daemon_version().as_deref())
+            }
+            AppInput::DismissVersionNotice => {
+                if let Some(notice) = self.get_version_notice().clone() {
+                    self.dismissed_version_notices.push(notice);
+                    self.set_version_notice(None);
+                }
+            }
         }
     }
 
@@ -673,40 +1859,102 @@ impl AsyncComponent for AppModel {
         &mut self,
         message: Self::CommandOutput,
         sender: AsyncComponentSender<Self>,
-        _root: &Self::Root,
+        root: &Self::Root,
     ) {
+        self.reset();
+
         match message {
             AppMsg::Ignore => {}
+            AppMsg::Tick => {
+                self.update_traffic();
+                self.update_session_duration();
+                self.update_time_left();
+                self.notify_expiry();
+                self.update_daemon_retry_label();
+
+                if self.is_logged_in()
+                    && self.is_account_expired()
+                    && self
+                        .account_data_fetched
+                        .is_none_or(|fetched| fetched.elapsed() >= OUT_OF_TIME_REFRESH_INTERVAL)
+                {
+                    self.fetch_account_data(sender.clone());
+                }
+            }
             AppMsg::DaemonEvent(event) => {
                 log::debug!("Daemon event: {:#?}", event);
                 match event {
                     Event::TunnelState(new_tunnel_state) => {
+                        if !new_tunnel_state.is_connected() {
+                            self.traffic_stats.borrow_mut().reset();
+                        }
+                        self.set_daemon_connection_failure(None);
+                        self.update_session(&new_tunnel_state);
                         self.set_tunnel_state(Some(new_tunnel_state));
                         self.fetch_account_data(sender.clone());
                     }
-                    Event::ConnectingToDaemon => self.set_state(AppState::ConnectingToDaemon),
-                    Event::Device(device_event) => match device_event.new_state {
-                        DeviceState::LoggedIn(account_and_device) => {
-                            self.set_state(AppState::LoggedIn(account_and_device.clone()));
-
-                            if let Some(components) = self.get_components() {
-                                components
-                                    .account
-                                    .emit(AccountMsg::UpdateAccountAndDevice(account_and_device));
-                            }
-                            self.fetch_account_data(sender.clone());
+                    Event::ConnectingToDaemon => {
+                        self.set_state(AppState::ConnectingToDaemon);
+                        // The daemon may come back updated.
+                        self.set_daemon_version(None);
+                        self.update_version_notice();
+                        self.daemon_retry_at = None;
+                        self.update_daemon_retry_label();
+                    }
+                    Event::DaemonConnectionFailed { failure, retry_in } => {
+                        self.set_daemon_connection_failure(Some(failure));
+                        self.daemon_retry_at = Some(Instant::now() + retry_in);
+                        self.update_daemon_retry_label();
+                    }
+                    Event::Device(device_event) => {
+                        // The account may have changed outside of this app. Updates, like
+                        // the ones replayed on every (re)subscribe, leave the account alone.
+                        if !matches!(device_event.cause, DeviceEventCause::Updated) {
+                            self.daemon_connector.invalidate_account_data();
                         }
-                        // TODO: process `revoked` state.
-                        DeviceState::LoggedOut | DeviceState::Revoked => {
-                            self.set_state(AppState::Login(LoginState::Normal));
-                            if let Ok(token) = self.daemon_connector.get_account_history().await {
-                                self.set_account_history(token);
+
+                        match device_event.new_state {
+                            DeviceState::LoggedIn(account_and_device) => {
+                                self.set_state(AppState::LoggedIn(account_and_device.clone()));
+
+                                if let Some(components) = self.get_components() {
+                                    components.account.emit(AccountMsg::UpdateAccountAndDevice(
+                                        account_and_device,
+                                    ));
+                                }
+                                self.fetch_account_data(sender.clone());
+                                self.fetch_devices(sender.clone());
+                            }
+                            DeviceState::LoggedOut => {
+                                self.failing_refreshes.clear();
+                                self.set_state(AppState::Login(LoginState::Normal));
+                                self.set_new_account(None);
+                                self.update_account_history().await;
+                            }
+                            DeviceState::Revoked => {
+                                self.set_state(AppState::DeviceRevoked);
+                                self.update_account_history().await;
                             }
                         }
-                    },
-                    Event::RemoveDevice(_) => {}
+                    }
+                    Event::RemoveDevice(remove_device_event) => {
+                        if let Some(components) = self.get_components() {
+                            components
+                                .account
+                                .emit(AccountMsg::UpdateDevices(remove_device_event.new_devices));
+                        }
+                    }
                     Event::AccountData(account_data) => {
-                        self.set_account_data(Some(account_data.clone()));
+                        self.set_account_data(Some(account_data.data.clone()));
+                        self.set_account_data_stale_since(
+                            account_data.is_stale.then_some(account_data.updated),
+                        );
+                        // Stale data means the refresh failed, which the label tells.
+                        if account_data.is_stale {
+                            self.failing_refreshes.insert(Refresh::AccountData);
+                        } else {
+                            self.failing_refreshes.remove(&Refresh::AccountData);
+                        }
 
                         if let Some(components) = self.get_components() {
                             components
@@ -717,18 +1965,111 @@ impl AsyncComponent for AppModel {
                     Event::Setting(settings) => {
                         self.set_lockdown_mode(settings.block_when_disconnected);
 
+                        if self.get_daemon_version().is_none() {
+                            let daemon_connector = self.daemon_connector.clone();
+                            sender.oneshot_command(async move {
+                                match daemon_connector.get_current_version().await {
+                                    Ok(version) => AppMsg::DaemonVersion(version),
+                                    Err(err) => {
+                                        log::warn!("Can't get the daemon version: {err:#}");
+                                        AppMsg::Ignore
+                                    }
+                                }
+                            });
+                        }
+
                         if let Some(components) = self.get_components() {
                             components
                                 .preferences
                                 .emit(PreferencesMsg::UpdateSettings(settings));
                         }
                     }
-                    Event::AppVersionInfo(_) => {}
+                    Event::AppVersionInfo(app_version_info) => {
+                        self.app_version_info = Some(app_version_info);
+                        self.update_version_notice();
+                    }
                     Event::RelayList(_) => {}
                     Event::NewAccessMethod(_) => {}
                 };
                 self.update_properties();
             }
+            AppMsg::ConnectionChecked(result) => {
+                self.set_checking_connection(false);
+                match result {
+                    Ok(report) if report.warnings.is_empty() => {
+                        self.set_connection_check(Some(tr!(
+                            "Verified, traffic leaves from {}",
+                            report.exit_ip
+                        )));
+                    }
+                    Ok(report) => {
+                        self.set_connection_check(Some(tr!(
+                            "Mismatch, traffic leaves from {}",
+                            report.exit_ip
+                        )));
+                        self.set_connection_warning(Some(report.warnings.join("\n")));
+                    }
+                    Err(error) => self.set_connection_check(Some(error)),
+                }
+            }
+            AppMsg::VoucherRedeemed(result) => {
+                if result.is_ok() {
+                    self.fetch_account_data(sender.clone());
+                }
+                if let Some(components) = self.get_components() {
+                    components.voucher.emit(match result {
+                        Ok(submission) => VoucherDialogMsg::Redeemed(submission),
+                        Err(error) => VoucherDialogMsg::Failed(error),
+                    });
+                }
+            }
+            AppMsg::Devices(devices) => {
+                self.failing_refreshes.remove(&Refresh::Devices);
+                if let Some(components) = self.get_components() {
+                    components.account.emit(AccountMsg::UpdateDevices(devices));
+                }
+            }
+            AppMsg::ScheduledChange(change) => {
+                self.set_scheduled_change(change.map(|change| {
+                    let time = if change.at.date_naive() == Local::now().date_naive() {
+                        change.at.format("%H:%M")
+                    } else {
+                        change.at.format("%a %H:%M")
+                    };
+                    if change.connect {
+                        tr!("Scheduled to connect at {}", time)
+                    } else {
+                        tr!("Scheduled to disconnect at {}", time)
+                    }
+                }));
+            }
+            AppMsg::TooManyDevices(account, devices) => {
+                self.set_state(AppState::Login(LoginState::Normal));
+
+                if let Some(components) = self.get_components() {
+                    let parent: &gtk::Window = root.as_ref();
+                    components.too_many_devices.emit(TooManyDevicesMsg::Open {
+                        account,
+                        devices,
+                        parent: parent.clone().upcast(),
+                    });
+                }
+            }
+            AppMsg::DaemonServiceStatus(status) => self.set_daemon_service_status(Some(status)),
+            AppMsg::DaemonVersion(version) => {
+                self.set_daemon_version(Some(version));
+                self.update_version_notice();
+            }
+            AppMsg::ConnectionHealth(health) => self.set_daemon_health(health),
+            AppMsg::Failed { action, error } => self.report_error(&action, &error),
+            AppMsg::RefreshFailed(refresh, error) => {
+                if self.failing_refreshes.insert(refresh) {
+                    self.report_error(&refresh.get_failed_action(), &error);
+                } else {
+                    log::debug!("{refresh:?} is still failing: {error:#}");
+                }
+            }
+            AppMsg::AccountCreated(account_number) => self.set_new_account(Some(account_number)),
             AppMsg::LoginError(error) | AppMsg::CreateAccountError(error) => {
                 self.set_banner_label(Some(error));
                 self.set_state(AppState::Login(LoginState::Normal));
@@ -737,12 +2078,12 @@ impl AsyncComponent for AppModel {
     }
 }
 
-async fn listen_to_mullvad_events(out: relm4::Sender<AppMsg>) {
-    let mut events_rx = mullvad::events_receiver();
+async fn listen_to_mullvad_events(out: relm4::Sender<AppMsg>, daemon_connector: DaemonConnector) {
+    let mut events = daemon_connector.subscribe();
 
     log::trace!("Listening for status updates...");
 
-    while let Some(event) = events_rx.recv().await {
+    while let Some(event) = events.recv().await {
         if let Err(msg) = out.send(AppMsg::DaemonEvent(event)) {
             log::debug!("Can't send an app message {msg:?} because all receivers were dropped");
             break;
@@ -752,9 +2093,109 @@ async fn listen_to_mullvad_events(out: relm4::Sender<AppMsg>) {
     log::trace!("Status updates stopped.");
 }
 
+async fn listen_to_connection_health(
+    out: relm4::Sender<AppMsg>,
+    mut health_receiver: watch::Receiver<ConnectionHealth>,
+) {
+    loop {
+        let health = health_receiver.borrow_and_update().clone();
+        if out.send(AppMsg::ConnectionHealth(health)).is_err()
+            || health_receiver.changed().await.is_err()
+        {
+            break;
+        }
+    }
+}
+
+/// Turns a login result into a message. If the account has too many devices,
+/// they are listed so the user can remove some and retry.
+async fn process_login_result(
+    login_result: anyhow::Result<()>,
+    account_token: AccountNumber,
+    daemon_connector: DaemonConnector,
+) -> AppMsg {
+    let Err(err) = login_result else {
+        return AppMsg::Ignore;
+    };
+
+    log::debug!("Login error: {:#?}", err);
+    match DaemonError::from_error(&err) {
+        DaemonError::InvalidAccount => {
+            AppMsg::LoginError(tr!("Login failed. Invalid account number."))
+        }
+        DaemonError::TooManyDevices => {
+            match daemon_connector.list_devices(account_token.clone()).await {
+                Ok(devices) => AppMsg::TooManyDevices(account_token, devices),
+                Err(err) => {
+                    log::debug!("Can't list devices: {err:#?}");
+                    AppMsg::LoginError(tr!("Login failed. Too many devices."))
+                }
+            }
+        }
+        error => AppMsg::LoginError(tr!("Login failed. {}", error.get_message())),
+    }
+}
+
+async fn tick(out: relm4::Sender<AppMsg>) {
+    let mut interval = tokio::time::interval(Duration::from_secs(1));
+
+    loop {
+        interval.tick().await;
+        if out.send(AppMsg::Tick).is_err() {
+            break;
+        }
+    }
+}
+
+async fn listen_to_schedule(
+    out: relm4::Sender<AppMsg>,
+    schedule_receiver: watch::Receiver<Schedule>,
+    daemon_connector: DaemonConnector,
+) {
+    let mut changes_rx = schedule::scheduler(schedule_receiver, daemon_connector);
+
+    while let Some(change) = changes_rx.recv().await {
+        if out.send(AppMsg::ScheduledChange(change)).is_err() {
+            break;
+        }
+    }
+}
+
+/// Follows the daemon unit, silently gives up when systemd can't be reached.
+async fn listen_to_systemd(out: relm4::Sender<AppMsg>, bus_address: Option<String>) {
+    let client = match SystemdClient::connect(bus_address.as_deref()).await {
+        Ok(client) => client,
+        Err(err) => {
+            log::info!("systemd is not available: {err:#}");
+            return;
+        }
+    };
+
+    let (sender, mut receiver) = tokio::sync::mpsc::channel(4);
+    let watch = client.watch(sender);
+    let forward = async {
+        while let Some(status) = receiver.recv().await {
+            if out.send(AppMsg::DaemonServiceStatus(status)).is_err() {
+                break;
+            }
+        }
+    };
+
+    tokio::select! {
+        result = watch => {
+            if let Err(err) = result {
+                log::info!("Can't watch {}: {err:#}", crate::systemd::DAEMON_UNIT);
+            }
+        }
+        () = forward => {}
+    }
+}
+
 relm4::new_action_group!(WindowActionGroup, "win");
 relm4::new_stateless_action!(AccountAction, WindowActionGroup, "account");
 relm4::new_stateless_action!(PreferencesAction, WindowActionGroup, "preferences");
+relm4::new_stateless_action!(ScheduleAction, WindowActionGroup, "schedule");
+relm4::new_stateless_action!(HistoryAction, WindowActionGroup, "history");
 relm4::new_stateless_action!(AboutAction, WindowActionGroup, "about");
 
 impl Clone for AccountAction {
@@ -762,3 +2203,176 @@ impl Clone for AccountAction {
         Self {}
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use talpid_types::{
+        net::wireguard::PrivateKey,
+        tunnel::{ErrorState, ErrorStateCause},
+    };
+
+    use super::*;
+    use crate::mullvad::{MockDaemon, MockState, MAX_DEVICES};
+    use crate::ui::too_many_devices;
+
+    const ACCOUNT: &str = "1234123412341234";
+
+    fn get_device(id: &str) -> Device {
+        Device {
+            id: id.to_string(),
+            name: id.to_string(),
+            pubkey: PrivateKey::new_from_random().public_key(),
+            hijack_dns: false,
+            created: Utc::now(),
+        }
+    }
+
+    /// A mock daemon whose account already has the most devices it can have.
+    fn get_full_account(unremovable_devices: &[&str]) -> (MockDaemon, DaemonConnector) {
+        let daemon = MockDaemon::new(MockState {
+            devices: (0..MAX_DEVICES)
+                .map(|i| get_device(&format!("device-{i}")))
+                .collect(),
+            unremovable_devices: unremovable_devices
+                .iter()
+                .map(|id| id.to_string())
+                .collect(),
+            ..Default::default()
+        });
+        let daemon_connector = DaemonConnector::new(Arc::new(daemon.clone()));
+        (daemon, daemon_connector)
+    }
+
+    fn get_ids(devices: &[Device]) -> Vec<&str> {
+        devices.iter().map(|device| device.id.as_str()).collect()
+    }
+
+    async fn login(daemon_connector: &DaemonConnector) -> AppMsg {
+        let login_result = daemon_connector.login_account(ACCOUNT.to_string()).await;
+        process_login_result(login_result, ACCOUNT.to_string(), daemon_connector.clone()).await
+    }
+
+    async fn remove_devices_and_login(
+        daemon_connector: &DaemonConnector,
+        device_ids: &[&str],
+    ) -> AppMsg {
+        let login_result = daemon_connector
+            .remove_devices_and_login(
+                ACCOUNT.to_string(),
+                device_ids.iter().map(|id| id.to_string()).collect(),
+            )
+            .await;
+        process_login_result(login_result, ACCOUNT.to_string(), daemon_connector.clone()).await
+    }
+
+    #[test]
+    fn token_is_added_to_the_account_url() {
+        let cases = [
+            (
+                "https://mullvad.net/account",
+                "https://mullvad.net/account?token=abc",
+            ),
+            (
+                "https://mullvad.net/account?",
+                "https://mullvad.net/account?token=abc",
+            ),
+            (
+                "https://mullvad.net/account?lang=sv",
+                "https://mullvad.net/account?lang=sv&token=abc",
+            ),
+            (
+                "https://mullvad.net/account?lang=sv#top",
+                "https://mullvad.net/account?lang=sv&token=abc#top",
+            ),
+        ];
+        for (url, expected) in cases {
+            assert_eq!(add_token_to_url(url, "abc").unwrap(), expected);
+        }
+
+        assert_eq!(
+            add_token_to_url("https://mullvad.net/account", "a+b/c=&d").unwrap(),
+            "https://mullvad.net/account?token=a%2Bb%2Fc%3D%26d"
+        );
+        assert!(add_token_to_url("not a url", "abc").is_err());
+    }
+
+    #[test]
+    fn errors_end_the_session() {
+        let connected = crate::mullvad::get_connect_sequence()
+            .pop()
+            .expect("connected state");
+        let error = TunnelState::Error(ErrorState::new(ErrorStateCause::IsOffline, None));
+        let mut model = AppModel::default();
+        let change_tunnel_state = |model: &mut AppModel, tunnel_state: &TunnelState| {
+            model.update_session(tunnel_state);
+            model.set_tunnel_state(Some(tunnel_state.clone()));
+        };
+
+        change_tunnel_state(&mut model, &connected);
+        assert!(model.get_session_duration().is_some());
+
+        change_tunnel_state(&mut model, &error);
+        assert!(model.session.is_none());
+        assert!(model.get_session_duration().is_none());
+
+        // Getting out of the error counts as a reconnect.
+        change_tunnel_state(&mut model, &connected);
+        assert!(model.get_session_duration().is_some());
+        assert!(model.get_last_reconnect().is_some());
+    }
+
+    #[tokio::test]
+    async fn full_account_lists_devices_to_remove() {
+        let (daemon, daemon_connector) = get_full_account(&[]);
+
+        let AppMsg::TooManyDevices(account, devices) = login(&daemon_connector).await else {
+            panic!("the login should ask which devices to remove");
+        };
+        assert_eq!(account, ACCOUNT);
+        assert_eq!(get_ids(&devices), get_ids(&daemon.get_state().devices));
+        // The dialog lets the login be retried once enough devices are selected.
+        assert!(!too_many_devices::can_login(devices.len(), 0));
+        assert!(too_many_devices::can_login(devices.len(), 1));
+
+        let message = remove_devices_and_login(&daemon_connector, &["device-1", "device-3"]).await;
+        assert!(matches!(message, AppMsg::Ignore), "{message:?}");
+
+        let state = daemon.get_state();
+        assert!(matches!(state.device_state, DeviceState::LoggedIn(_)));
+        assert_eq!(
+            get_ids(&state.devices),
+            ["device-0", "device-2", "device-4", "mock-device"]
+        );
+    }
+
+    #[tokio::test]
+    async fn login_goes_on_if_enough_devices_were_removed() {
+        let (daemon, daemon_connector) = get_full_account(&["device-1"]);
+
+        let message = remove_devices_and_login(&daemon_connector, &["device-1", "device-3"]).await;
+        assert!(matches!(message, AppMsg::Ignore), "{message:?}");
+
+        let state = daemon.get_state();
+        assert!(matches!(state.device_state, DeviceState::LoggedIn(_)));
+        assert!(get_ids(&state.devices).contains(&"device-1"));
+    }
+
+    #[tokio::test]
+    async fn failed_removal_lists_the_devices_left() {
+        let (daemon, daemon_connector) = get_full_account(&["device-1"]);
+
+        let AppMsg::TooManyDevices(_, devices) =
+            remove_devices_and_login(&daemon_connector, &["device-1"]).await
+        else {
+            panic!("the login should ask again which devices to remove");
+        };
+        assert_eq!(devices.len(), MAX_DEVICES);
+        assert!(get_ids(&devices).contains(&"device-1"));
+        assert!(matches!(
+            daemon.get_state().device_state,
+            DeviceState::LoggedOut
+        ));
+    }
+}
@@ -0,0 +1,82 @@
+use crate::tr;
+
+use adw::prelude::*;
+use relm4::prelude::*;
+
+#[relm4::widget_template(pub)]
+impl WidgetTemplate for ConnectingView {
+    view! {
+        gtk::Box {
+            set_orientation: gtk::Orientation::Vertical,
+            set_margin_all: 20,
+            set_valign: gtk::Align::Center,
+            set_spacing: 12,
+
+            gtk::Label {
+                set_label: &tr!("Connecting to Mullvad system service..."),
+                add_css_class: "title-4",
+                set_wrap: true,
+            },
+
+            // Shown after a failed connection attempt.
+            #[name = "failure_box"]
+            gtk::Box {
+                set_orientation: gtk::Orientation::Vertical,
+                set_spacing: 12,
+
+                #[name = "failure_label"]
+                gtk::Label {
+                    set_wrap: true,
+                    add_css_class: "error",
+                },
+
+                #[name = "suggestion_label"]
+                gtk::Label {
+                    set_use_markup: true,
+                    set_selectable: true,
+                    set_wrap: true,
+                },
+
+                #[name = "retry_label"]
+                gtk::Label {
+                    add_css_class: "caption",
+                },
+
+                #[name = "retry_button"]
+                gtk::Button {
+                    set_label: &tr!("Retry now"),
+                    set_halign: gtk::Align::Center,
+                },
+            },
+
+            // Shown when systemd can be reached over D-Bus.
+            #[name = "service_box"]
+            gtk::Box {
+                set_orientation: gtk::Orientation::Vertical,
+                set_spacing: 12,
+
+                #[name = "service_label"]
+                gtk::Label {
+                    set_wrap: true,
+                    add_css_class: "caption",
+                },
+
+                gtk::Box {
+                    set_spacing: 12,
+                    set_halign: gtk::Align::Center,
+
+                    #[name = "start_service_button"]
+                    gtk::Button {
+                        set_label: &tr!("Start service"),
+                        add_css_class: "suggested-action",
+                    },
+
+                    #[name = "enable_service_button"]
+                    gtk::Button {
+                        set_label: &tr!("Enable at boot"),
+                    },
+                },
+            },
+        }
+    }
+}
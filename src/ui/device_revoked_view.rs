@@ -0,0 +1,49 @@
+use crate::tr;
+
+use adw::prelude::*;
+use relm4::prelude::*;
+
+#[relm4::widget_template(pub)]
+impl WidgetTemplate for DeviceRevokedView {
+    view! {
+        gtk::Box {
+            set_orientation: gtk::Orientation::Vertical,
+            set_margin_all: 20,
+            set_valign: gtk::Align::Center,
+            set_spacing: 12,
+
+            gtk::Label {
+                set_label: &tr!("This device was removed"),
+                add_css_class: "title-1",
+                set_wrap: true,
+                set_halign: gtk::Align::Start,
+            },
+
+            gtk::Label {
+                set_label: &tr!("This device was removed from your account, either from another device or on the Mullvad website. It can't connect to Mullvad VPN until it is logged in again."),
+                set_wrap: true,
+                set_halign: gtk::Align::Start,
+            },
+
+            #[name = "blocking_label"]
+            gtk::Label {
+                set_use_markup: true,
+                set_wrap: true,
+                set_halign: gtk::Align::Start,
+                set_margin_bottom: 8,
+            },
+
+            #[name = "login_again_button"]
+            gtk::Button {
+                set_label: &tr!("Log in again"),
+                set_css_classes: &["opaque", "login_btn"],
+            },
+
+            #[name = "logout_and_unblock_button"]
+            gtk::Button {
+                set_label: &tr!("Log out and unblock"),
+                set_css_classes: &["opaque", "logout_btn"],
+            },
+        }
+    }
+}
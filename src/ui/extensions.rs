@@ -1,3 +1,5 @@
+use std::{cell::Cell, rc::Rc};
+
 use gtk::{
     glib::{object::IsA, SignalHandlerId},
     prelude::{EntryExt as _, *},
@@ -5,20 +7,25 @@ use gtk::{
 };
 use relm4::prelude::*;
 
-pub trait EntryExt: IsA<Entry> + 'static {
+use crate::extensions::CodeFormatExt;
+
+pub trait EntryExt: IsA<Editable> + 'static {
     fn connect_delegate_insert_text<F>(&self, f: F) -> SignalHandlerId
     where
         F: Fn(&Self, &Editable, &str, &mut i32) + 'static,
     {
         let entry = self.clone();
-        let delegate = self.as_ref().delegate().expect("delegate");
+        let delegate = self.upcast_ref::<Editable>().delegate().expect("delegate");
 
         delegate.connect_insert_text(move |delegate, text, position| {
             f(&entry, delegate, text, position);
         })
     }
 
-    fn enable_input_purpose_behavior(&self) -> SignalHandlerId {
+    fn enable_input_purpose_behavior(&self) -> SignalHandlerId
+    where
+        Self: IsA<Entry>,
+    {
         self.connect_delegate_insert_text(|this, delegate, text, _position| {
             if this.input_purpose() == gtk::InputPurpose::Digits
                 && text.chars().any(|c| !c.is_ascii_digit())
@@ -27,6 +34,90 @@ pub trait EntryExt: IsA<Entry> + 'static {
             }
         })
     }
+
+    /// Keeps at most `max_len` characters `normalize` lets through, shown in
+    /// groups of four, so pasted codes with spaces or dashes end up the same
+    /// as typed ones. The caret stays behind the character it was behind.
+    ///
+    /// `on_code` gets the code without separators after every edit.
+    fn enable_grouped_code_behavior<N, F>(
+        &self,
+        max_len: usize,
+        separator: char,
+        normalize: N,
+        on_code: F,
+    ) where
+        N: Fn(char) -> Option<char> + 'static,
+        F: Fn(&str) + 'static,
+    {
+        let delegate = self.upcast_ref::<Editable>().delegate().expect("delegate");
+        let formatting = Rc::new(Cell::new(false));
+
+        // Replaces the edit with the regrouped text, returning the new caret.
+        let regroup = Rc::new({
+            let formatting = formatting.clone();
+            move |delegate: &Editable, text: String, position: usize| {
+                let regrouped = text.regroup_by_four(position, max_len, separator, &normalize);
+                formatting.set(true);
+                delegate.set_text(&regrouped.text);
+                formatting.set(false);
+                on_code(&regrouped.code);
+                regrouped.position as i32
+            }
+        });
+
+        delegate.connect_insert_text({
+            let formatting = formatting.clone();
+            let regroup = regroup.clone();
+            move |delegate, inserted, position| {
+                if formatting.get() {
+                    return;
+                }
+                let mut text: Vec<char> = delegate.text().chars().collect();
+                let at = (*position as usize).min(text.len());
+                text.splice(at..at, inserted.chars());
+
+                delegate.stop_signal_emission_by_name("insert-text");
+                // The caller moves the caret to `position` after inserting.
+                *position = regroup(
+                    delegate,
+                    text.into_iter().collect(),
+                    at + inserted.chars().count(),
+                );
+            }
+        });
+
+        delegate.connect_delete_text(move |delegate, start, end| {
+            if formatting.get() {
+                return;
+            }
+            let mut text: Vec<char> = delegate.text().chars().collect();
+            let start = (start.max(0) as usize).min(text.len());
+            let end = if end < 0 {
+                text.len()
+            } else {
+                (end as usize).clamp(start, text.len())
+            };
+            text.drain(start..end);
+
+            delegate.stop_signal_emission_by_name("delete-text");
+            let position = regroup(delegate, text.into_iter().collect(), start);
+            delegate.set_position(position);
+        });
+    }
+
+    /// `enable_grouped_code_behavior` for numbers, like account numbers.
+    fn enable_grouped_digits_behavior<F>(&self, max_digits: usize, on_digits: F)
+    where
+        F: Fn(&str) + 'static,
+    {
+        self.enable_grouped_code_behavior(
+            max_digits,
+            ' ',
+            |c| c.is_ascii_digit().then_some(c),
+            on_digits,
+        );
+    }
 }
 
-impl<O: IsA<Entry>> EntryExt for O {}
+impl<O: IsA<Editable>> EntryExt for O {}
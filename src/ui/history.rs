@@ -0,0 +1,334 @@
+use adw::prelude::*;
+use chrono::Local;
+use relm4::prelude::*;
+
+use crate::{
+    extensions::DurationExt,
+    history::{self, HistoryEntry, HistoryLog, Session, SessionEnd},
+    tr,
+};
+
+/// Values of the state filter drop down, `None` meaning every state.
+const STATE_FILTERS: [Option<&str>; 6] = [
+    None,
+    Some("connected"),
+    Some("connecting"),
+    Some("disconnected"),
+    Some("disconnecting"),
+    Some("error"),
+];
+
+/// How many more rows each list shows at a time.
+const PAGE_SIZE: usize = 50;
+
+#[derive(Debug)]
+pub struct HistoryModel {
+    window: adw::PreferencesWindow,
+    sessions_list: gtk::ListBox,
+    events_list: gtk::ListBox,
+    more_sessions_button: gtk::Button,
+    more_events_button: gtk::Button,
+
+    history_log: HistoryLog,
+    entries: Vec<HistoryEntry>,
+    state_filter: Option<&'static str>,
+    text_filter: String,
+    sessions_shown: usize,
+    events_shown: usize,
+}
+
+#[derive(Debug)]
+pub enum HistoryMsg {
+    Show,
+    Close,
+    SetStateFilter(u32),
+    SetTextFilter(String),
+    ShowMoreSessions,
+    ShowMoreEvents,
+    Export,
+}
+
+fn format_local_time(time: &chrono::DateTime<chrono::Utc>) -> String {
+    time.with_timezone(&Local)
+        .format("%Y-%m-%d %H:%M:%S")
+        .to_string()
+}
+
+impl HistoryModel {
+    fn get_filtered_entries(&self) -> Vec<HistoryEntry> {
+        self.entries
+            .iter()
+            .filter(|entry| self.state_filter.is_none_or(|state| entry.state == state))
+            .filter(|entry| self.text_filter.is_empty() || entry.matches(&self.text_filter))
+            .cloned()
+            .collect()
+    }
+
+    /// Connected sessions matching the filters, newest first.
+    fn get_filtered_sessions(&self) -> Vec<Session> {
+        if self.state_filter.is_some_and(|state| state != "connected") {
+            return vec![];
+        }
+
+        let mut sessions = history::get_sessions(&self.entries);
+        sessions.retain(|session| {
+            self.text_filter.is_empty() || session.entry.matches(&self.text_filter)
+        });
+        sessions.reverse();
+        sessions
+    }
+
+    fn reset_pages(&mut self) {
+        self.sessions_shown = PAGE_SIZE;
+        self.events_shown = PAGE_SIZE;
+    }
+
+    /// Only the shown pages get rows, the history can have thousands of entries.
+    fn render(&self) {
+        self.sessions_list.remove_all();
+        self.events_list.remove_all();
+
+        let sessions = self.get_filtered_sessions();
+        for session in sessions.iter().take(self.sessions_shown) {
+            let start = format_local_time(&session.start);
+            let duration = session
+                .get_duration()
+                .map(|duration| duration.to_human_string());
+            let subtitle = match (session.end, duration) {
+                (SessionEnd::Ongoing, Some(duration)) => {
+                    tr!("{} · {} (ongoing)", start, duration)
+                }
+                (_, Some(duration)) => format!("{start} · {duration}"),
+                (_, None) => tr!("{} · end unknown, the app was closed", start),
+            };
+
+            relm4::view! {
+                #[name = "action_row"]
+                adw::ActionRow {
+                    set_title: session.entry.hostname.as_deref().unwrap_or_default(),
+                    set_subtitle: &subtitle,
+                    add_css_class: "property",
+                }
+            }
+            self.sessions_list.append(&action_row);
+        }
+        self.sessions_list.set_visible(!sessions.is_empty());
+        self.more_sessions_button
+            .set_visible(sessions.len() > self.sessions_shown);
+
+        let entries = self.get_filtered_entries();
+        for entry in entries.iter().rev().take(self.events_shown) {
+            let mut details = vec![format_local_time(&entry.timestamp)];
+            details.extend(entry.get_location());
+            details.extend(entry.protocol.clone());
+            if let Some(ms) = entry.time_to_connect_ms {
+                details.push(tr!("connected in {:.1} s", ms as f64 / 1000.0));
+            }
+            details.extend(entry.error_cause.clone());
+
+            relm4::view! {
+                #[name = "action_row"]
+                adw::ActionRow {
+                    set_title: &entry.state,
+                    set_subtitle: &details.join(" · "),
+                    set_subtitle_selectable: true,
+                }
+            }
+            self.events_list.append(&action_row);
+        }
+        self.events_list.set_visible(!entries.is_empty());
+        self.more_events_button
+            .set_visible(entries.len() > self.events_shown);
+    }
+
+    async fn export(&self) {
+        let dialog = gtk::FileDialog::builder()
+            .title(tr!("Export history"))
+            .initial_name("mullvadwaita-history.csv")
+            .modal(true)
+            .build();
+
+        let file = match dialog.save_future(Some(&self.window)).await {
+            Ok(file) => file,
+            Err(err) => {
+                log::debug!("History export cancelled: {err}");
+                return;
+            }
+        };
+
+        let csv = history::to_csv(&self.get_filtered_entries());
+        // Written asynchronously, so the main loop keeps running.
+        if let Err((_, err)) = file
+            .replace_contents_future(
+                csv,
+                None,
+                false,
+                gtk::gio::FileCreateFlags::REPLACE_DESTINATION,
+            )
+            .await
+        {
+            log::warn!("Can't export history to {}: {err}", file.uri());
+        }
+    }
+}
+
+#[relm4::component(async, pub)]
+impl SimpleAsyncComponent for HistoryModel {
+    type Init = HistoryLog;
+    type Input = HistoryMsg;
+    type Output = ();
+    type Widgets = HistoryWidgets;
+
+    view! {
+        adw::PreferencesWindow {
+            set_title: Some(&tr!("History")),
+            set_search_enabled: false,
+            connect_close_request[sender] => move |_| {
+                sender.input(HistoryMsg::Close);
+                gtk::glib::Propagation::Stop
+            },
+            add = &adw::PreferencesPage {
+                add = &adw::PreferencesGroup {
+                    add = &gtk::SearchEntry {
+                        set_placeholder_text: Some(&tr!("Filter by relay, location or error")),
+                        set_margin_bottom: 6,
+
+                        connect_search_changed[sender] => move |this| {
+                            sender.input(HistoryMsg::SetTextFilter(this.text().into()));
+                        },
+                    },
+
+                    add = &adw::ComboRow {
+                        set_title: &tr!("State"),
+                        set_model: Some(&gtk::StringList::new(&[
+                            &*tr!("All"),
+                            &*tr!("Connected"),
+                            &*tr!("Connecting"),
+                            &*tr!("Disconnected"),
+                            &*tr!("Disconnecting"),
+                            &*tr!("Error"),
+                        ])),
+
+                        connect_selected_notify[sender] => move |this| {
+                            sender.input(HistoryMsg::SetStateFilter(this.selected()));
+                        },
+                    },
+                },
+
+                add = &adw::PreferencesGroup {
+                    set_title: &tr!("Sessions"),
+
+                    #[local_ref]
+                    add = sessions_list -> gtk::ListBox {
+                        add_css_class: "boxed-list",
+                        set_selection_mode: gtk::SelectionMode::None,
+                    },
+
+                    #[local_ref]
+                    add = more_sessions_button -> gtk::Button {
+                        set_label: &tr!("Show More"),
+                        set_halign: gtk::Align::Center,
+                        set_margin_top: 12,
+                        add_css_class: "pill",
+
+                        connect_clicked[sender] => move |_| {
+                            sender.input(HistoryMsg::ShowMoreSessions);
+                        },
+                    },
+                },
+
+                add = &adw::PreferencesGroup {
+                    set_title: &tr!("Events"),
+
+                    #[wrap(Some)]
+                    set_header_suffix = &gtk::Button {
+                        set_label: &tr!("Export CSV"),
+                        add_css_class: "flat",
+
+                        connect_clicked[sender] => move |_| {
+                            sender.input(HistoryMsg::Export);
+                        },
+                    },
+
+                    #[local_ref]
+                    add = events_list -> gtk::ListBox {
+                        add_css_class: "boxed-list",
+                        set_selection_mode: gtk::SelectionMode::None,
+                    },
+
+                    #[local_ref]
+                    add = more_events_button -> gtk::Button {
+                        set_label: &tr!("Show More"),
+                        set_halign: gtk::Align::Center,
+                        set_margin_top: 12,
+                        add_css_class: "pill",
+
+                        connect_clicked[sender] => move |_| {
+                            sender.input(HistoryMsg::ShowMoreEvents);
+                        },
+                    },
+                },
+            }
+        }
+    }
+
+    async fn init(
+        history_log: Self::Init,
+        root: Self::Root,
+        sender: AsyncComponentSender<Self>,
+    ) -> AsyncComponentParts<Self> {
+        let model = HistoryModel {
+            window: root.clone(),
+            sessions_list: gtk::ListBox::new(),
+            events_list: gtk::ListBox::new(),
+            more_sessions_button: gtk::Button::new(),
+            more_events_button: gtk::Button::new(),
+            history_log,
+            entries: vec![],
+            state_filter: None,
+            text_filter: String::new(),
+            sessions_shown: PAGE_SIZE,
+            events_shown: PAGE_SIZE,
+        };
+
+        let sessions_list = &model.sessions_list;
+        let events_list = &model.events_list;
+        let more_sessions_button = &model.more_sessions_button;
+        let more_events_button = &model.more_events_button;
+
+        let widgets = view_output!();
+
+        AsyncComponentParts { model, widgets }
+    }
+
+    async fn update(&mut self, message: Self::Input, _sender: AsyncComponentSender<Self>) {
+        match message {
+            HistoryMsg::Show => {
+                self.entries = self.history_log.read_all().await;
+                self.reset_pages();
+                self.render();
+                self.window.present();
+            }
+            HistoryMsg::Close => self.window.set_visible(false),
+            HistoryMsg::SetStateFilter(index) => {
+                self.state_filter = STATE_FILTERS.get(index as usize).copied().flatten();
+                self.reset_pages();
+                self.render();
+            }
+            HistoryMsg::SetTextFilter(text) => {
+                self.text_filter = text;
+                self.reset_pages();
+                self.render();
+            }
+            HistoryMsg::ShowMoreSessions => {
+                self.sessions_shown += PAGE_SIZE;
+                self.render();
+            }
+            HistoryMsg::ShowMoreEvents => {
+                self.events_shown += PAGE_SIZE;
+                self.render();
+            }
+            HistoryMsg::Export => self.export().await,
+        }
+    }
+}
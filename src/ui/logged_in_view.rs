@@ -45,6 +45,19 @@ impl WidgetTemplate for LoggedInView {
                 set_halign: gtk::Align::Start
             },
 
+            #[name = "session_duration_label"]
+            gtk::Label {
+                set_css_classes: &["caption"],
+                set_halign: gtk::Align::Start,
+            },
+
+            #[name = "last_reconnect_label"]
+            gtk::Label {
+                set_margin_bottom: 10,
+                set_css_classes: &["caption", "dim-label"],
+                set_halign: gtk::Align::Start,
+            },
+
             #[name = "country_label"]
             gtk::Label {
                 set_margin_bottom: 0,
@@ -88,6 +101,61 @@ impl WidgetTemplate for LoggedInView {
                         set_css_classes: &["property", "monospace"],
                         set_subtitle_selectable: true,
                     },
+
+                    #[name = "connection_check_row"]
+                    add_row = &adw::ActionRow {
+                        set_title: &tr!("Connection check"),
+                        set_css_classes: &["property"],
+                        set_subtitle_selectable: true,
+
+                        #[name = "verify_connection_stack"]
+                        add_suffix = &gtk::Stack {
+                            add_named[Some("checking")] = &gtk::Spinner {
+                                set_spinning: true,
+                            },
+
+                            #[name = "verify_connection_button"]
+                            add_named[Some("default")] = &gtk::Button {
+                                set_label: &tr!("Verify connection"),
+                                set_valign: gtk::Align::Center,
+                            },
+                        },
+                    },
+
+                    #[name = "connection_warning_row"]
+                    add_row = &adw::ActionRow {
+                        set_title: &tr!("Connection mismatch"),
+                        set_css_classes: &["property", "warning"],
+                        set_subtitle_selectable: true,
+                        set_visible: false,
+
+                        add_prefix = &gtk::Image {
+                            set_icon_name: Some(icon_names::WARNING_OUTLINE),
+                        },
+                    },
+
+                    #[name = "traffic_rate_row"]
+                    add_row = &adw::ActionRow {
+                        set_title: &tr!("Traffic"),
+                        set_css_classes: &["property", "monospace"],
+                    },
+
+                    #[name = "traffic_total_row"]
+                    add_row = &adw::ActionRow {
+                        set_title: &tr!("Session total"),
+                        set_css_classes: &["property", "monospace"],
+                    },
+
+                    #[name = "traffic_graph_row"]
+                    add_row = &gtk::ListBoxRow {
+                        set_activatable: false,
+
+                        #[name = "traffic_graph"]
+                        gtk::DrawingArea {
+                            set_content_height: 48,
+                            set_margin_all: 12,
+                        },
+                    },
                 },
             },
 
@@ -125,7 +193,14 @@ impl WidgetTemplate for LoggedInView {
                     set_css_classes: &["opaque", "reconnect_btn"],
                     set_icon_name: icon_names::ARROW_CIRCULAR_TOP_RIGHT,
                 },
-            }
+            },
+
+            #[name = "scheduled_change_label"]
+            gtk::Label {
+                set_css_classes: &["caption", "dim-label"],
+                set_halign: gtk::Align::Center,
+                set_margin_top: 10,
+            },
         }
     }
 }
@@ -4,6 +4,9 @@ use crate::tr;
 use adw::prelude::*;
 use relm4::prelude::*;
 
+/// Number of digits in a Mullvad account number.
+pub const ACCOUNT_NUMBER_LENGTH: usize = 16;
+
 #[relm4::widget_template(pub)]
 impl WidgetTemplate for LoginView {
     view! {
@@ -60,6 +63,7 @@ impl WidgetTemplate for LoginView {
                 #[name = "account_number"]
                 append = &adw::EntryRow {
                     set_title: &tr!("Enter your account number"),
+                    set_input_purpose: gtk::InputPurpose::Digits,
 
                     #[name = "login_button_stack"]
                     add_suffix = &gtk::Stack {
@@ -73,6 +77,7 @@ impl WidgetTemplate for LoginView {
                             set_valign: gtk::Align::Center,
                             set_css_classes: &["opaque", "login_btn"],
                             set_receives_default: true,
+                            set_sensitive: false,
                         },
                     },
                 },
@@ -81,11 +86,6 @@ impl WidgetTemplate for LoginView {
                 adw::ActionRow {
                     set_activatable: true,
 
-                    connect_activated[account_number, login_button] => move |this| {
-                        account_number.set_text(this.title().as_ref());
-                        login_button.emit_clicked();
-                    },
-
                     #[name = "clear_account_history_button"]
                     add_suffix = &gtk::Button {
                         set_icon_name: icon_names::CROSS_LARGE_CIRCLE_FILLED,
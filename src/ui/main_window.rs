@@ -1,10 +1,14 @@
-use crate::{icon_names, tr, ui::logged_in_view::LoggedInView};
+use crate::{icon_names, ui::logged_in_view::LoggedInView};
 
 use adw::prelude::*;
 use gtk::StackTransitionType;
 use relm4::prelude::*;
 
+use super::connecting_view::ConnectingView;
+use super::device_revoked_view::DeviceRevokedView;
 use super::login_view::LoginView;
+use super::new_account_view::NewAccountView;
+use super::out_of_time_view::OutOfTimeView;
 
 #[relm4::widget_template(pub)]
 impl WidgetTemplate for MainWindow {
@@ -20,6 +24,7 @@ impl WidgetTemplate for MainWindow {
                     add_css_class: "flat",
 
                     #[wrap(Some)]
+                    #[name = "window_title"]
                     set_title_widget = &adw::WindowTitle {
                         set_title: "Mullvadwaita",
                         set_subtitle: "for Mullvad VPN",
@@ -29,33 +34,62 @@ impl WidgetTemplate for MainWindow {
                     pack_end = &gtk::MenuButton {
                         set_icon_name: icon_names::MENU_LARGE,
                     },
+
+                    // Health of the daemon connection, hidden while it's fine.
+                    #[name = "health_spinner"]
+                    pack_end = &gtk::Spinner {
+                        set_visible: false,
+                    },
+
+                    #[name = "health_icon"]
+                    pack_end = &gtk::Image {
+                        set_icon_name: Some(icon_names::WARNING_OUTLINE),
+                        add_css_class: "warning",
+                        set_visible: false,
+                    },
                 },
 
                 #[name = "banner"]
                 adw::Banner {},
 
-                adw::Clamp {
-                    set_maximum_size: 600,
+                #[name = "version_banner"]
+                adw::Banner {},
 
-                    #[name = "view_stack"]
-                    gtk::Stack {
-                        set_transition_type: StackTransitionType::SlideLeftRight,
+                #[name = "toast_overlay"]
+                adw::ToastOverlay {
+                    #[wrap(Some)]
+                    set_child = &adw::Clamp {
+                        set_maximum_size: 600,
 
-                        #[template]
-                        #[name = "logged_in_view"]
-                        add_named[Some("logged_in")] = &LoggedInView {},
+                        #[name = "view_stack"]
+                        gtk::Stack {
+                            set_transition_type: StackTransitionType::SlideLeftRight,
 
-                        #[template]
-                        #[name = "login_view"]
-                        add_named[Some("login")] = &LoginView {},
+                            #[template]
+                            #[name = "logged_in_view"]
+                            add_named[Some("logged_in")] = &LoggedInView {},
 
-                        add_named[Some("connecting_to_daemon")] = &gtk::Label {
-                            set_label: &tr!("Connecting to Mullvad system service..."),
-                            set_margin_all: 5,
-                            add_css_class: "title-4",
-                            set_wrap: true
+                            #[template]
+                            #[name = "login_view"]
+                            add_named[Some("login")] = &LoginView {},
+
+                            #[template]
+                            #[name = "device_revoked_view"]
+                            add_named[Some("device_revoked")] = &DeviceRevokedView {},
+
+                            #[template]
+                            #[name = "out_of_time_view"]
+                            add_named[Some("out_of_time")] = &OutOfTimeView {},
+
+                            #[template]
+                            #[name = "new_account_view"]
+                            add_named[Some("new_account")] = &NewAccountView {},
+
+                            #[template]
+                            #[name = "connecting_view"]
+                            add_named[Some("connecting_to_daemon")] = &ConnectingView {},
                         }
-                    }
+                    },
                 }
             }
         }
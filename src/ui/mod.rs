@@ -1,12 +1,22 @@
 pub mod about;
 pub mod account;
 pub mod app;
+pub mod connecting_view;
+pub mod device_revoked_view;
 pub mod entry_dialog;
 pub mod extensions;
+pub mod history;
 pub mod logged_in_view;
 pub mod login_view;
 pub mod main_window;
+pub mod new_account_view;
+pub mod out_of_time_view;
 pub mod preferences;
+pub mod qr_code;
+pub mod schedule;
+pub mod sparkline;
+pub mod too_many_devices;
 pub mod types;
 pub mod variant_selector;
+pub mod voucher_dialog;
 pub mod widgets;
@@ -0,0 +1,92 @@
+use crate::icon_names;
+use crate::tr;
+
+use adw::prelude::*;
+use relm4::prelude::*;
+
+#[relm4::widget_template(pub)]
+impl WidgetTemplate for NewAccountView {
+    view! {
+        gtk::ScrolledWindow {
+            set_hscrollbar_policy: gtk::PolicyType::Never,
+
+            gtk::Box {
+                set_orientation: gtk::Orientation::Vertical,
+                set_margin_all: 20,
+                set_valign: gtk::Align::Center,
+                set_spacing: 12,
+
+                gtk::Label {
+                    set_label: &tr!("Account created"),
+                    add_css_class: "title-1",
+                    set_wrap: true,
+                    set_halign: gtk::Align::Start,
+                },
+
+                gtk::Label {
+                    set_label: &tr!("This is your account number. It is the only thing needed to log in, so save it somewhere safe."),
+                    set_wrap: true,
+                    set_halign: gtk::Align::Start,
+                },
+
+                gtk::Box {
+                    set_spacing: 6,
+
+                    #[name = "account_number_label"]
+                    gtk::Label {
+                        set_css_classes: &["title-2", "monospace"],
+                        set_selectable: true,
+                        set_hexpand: true,
+                        set_halign: gtk::Align::Start,
+                    },
+
+                    #[name = "copy_button"]
+                    gtk::Button {
+                        set_icon_name: icon_names::COPY,
+                        set_valign: gtk::Align::Center,
+                        set_css_classes: &["flat", "image-button"],
+                        set_tooltip_text: Some(&tr!("Copy")),
+                    },
+
+                    #[name = "save_button"]
+                    gtk::Button {
+                        set_label: &tr!("Save to file"),
+                        set_valign: gtk::Align::Center,
+                        add_css_class: "flat",
+                    },
+                },
+
+                #[name = "qr_code"]
+                gtk::DrawingArea {
+                    set_content_width: 160,
+                    set_content_height: 160,
+                    set_halign: gtk::Align::Center,
+                },
+
+                gtk::Label {
+                    set_label: &tr!("<b>The account number can't be recovered.</b> If you lose it, any time left on the account is lost too."),
+                    set_use_markup: true,
+                    set_wrap: true,
+                    set_halign: gtk::Align::Start,
+                    add_css_class: "warning",
+                },
+
+                #[name = "saved_check_button"]
+                gtk::CheckButton {
+                    set_label: Some(&tr!("I have saved my account number")),
+
+                    connect_toggled[continue_button] => move |this| {
+                        continue_button.set_sensitive(this.is_active());
+                    },
+                },
+
+                #[name = "continue_button"]
+                gtk::Button {
+                    set_label: &tr!("Continue"),
+                    set_css_classes: &["opaque", "login_btn"],
+                    set_sensitive: false,
+                },
+            },
+        }
+    }
+}
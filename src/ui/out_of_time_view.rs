@@ -0,0 +1,54 @@
+use crate::tr;
+
+use adw::prelude::*;
+use relm4::prelude::*;
+
+#[relm4::widget_template(pub)]
+impl WidgetTemplate for OutOfTimeView {
+    view! {
+        gtk::Box {
+            set_orientation: gtk::Orientation::Vertical,
+            set_margin_all: 20,
+            set_valign: gtk::Align::Center,
+            set_spacing: 12,
+
+            gtk::Label {
+                set_label: &tr!("Out of time"),
+                add_css_class: "title-1",
+                set_wrap: true,
+                set_halign: gtk::Align::Start,
+            },
+
+            gtk::Label {
+                set_label: &tr!("You have no more VPN time left on this account. Either buy credit on our website or redeem a voucher."),
+                set_wrap: true,
+                set_halign: gtk::Align::Start,
+            },
+
+            #[name = "blocking_label"]
+            gtk::Label {
+                set_use_markup: true,
+                set_wrap: true,
+                set_halign: gtk::Align::Start,
+                set_margin_bottom: 8,
+            },
+
+            #[name = "buy_more_credit_button"]
+            gtk::Button {
+                set_label: &tr!("Buy more credit"),
+                set_css_classes: &["opaque", "login_btn"],
+            },
+
+            #[name = "redeem_voucher_button"]
+            gtk::Button {
+                set_label: &tr!("Redeem voucher"),
+                set_css_classes: &["opaque", "login_btn"],
+            },
+
+            #[name = "disconnect_button"]
+            gtk::Button {
+                set_css_classes: &["opaque", "logout_btn"],
+            },
+        }
+    }
+}
@@ -1,3 +1,5 @@
+use std::path::PathBuf;
+
 use adw::prelude::*;
 use relm4::prelude::*;
 
@@ -7,8 +9,15 @@ use mullvad_types::{
 };
 
 use crate::{
-    icon_names, tr,
-    ui::{app::AppInput, types::*, variant_selector::VariantSelectorMsg, widgets::InfoButton},
+    icon_names,
+    mullvad::DaemonError,
+    tr,
+    ui::{
+        app::{get_error_toast, AppInput},
+        types::*,
+        variant_selector::VariantSelectorMsg,
+        widgets::InfoButton,
+    },
 };
 
 use super::variant_selector::VariantSelector;
@@ -39,6 +48,16 @@ pub struct PreferencesModel {
     enable_ipv6: bool,
     auto_connect: bool,
     relay_settings: Option<RelaySettings>,
+
+    daemon_socket_path: Option<PathBuf>,
+    /// Set when the command line overrides the socket preference.
+    daemon_socket_override: Option<PathBuf>,
+}
+
+#[derive(Debug)]
+pub struct PreferencesInit {
+    pub daemon_socket_path: Option<PathBuf>,
+    pub daemon_socket_override: Option<PathBuf>,
 }
 
 #[derive(Debug)]
@@ -46,6 +65,8 @@ pub enum PreferencesMsg {
     Show,
     Close,
     UpdateSettings(Settings),
+    /// What failed and why.
+    ShowError(String, DaemonError),
     TunnelProtocolChanged(TunnelProtocol),
     WireGuardPortChanged(WireGuardPort),
     SetMultihop(bool),
@@ -99,6 +120,14 @@ impl PreferencesModel {
             .unwrap_or_default()
     }
 
+    fn get_daemon_socket_text(&self) -> String {
+        self.daemon_socket_override
+            .as_ref()
+            .or(self.daemon_socket_path.as_ref())
+            .map(|path| path.display().to_string())
+            .unwrap_or_default()
+    }
+
     fn is_multihop_allowed(&self) -> bool {
         self.get_tunnel_protocol()
             .map(|value| match value {
@@ -111,7 +140,7 @@ impl PreferencesModel {
 
 #[relm4::component(async, pub)]
 impl SimpleAsyncComponent for PreferencesModel {
-    type Init = ();
+    type Init = PreferencesInit;
     type Input = PreferencesMsg;
     type Output = AppInput;
     type Widgets = PreferencesWidgets;
@@ -398,13 +427,38 @@ impl SimpleAsyncComponent for PreferencesModel {
                             },
                         }
                     },
-                }
+                },
+
+                // Daemon.
+                add = &adw::PreferencesGroup {
+                    set_title: &tr!("Daemon"),
+
+                    add = &adw::EntryRow {
+                        set_title: &tr!("Management socket"),
+                        set_show_apply_button: true,
+                        set_text: &model.get_daemon_socket_text(),
+                        set_sensitive: model.daemon_socket_override.is_none(),
+                        set_tooltip_text: Some(&if model.daemon_socket_override.is_some() {
+                            tr!("Set with --socket on the command line.")
+                        } else {
+                            tr!("Leave empty for the default socket.")
+                        }),
+
+                        connect_apply[sender] => move |this| {
+                            let text = this.text();
+                            let path = Some(text.trim())
+                                .filter(|path| !path.is_empty())
+                                .map(PathBuf::from);
+                            let _ = sender.output(AppInput::SetDaemonSocketPath(path));
+                        },
+                    },
+                },
             }
         }
     }
 
     async fn init(
-        _: Self::Init,
+        init: Self::Init,
         root: Self::Root,
         sender: AsyncComponentSender<Self>,
     ) -> AsyncComponentParts<Self> {
@@ -425,6 +479,8 @@ impl SimpleAsyncComponent for PreferencesModel {
             local_network_sharing: false,
             lockdown_mode: false,
             relay_settings: None,
+            daemon_socket_path: init.daemon_socket_path,
+            daemon_socket_override: init.daemon_socket_override,
 
             tracker: Default::default(),
         };
@@ -460,6 +516,9 @@ impl SimpleAsyncComponent for PreferencesModel {
                 self.wireguard_port_selector
                     .emit(VariantSelectorMsg::SelectVariant(self.get_wireguard_port()));
             }
+            PreferencesMsg::ShowError(action, error) => {
+                self.window.add_toast(get_error_toast(&action, &error));
+            }
             PreferencesMsg::TunnelProtocolChanged(tunnel_protocol) => {
                 self.update_normal_relay_constraints(sender, |relay_constraints| {
                     relay_constraints.tunnel_protocol = tunnel_protocol.into()
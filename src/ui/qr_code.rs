@@ -0,0 +1,56 @@
+use gtk::cairo;
+use qrcode::{Color, QrCode};
+
+/// Empty modules around the code, the QR specification asks for four.
+const QUIET_ZONE: usize = 4;
+
+/// Returns a `gtk::DrawingArea` draw function rendering `text` as a QR code.
+pub fn get_draw_func(
+    text: Option<&str>,
+) -> impl Fn(&gtk::DrawingArea, &cairo::Context, i32, i32) + 'static {
+    let code = text.and_then(|text| {
+        QrCode::new(text)
+            .inspect_err(|err| log::warn!("Can't encode QR code: {err}"))
+            .ok()
+    });
+
+    move |_, cr, width, height| {
+        if let Some(code) = &code {
+            draw_qr_code(cr, width, height, code);
+        }
+    }
+}
+
+/// Draws black modules on white, centered and scaled to the smaller side.
+fn draw_qr_code(cr: &cairo::Context, width: i32, height: i32, code: &QrCode) {
+    let modules = code.width();
+    let size = width.min(height) as f64;
+    let module_size = (size / (modules + 2 * QUIET_ZONE) as f64).floor();
+    if module_size < 1.0 {
+        return;
+    }
+
+    let code_size = module_size * (modules + 2 * QUIET_ZONE) as f64;
+    let x0 = ((width as f64 - code_size) / 2.0).floor();
+    let y0 = ((height as f64 - code_size) / 2.0).floor();
+
+    // The code must stay readable in dark mode too.
+    cr.set_source_rgb(1.0, 1.0, 1.0);
+    cr.rectangle(x0, y0, code_size, code_size);
+    let _ = cr.fill();
+
+    cr.set_source_rgb(0.0, 0.0, 0.0);
+    for (index, color) in code.to_colors().into_iter().enumerate() {
+        if color == Color::Dark {
+            let x = (index % modules + QUIET_ZONE) as f64;
+            let y = (index / modules + QUIET_ZONE) as f64;
+            cr.rectangle(
+                x0 + x * module_size,
+                y0 + y * module_size,
+                module_size,
+                module_size,
+            );
+        }
+    }
+    let _ = cr.fill();
+}
@@ -0,0 +1,291 @@
+use adw::prelude::*;
+use chrono::{NaiveTime, Timelike, Weekday};
+use relm4::prelude::*;
+
+use crate::{
+    icon_names,
+    schedule::{Schedule, ScheduleRule, WEEKDAYS},
+    tr,
+};
+
+use super::app::AppInput;
+
+#[tracker::track]
+#[derive(Debug)]
+pub struct ScheduleModel {
+    window: adw::PreferencesWindow,
+
+    #[do_not_track]
+    rules_list: gtk::ListBox,
+
+    #[no_eq]
+    schedule: Schedule,
+
+    #[no_eq]
+    new_rule: ScheduleRule,
+}
+
+#[derive(Debug)]
+pub enum ScheduleMsg {
+    Show,
+    Close,
+    UpdateSchedule(Schedule),
+    SetEnabled(bool),
+    SetWeekday(Weekday, bool),
+    SetStart(NaiveTime),
+    SetEnd(NaiveTime),
+    AddRule,
+    RemoveRule(usize),
+}
+
+impl ScheduleModel {
+    fn render_rules(&self, sender: &AsyncComponentSender<Self>) {
+        self.rules_list.remove_all();
+
+        for (index, rule) in self.schedule.rules.iter().enumerate() {
+            relm4::view! {
+                #[name = "action_row"]
+                adw::ActionRow {
+                    set_title: &rule.get_weekdays_label(),
+                    set_subtitle: &rule.get_time_range_label(),
+                    add_css_class: "property",
+
+                    add_suffix = &gtk::Button {
+                        set_icon_name: icon_names::CROSS_LARGE_CIRCLE_FILLED,
+                        set_valign: gtk::Align::Center,
+                        set_css_classes: &["flat"],
+                        set_tooltip_text: Some(&tr!("Remove rule")),
+
+                        connect_clicked[sender] => move |_| {
+                            sender.input(ScheduleMsg::RemoveRule(index));
+                        }
+                    }
+                }
+            }
+            self.rules_list.append(&action_row);
+        }
+
+        self.rules_list.set_visible(!self.schedule.rules.is_empty());
+    }
+
+    fn output_schedule(&self, sender: &AsyncComponentSender<Self>) {
+        sender
+            .output(AppInput::SetSchedule(self.schedule.clone()))
+            .ok();
+    }
+}
+
+fn time_spin_button(max: f64) -> gtk::SpinButton {
+    let spin_button = gtk::SpinButton::with_range(0.0, max, 1.0);
+    spin_button.set_valign(gtk::Align::Center);
+    spin_button.set_orientation(gtk::Orientation::Vertical);
+    spin_button.set_wrap(true);
+    spin_button.connect_output(|this| {
+        this.set_text(&format!("{:02}", this.value_as_int()));
+        gtk::glib::Propagation::Stop
+    });
+    spin_button
+}
+
+fn time_picker<F>(time: NaiveTime, on_changed: F) -> gtk::Box
+where
+    F: Fn(NaiveTime) + Clone + 'static,
+{
+    let hours = time_spin_button(23.0);
+    let minutes = time_spin_button(59.0);
+    hours.set_value(time.hour() as f64);
+    minutes.set_value(time.minute() as f64);
+
+    for spin_button in [&hours, &minutes] {
+        let (hours, minutes, on_changed) = (hours.clone(), minutes.clone(), on_changed.clone());
+        spin_button.connect_value_changed(move |_| {
+            if let Some(time) = NaiveTime::from_hms_opt(
+                hours.value_as_int() as u32,
+                minutes.value_as_int() as u32,
+                0,
+            ) {
+                on_changed(time);
+            }
+        });
+    }
+
+    let picker = gtk::Box::new(gtk::Orientation::Horizontal, 4);
+    picker.append(&hours);
+    picker.append(&gtk::Label::new(Some(":")));
+    picker.append(&minutes);
+    picker
+}
+
+#[relm4::component(async, pub)]
+impl SimpleAsyncComponent for ScheduleModel {
+    type Init = Schedule;
+    type Input = ScheduleMsg;
+    type Output = AppInput;
+    type Widgets = ScheduleWidgets;
+
+    view! {
+        adw::PreferencesWindow {
+            set_title: Some(&tr!("Schedule")),
+            set_search_enabled: false,
+            connect_close_request[sender] => move |_| {
+                sender.input(ScheduleMsg::Close);
+                gtk::glib::Propagation::Stop
+            },
+            add = &adw::PreferencesPage {
+                add = &adw::PreferencesGroup {
+                    add = &adw::SwitchRow {
+                        set_title: &tr!("Connection schedule"),
+                        set_subtitle: &tr!("Connect during the time ranges below and disconnect outside of them. Manual changes last until the next scheduled change."),
+
+                        #[track = "model.changed(ScheduleModel::schedule())"]
+                        #[block_signal(enabled_active_notify_handler)]
+                        set_active: model.schedule.enabled,
+
+                        connect_active_notify[sender] => move |this| {
+                            sender.input(ScheduleMsg::SetEnabled(this.is_active()));
+                        } @enabled_active_notify_handler
+                    },
+                },
+
+                add = &adw::PreferencesGroup {
+                    set_title: &tr!("Connected"),
+
+                    #[local_ref]
+                    add = rules_list -> gtk::ListBox {
+                        add_css_class: "boxed-list",
+                        set_selection_mode: gtk::SelectionMode::None,
+                    },
+                },
+
+                add = &adw::PreferencesGroup {
+                    set_title: &tr!("New rule"),
+
+                    add = &adw::ActionRow {
+                        set_title: &tr!("Days"),
+
+                        #[name = "weekdays_box"]
+                        add_suffix = &gtk::Box {
+                            add_css_class: "linked",
+                            set_valign: gtk::Align::Center,
+                        },
+                    },
+
+                    #[name = "start_row"]
+                    add = &adw::ActionRow {
+                        set_title: &tr!("From"),
+                    },
+
+                    #[name = "end_row"]
+                    add = &adw::ActionRow {
+                        set_title: &tr!("To"),
+                    },
+
+                    add = &gtk::Button {
+                        set_margin_top: 12,
+                        set_label: &tr!("Add rule"),
+                        add_css_class: "suggested-action",
+
+                        #[track = "model.changed(ScheduleModel::new_rule())"]
+                        set_sensitive: !model.new_rule.weekdays.is_empty(),
+
+                        connect_clicked[sender] => move |_| {
+                            sender.input(ScheduleMsg::AddRule);
+                        },
+                    },
+                },
+            }
+        }
+    }
+
+    async fn init(
+        schedule: Self::Init,
+        root: Self::Root,
+        sender: AsyncComponentSender<Self>,
+    ) -> AsyncComponentParts<Self> {
+        let model = ScheduleModel {
+            window: root.clone(),
+            rules_list: gtk::ListBox::new(),
+            schedule,
+            new_rule: ScheduleRule::default(),
+            tracker: Default::default(),
+        };
+
+        let rules_list = &model.rules_list;
+
+        let widgets = view_output!();
+
+        for weekday in WEEKDAYS {
+            let sender = sender.clone();
+            let button = gtk::ToggleButton::builder()
+                .label(weekday.to_string())
+                .active(model.new_rule.weekdays.contains(&weekday))
+                .build();
+            button.connect_toggled(move |this| {
+                sender.input(ScheduleMsg::SetWeekday(weekday, this.is_active()));
+            });
+            widgets.weekdays_box.append(&button);
+        }
+
+        {
+            let sender = sender.clone();
+            widgets
+                .start_row
+                .add_suffix(&time_picker(model.new_rule.start, move |time| {
+                    sender.input(ScheduleMsg::SetStart(time))
+                }));
+        }
+        {
+            let sender = sender.clone();
+            widgets
+                .end_row
+                .add_suffix(&time_picker(model.new_rule.end, move |time| {
+                    sender.input(ScheduleMsg::SetEnd(time))
+                }));
+        }
+
+        model.render_rules(&sender);
+
+        AsyncComponentParts { model, widgets }
+    }
+
+    async fn update(&mut self, message: Self::Input, sender: AsyncComponentSender<Self>) {
+        self.reset();
+
+        log::debug!("ScheduleMsg: {message:#?}");
+
+        match message {
+            ScheduleMsg::Show => self.window.present(),
+            ScheduleMsg::Close => self.window.set_visible(false),
+            ScheduleMsg::UpdateSchedule(schedule) => {
+                self.set_schedule(schedule);
+                self.render_rules(&sender);
+            }
+            ScheduleMsg::SetEnabled(enabled) => {
+                self.get_mut_schedule().enabled = enabled;
+                self.output_schedule(&sender);
+            }
+            ScheduleMsg::SetWeekday(weekday, active) => {
+                let weekdays = &mut self.get_mut_new_rule().weekdays;
+                weekdays.retain(|day| *day != weekday);
+                if active {
+                    weekdays.push(weekday);
+                }
+            }
+            ScheduleMsg::SetStart(time) => self.get_mut_new_rule().start = time,
+            ScheduleMsg::SetEnd(time) => self.get_mut_new_rule().end = time,
+            ScheduleMsg::AddRule => {
+                let rule = self.new_rule.clone();
+                self.get_mut_schedule().rules.push(rule);
+                self.render_rules(&sender);
+                self.output_schedule(&sender);
+            }
+            ScheduleMsg::RemoveRule(index) => {
+                if index < self.schedule.rules.len() {
+                    self.get_mut_schedule().rules.remove(index);
+                    self.render_rules(&sender);
+                    self.output_schedule(&sender);
+                }
+            }
+        }
+    }
+}
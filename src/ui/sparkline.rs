@@ -0,0 +1,64 @@
+use std::collections::VecDeque;
+
+use gtk::cairo;
+
+use crate::traffic::{TrafficRate, HISTORY_LENGTH};
+
+type Color = (f64, f64, f64);
+
+/// Adwaita palette Green 4 and Blue 3.
+const LIGHT_COLORS: (Color, Color) = ((0.18, 0.76, 0.49), (0.21, 0.52, 0.89));
+/// Adwaita palette Green 2 and Blue 2, readable on a dark background.
+const DARK_COLORS: (Color, Color) = ((0.34, 0.89, 0.54), (0.38, 0.63, 0.92));
+
+/// Draws the download and upload rates as two lines scaled to the peak rate.
+pub fn draw_traffic_sparkline(
+    cr: &cairo::Context,
+    width: i32,
+    height: i32,
+    history: &VecDeque<TrafficRate>,
+) {
+    let (rx_color, tx_color) = if adw::StyleManager::default().is_dark() {
+        DARK_COLORS
+    } else {
+        LIGHT_COLORS
+    };
+
+    let peak = history
+        .iter()
+        .map(|rate| rate.rx_bytes_per_sec.max(rate.tx_bytes_per_sec))
+        .fold(0.0, f64::max);
+
+    if history.len() < 2 || peak <= 0.0 {
+        return;
+    }
+
+    let (width, height) = (width as f64, height as f64);
+    let step = width / (HISTORY_LENGTH - 1) as f64;
+    // Newest sample on the right edge.
+    let offset = width - step * (history.len() - 1) as f64;
+
+    let series: [(fn(&TrafficRate) -> f64, _); 2] = [
+        (|rate| rate.rx_bytes_per_sec, rx_color),
+        (|rate| rate.tx_bytes_per_sec, tx_color),
+    ];
+
+    cr.set_line_width(1.5);
+    cr.set_line_join(cairo::LineJoin::Round);
+
+    for (get_value, (r, g, b)) in series {
+        for (i, rate) in history.iter().enumerate() {
+            let x = offset + step * i as f64;
+            let y = height - 1.0 - (height - 2.0) * get_value(rate) / peak;
+            if i == 0 {
+                cr.move_to(x, y);
+            } else {
+                cr.line_to(x, y);
+            }
+        }
+        cr.set_source_rgb(r, g, b);
+        if let Err(err) = cr.stroke() {
+            log::debug!("Can't draw the traffic sparkline: {err}");
+        }
+    }
+}
@@ -0,0 +1,196 @@
+use adw::prelude::*;
+use chrono::Local;
+use mullvad_types::{
+    account::AccountNumber,
+    device::{Device, DeviceId},
+};
+use relm4::prelude::*;
+use relm4::SimpleComponent;
+
+use crate::{mullvad::MAX_DEVICES, tr};
+
+/// Whether removing `selected` of the account's `devices` frees a place for this one.
+pub fn can_login(devices: usize, selected: usize) -> bool {
+    selected > 0 && devices.saturating_sub(selected) < MAX_DEVICES
+}
+
+#[tracker::track]
+#[derive(Debug)]
+pub struct TooManyDevicesDialog {
+    dialog: adw::Dialog,
+
+    #[do_not_track]
+    devices_list: gtk::ListBox,
+
+    #[do_not_track]
+    account: AccountNumber,
+
+    #[no_eq]
+    devices: Vec<Device>,
+
+    #[no_eq]
+    selected: Vec<DeviceId>,
+}
+
+#[derive(Debug)]
+pub enum TooManyDevicesMsg {
+    Open {
+        account: AccountNumber,
+        devices: Vec<Device>,
+        parent: gtk::Widget,
+    },
+    Select(DeviceId, bool),
+    RemoveAndLogin,
+}
+
+#[derive(Debug)]
+pub struct TooManyDevicesOutput {
+    pub account: AccountNumber,
+    pub devices: Vec<DeviceId>,
+}
+
+impl TooManyDevicesDialog {
+    fn can_login(&self) -> bool {
+        can_login(self.devices.len(), self.selected.len())
+    }
+
+    fn render_devices(&self, sender: &ComponentSender<Self>) {
+        self.devices_list.remove_all();
+
+        for device in &self.devices {
+            let created = device.created.with_timezone(Local::now().offset());
+            let id = device.id.clone();
+
+            relm4::view! {
+                #[name = "action_row"]
+                adw::ActionRow {
+                    set_title: &device.pretty_name(),
+                    set_subtitle: &tr!("Created {}", created.format("%Y-%m-%d")),
+                    set_activatable: true,
+
+                    #[name = "check_button"]
+                    add_prefix = &gtk::CheckButton {
+                        connect_toggled[sender] => move |this| {
+                            sender.input(TooManyDevicesMsg::Select(id.clone(), this.is_active()));
+                        },
+                    },
+
+                    connect_activated[check_button] => move |_| {
+                        check_button.emit_activate();
+                    },
+                }
+            }
+            self.devices_list.append(&action_row);
+        }
+    }
+}
+
+#[relm4::component(pub)]
+impl SimpleComponent for TooManyDevicesDialog {
+    type Input = TooManyDevicesMsg;
+    type Output = TooManyDevicesOutput;
+    type Init = ();
+    type Widgets = TooManyDevicesWidgets;
+
+    view! {
+        adw::Dialog {
+            set_width_request: 360,
+            set_title: &tr!("Too many devices"),
+
+            #[wrap(Some)]
+            set_child = &gtk::Box {
+                set_orientation: gtk::Orientation::Vertical,
+
+                adw::HeaderBar {
+                    add_css_class: "flat",
+                },
+
+                gtk::Box {
+                    set_orientation: gtk::Orientation::Vertical,
+                    set_margin_all: 12,
+                    set_spacing: 12,
+
+                    gtk::Label {
+                        set_label: &tr!("You can have up to {} devices logged in on one Mullvad account. Remove at least one device to log in on this one.", MAX_DEVICES),
+                        set_wrap: true,
+                        set_halign: gtk::Align::Start,
+                    },
+
+                    #[local_ref]
+                    devices_list -> gtk::ListBox {
+                        add_css_class: "boxed-list",
+                        set_selection_mode: gtk::SelectionMode::None,
+                    },
+
+                    gtk::Button {
+                        set_label: &tr!("Remove selected and log in"),
+                        set_css_classes: &["destructive-action"],
+
+                        #[track = "model.changed(Self::selected())"]
+                        set_sensitive: model.can_login(),
+
+                        connect_clicked[sender] => move |_| {
+                            sender.input(TooManyDevicesMsg::RemoveAndLogin);
+                        },
+                    },
+                },
+            },
+        }
+    }
+
+    fn init(
+        _: Self::Init,
+        root: Self::Root,
+        sender: ComponentSender<Self>,
+    ) -> ComponentParts<Self> {
+        let model = TooManyDevicesDialog {
+            dialog: root.clone(),
+            devices_list: gtk::ListBox::new(),
+            account: AccountNumber::default(),
+            devices: vec![],
+            selected: vec![],
+            tracker: 0,
+        };
+
+        let devices_list = &model.devices_list;
+
+        let widgets = view_output!();
+
+        ComponentParts { model, widgets }
+    }
+
+    fn update(&mut self, message: Self::Input, sender: ComponentSender<Self>) {
+        self.reset();
+
+        match message {
+            TooManyDevicesMsg::Open {
+                account,
+                devices,
+                parent,
+            } => {
+                self.account = account;
+                self.set_devices(devices);
+                self.set_selected(vec![]);
+                self.render_devices(&sender);
+
+                self.dialog.present(Some(&parent));
+            }
+            TooManyDevicesMsg::Select(id, selected) => {
+                let selected_ids = self.get_mut_selected();
+                selected_ids.retain(|selected_id| *selected_id != id);
+                if selected {
+                    selected_ids.push(id);
+                }
+            }
+            TooManyDevicesMsg::RemoveAndLogin => {
+                if self.can_login() {
+                    self.dialog.close();
+                    let _ = sender.output(TooManyDevicesOutput {
+                        account: self.account.clone(),
+                        devices: self.selected.clone(),
+                    });
+                }
+            }
+        }
+    }
+}
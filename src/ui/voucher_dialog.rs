@@ -0,0 +1,217 @@
+use adw::prelude::*;
+use chrono::Local;
+use mullvad_types::account::VoucherSubmission;
+use relm4::prelude::*;
+
+use crate::tr;
+
+use super::{app::AppInput, extensions::EntryExt as _};
+
+const VOUCHER_CODE_LENGTH: usize = 16;
+
+#[tracker::track]
+#[derive(Debug)]
+pub struct VoucherDialog {
+    dialog: adw::Dialog,
+
+    #[no_eq]
+    code: String,
+    state: VoucherState,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum VoucherState {
+    Editing,
+    Submitting,
+    Redeemed(String),
+    Failed(String),
+}
+
+#[derive(Debug)]
+pub enum VoucherDialogMsg {
+    Open(gtk::Widget),
+    TextChanged(String),
+    Submit,
+    Redeemed(VoucherSubmission),
+    Failed(String),
+}
+
+/// Keeps only the characters a voucher code can have, in upper case.
+fn normalize_voucher_char(c: char) -> Option<char> {
+    c.is_ascii_alphanumeric().then(|| c.to_ascii_uppercase())
+}
+
+impl VoucherDialog {
+    fn is_code_valid(&self) -> bool {
+        self.code.len() == VOUCHER_CODE_LENGTH
+    }
+
+    fn can_submit(&self) -> bool {
+        self.is_code_valid() && !matches!(self.state, VoucherState::Submitting)
+    }
+
+    fn get_message(&self) -> Option<&str> {
+        match &self.state {
+            VoucherState::Redeemed(message) | VoucherState::Failed(message) => Some(message),
+            _ => None,
+        }
+    }
+}
+
+#[relm4::component(pub)]
+impl Component for VoucherDialog {
+    type CommandOutput = ();
+    type Input = VoucherDialogMsg;
+    type Output = AppInput;
+    type Init = ();
+    type Widgets = VoucherDialogWidgets;
+
+    view! {
+        adw::Dialog {
+            set_width_request: 340,
+            set_title: &tr!("Redeem voucher"),
+
+            #[wrap(Some)]
+            set_child = &gtk::Box {
+                set_orientation: gtk::Orientation::Vertical,
+
+                adw::HeaderBar {
+                    add_css_class: "flat",
+                },
+
+                gtk::Box {
+                    set_orientation: gtk::Orientation::Vertical,
+                    set_margin_all: 12,
+                    set_spacing: 12,
+
+                    #[name = "entry"]
+                    gtk::Entry {
+                        set_placeholder_text: Some("XXXX-XXXX-XXXX-XXXX"),
+                        set_max_length: 19,
+                        add_css_class: "monospace",
+
+                        #[track = "model.changed(VoucherDialog::state())"]
+                        set_sensitive: !matches!(model.state, VoucherState::Submitting),
+
+                        #[track = "model.changed(VoucherDialog::state())"]
+                        set_class_active[matches!(model.state, VoucherState::Failed(_))]: "error",
+
+                        connect_activate[sender] => move |_| {
+                            sender.input(VoucherDialogMsg::Submit);
+                        },
+                    },
+
+                    gtk::Label {
+                        set_wrap: true,
+                        set_halign: gtk::Align::Start,
+
+                        #[track = "model.changed(VoucherDialog::state())"]
+                        set_label: model.get_message().unwrap_or_default(),
+
+                        #[track = "model.changed(VoucherDialog::state())"]
+                        set_visible: model.get_message().is_some(),
+
+                        #[track = "model.changed(VoucherDialog::state())"]
+                        set_class_active[matches!(model.state, VoucherState::Failed(_))]: "error",
+
+                        #[track = "model.changed(VoucherDialog::state())"]
+                        set_class_active[matches!(model.state, VoucherState::Redeemed(_))]: "success",
+                    },
+
+                    gtk::Stack {
+                        #[track = "model.changed(VoucherDialog::state())"]
+                        set_visible_child_name: if matches!(model.state, VoucherState::Submitting) { "submitting" } else { "default" },
+
+                        add_named[Some("submitting")] = &gtk::Spinner {
+                            set_spinning: true,
+                        },
+
+                        add_named[Some("default")] = &gtk::Button {
+                            set_label: &tr!("Redeem"),
+                            set_css_classes: &["suggested-action"],
+
+                            #[track = "model.changed(VoucherDialog::code()) || model.changed(VoucherDialog::state())"]
+                            set_sensitive: model.can_submit(),
+
+                            connect_clicked[sender] => move |_| {
+                                sender.input(VoucherDialogMsg::Submit);
+                            },
+                        },
+                    },
+                },
+            },
+        }
+    }
+
+    fn init(
+        _: Self::Init,
+        root: Self::Root,
+        sender: ComponentSender<Self>,
+    ) -> ComponentParts<Self> {
+        let model = VoucherDialog {
+            dialog: root.clone(),
+            code: String::new(),
+            state: VoucherState::Editing,
+            tracker: 0,
+        };
+
+        let widgets = view_output!();
+
+        widgets.entry.enable_grouped_code_behavior(
+            VOUCHER_CODE_LENGTH,
+            '-',
+            normalize_voucher_char,
+            move |code| sender.input(VoucherDialogMsg::TextChanged(code.to_owned())),
+        );
+
+        ComponentParts { model, widgets }
+    }
+
+    fn update_with_view(
+        &mut self,
+        widgets: &mut Self::Widgets,
+        message: Self::Input,
+        sender: ComponentSender<Self>,
+        root: &Self::Root,
+    ) {
+        self.reset();
+
+        match message {
+            VoucherDialogMsg::Open(parent) => {
+                self.set_code(String::new());
+                self.set_state(VoucherState::Editing);
+                widgets.entry.set_text("");
+
+                root.present(Some(&parent));
+            }
+            VoucherDialogMsg::TextChanged(code) => {
+                if matches!(self.state, VoucherState::Failed(_)) {
+                    self.set_state(VoucherState::Editing);
+                }
+                self.set_code(code);
+            }
+            VoucherDialogMsg::Submit => {
+                if self.can_submit() {
+                    self.set_state(VoucherState::Submitting);
+                    let _ = sender.output(AppInput::RedeemVoucher(self.code.clone()));
+                }
+            }
+            VoucherDialogMsg::Redeemed(submission) => {
+                let days_added = submission.time_added / (24 * 60 * 60);
+                let new_expiry = submission
+                    .new_expiry
+                    .with_timezone(Local::now().offset())
+                    .format("%Y-%m-%d %H:%M");
+                self.set_state(VoucherState::Redeemed(format!(
+                    "{} {}",
+                    tr!("1 day was added to your account."
+                        | "{n} days were added to your account." % days_added),
+                    tr!("Paid until {}.", new_expiry)
+                )));
+            }
+            VoucherDialogMsg::Failed(error) => self.set_state(VoucherState::Failed(error)),
+        }
+
+        self.update_view(widgets, sender);
+    }
+}